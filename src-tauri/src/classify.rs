@@ -0,0 +1,461 @@
+use std::io::Cursor;
+
+use serde::{Deserialize, Serialize};
+
+use crate::date::{self, DateOptions};
+use crate::structured::{self, DataFormat};
+
+/// Structured kind of a clipboard item, computed once at insert time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ContentKind {
+    Text,
+    Image,
+    Url,
+    Email,
+    Path,
+    Json,
+    Yaml,
+    Toml,
+    Env,
+    Color,
+    Date,
+    Number,
+    Code,
+    Phone,
+    Uuid,
+}
+
+impl ContentKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ContentKind::Text => "text",
+            ContentKind::Image => "image",
+            ContentKind::Url => "url",
+            ContentKind::Email => "email",
+            ContentKind::Path => "path",
+            ContentKind::Json => "json",
+            ContentKind::Yaml => "yaml",
+            ContentKind::Toml => "toml",
+            ContentKind::Env => "env",
+            ContentKind::Color => "color",
+            ContentKind::Date => "date",
+            ContentKind::Number => "number",
+            ContentKind::Code => "code",
+            ContentKind::Phone => "phone",
+            ContentKind::Uuid => "uuid",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Classification {
+    pub kind: ContentKind,
+    /// Best-guess language when `kind` is `Code`
+    pub language: Option<&'static str>,
+    /// RFC 3339 UTC representation when `kind` is `Date`
+    pub detected_date: Option<String>,
+    /// Hex representation when `kind` is `Color`
+    pub detected_color: Option<String>,
+}
+
+impl Classification {
+    fn of(kind: ContentKind) -> Self {
+        Self {
+            kind,
+            language: None,
+            detected_date: None,
+            detected_color: None,
+        }
+    }
+}
+
+/// Classifies clipboard content. Single-line checks run from most to least specific,
+/// so e.g. `123` is a number rather than the shorthand hex color `#112233`.
+pub fn classify(
+    content_type: &str,
+    text: Option<&str>,
+    date_options: &DateOptions,
+) -> Classification {
+    if content_type == "image" {
+        return Classification::of(ContentKind::Image);
+    }
+
+    let Some(text) = text else {
+        return Classification::of(ContentKind::Text);
+    };
+    let trimmed = text.trim();
+    if trimmed.is_empty() {
+        return Classification::of(ContentKind::Text);
+    }
+
+    let format = structured::detect_format(trimmed);
+    if format == Some(DataFormat::Json) {
+        return Classification::of(ContentKind::Json);
+    }
+
+    if !trimmed.contains('\n') {
+        if is_uuid(trimmed) {
+            return Classification::of(ContentKind::Uuid);
+        }
+        if is_url(trimmed) {
+            return Classification::of(ContentKind::Url);
+        }
+        if is_email(trimmed) {
+            return Classification::of(ContentKind::Email);
+        }
+        if let Some(date) = date::detect(trimmed, date_options) {
+            return Classification {
+                detected_date: Some(date),
+                ..Classification::of(ContentKind::Date)
+            };
+        }
+        if is_number(trimmed) {
+            return Classification::of(ContentKind::Number);
+        }
+        if let Some(color) = detect_color(trimmed) {
+            return Classification {
+                detected_color: Some(color),
+                ..Classification::of(ContentKind::Color)
+            };
+        }
+        if is_phone(trimmed) {
+            return Classification::of(ContentKind::Phone);
+        }
+        if is_path(trimmed) {
+            return Classification::of(ContentKind::Path);
+        }
+    }
+
+    if is_env(text) {
+        return Classification::of(ContentKind::Env);
+    }
+
+    if format == Some(DataFormat::Toml) {
+        return Classification::of(ContentKind::Toml);
+    }
+
+    // Code goes before YAML: `def f():` followed by an indented body parses as a mapping
+    if let Some(language) = detect_code_language(text) {
+        return Classification {
+            language: Some(language),
+            ..Classification::of(ContentKind::Code)
+        };
+    }
+
+    if format == Some(DataFormat::Yaml) {
+        return Classification::of(ContentKind::Yaml);
+    }
+
+    Classification::of(ContentKind::Text)
+}
+
+pub fn is_env(text: &str) -> bool {
+    let cursor = Cursor::new(text.as_bytes());
+    let iter = dotenvy::from_read_iter(cursor);
+    let mut valid_count = 0;
+    for result in iter {
+        match result {
+            Ok(_) => valid_count += 1,
+            Err(_) => return false,
+        }
+    }
+    valid_count > 0
+}
+
+/// Detects if text is a CSS color value. Returns the hex representation if detected.
+/// Supports: hex, rgb/rgba, hsl/hsla, hwb, lab, lch, oklab, oklch, and named CSS colors.
+pub fn detect_color(text: &str) -> Option<String> {
+    let text = text.trim();
+    // Reject strings that are too long or contain newlines (not a color)
+    if text.len() > 100 || text.contains('\n') {
+        return None;
+    }
+    csscolorparser::parse(text)
+        .ok()
+        .map(|color| color.to_hex_string())
+}
+
+fn is_uuid(text: &str) -> bool {
+    let text = text
+        .strip_prefix('{')
+        .and_then(|t| t.strip_suffix('}'))
+        .unwrap_or(text);
+    let groups: Vec<&str> = text.split('-').collect();
+    groups.len() == 5
+        && groups
+            .iter()
+            .zip([8, 4, 4, 4, 12])
+            .all(|(g, len)| g.len() == len && g.chars().all(|c| c.is_ascii_hexdigit()))
+}
+
+fn is_url(text: &str) -> bool {
+    if text.contains(char::is_whitespace) {
+        return false;
+    }
+    match url::Url::parse(text) {
+        Ok(u) => {
+            matches!(u.scheme(), "http" | "https" | "ftp" | "ws" | "wss" | "file")
+                && (u.scheme() == "file" || u.host_str().is_some())
+        }
+        Err(_) => text.starts_with("www.") && text[4..].contains('.'),
+    }
+}
+
+fn is_email(text: &str) -> bool {
+    let text = text.strip_prefix("mailto:").unwrap_or(text);
+    if text.contains(char::is_whitespace) {
+        return false;
+    }
+    let Some((local, domain)) = text.split_once('@') else {
+        return false;
+    };
+    !local.is_empty()
+        && !domain.contains('@')
+        && domain.contains('.')
+        && !domain.starts_with('.')
+        && !domain.ends_with('.')
+}
+
+fn is_number(text: &str) -> bool {
+    if let Some(hex) = text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        return !hex.is_empty() && hex.chars().all(|c| c.is_ascii_hexdigit());
+    }
+    let normalized: String = text.chars().filter(|c| *c != '_').collect();
+    // Reject "inf", "NaN" and friends which f64 parsing accepts
+    normalized.chars().any(|c| c.is_ascii_digit()) && normalized.parse::<f64>().is_ok()
+}
+
+fn is_phone(text: &str) -> bool {
+    if !text
+        .chars()
+        .all(|c| c.is_ascii_digit() || matches!(c, '+' | ' ' | '-' | '(' | ')' | '.'))
+    {
+        return false;
+    }
+    let digits = text.chars().filter(|c| c.is_ascii_digit()).count();
+    let has_separator = text.starts_with('+') || text.contains([' ', '-', '(']);
+    (7..=15).contains(&digits) && has_separator
+}
+
+fn is_path(text: &str) -> bool {
+    if text.len() > 4096 {
+        return false;
+    }
+    let unix = (text.starts_with('/') && !text.starts_with("//") && text.len() > 1)
+        || text.starts_with("~/")
+        || text.starts_with("./")
+        || text.starts_with("../");
+    let bytes = text.as_bytes();
+    let windows = bytes.len() > 2
+        && bytes[0].is_ascii_alphabetic()
+        && bytes[1] == b':'
+        && (bytes[2] == b'\\' || bytes[2] == b'/');
+    unix || windows
+}
+
+/// Hints for one language. Keywords that also start ordinary sentences only
+/// count at the start of a line; punctuation-heavy tokens count anywhere.
+struct LanguageHints {
+    language: &'static str,
+    /// Matched against each line after its indentation
+    line_starts: &'static [&'static str],
+    tokens: &'static [&'static str],
+}
+
+/// A language needs at least two distinct hits to count.
+const LANGUAGE_HINTS: &[LanguageHints] = &[
+    LanguageHints {
+        language: "rust",
+        line_starts: &[
+            "fn ",
+            "pub fn ",
+            "impl ",
+            "use std::",
+            "let mut ",
+            "#[derive",
+            "pub struct ",
+        ],
+        tokens: &["::<", "-> Result<", "&mut ", "println!(", "Some(", "=> {"],
+    },
+    LanguageHints {
+        language: "python",
+        line_starts: &["def ", "import ", "class ", "elif ", "print("],
+        tokens: &["self.", "__init__", "None:", "lambda "],
+    },
+    LanguageHints {
+        language: "typescript",
+        line_starts: &["interface ", "export type ", "readonly "],
+        tokens: &[": string", ": number", ": boolean", "as const"],
+    },
+    LanguageHints {
+        language: "javascript",
+        line_starts: &["const ", "let ", "function ", "export default"],
+        tokens: &["=> {", "console.log", "require(", "===", "!=="],
+    },
+    LanguageHints {
+        language: "go",
+        line_starts: &["func ", "package ", "go func"],
+        tokens: &[":= ", "fmt.", "err != nil"],
+    },
+    LanguageHints {
+        language: "sql",
+        line_starts: &[],
+        tokens: &[
+            "SELECT ",
+            "FROM ",
+            "WHERE ",
+            "INSERT INTO",
+            "UPDATE ",
+            "CREATE TABLE",
+            "JOIN ",
+            "GROUP BY",
+        ],
+    },
+    LanguageHints {
+        language: "shell",
+        line_starts: &["#!/bin/", "echo ", "sudo ", "export "],
+        tokens: &["$(", "${", "&& ", " | grep"],
+    },
+    LanguageHints {
+        language: "html",
+        line_starts: &[],
+        tokens: &[
+            "<div",
+            "</",
+            "<html",
+            "<span",
+            "class=\"",
+            "<!DOCTYPE",
+            "<body",
+        ],
+    },
+    LanguageHints {
+        language: "css",
+        line_starts: &["color:", "margin:", "padding:", "display:", "font-size:"],
+        tokens: &["px;", "@media"],
+    },
+    LanguageHints {
+        language: "c",
+        line_starts: &["#include", "int main(", "void "],
+        tokens: &["printf(", "malloc(", "sizeof("],
+    },
+    LanguageHints {
+        language: "java",
+        line_starts: &["private "],
+        tokens: &[
+            "public class",
+            "public static void",
+            "System.out",
+            "import java.",
+            "@Override",
+        ],
+    },
+];
+
+fn detect_code_language(text: &str) -> Option<&'static str> {
+    let lines: Vec<&str> = text.lines().map(str::trim_start).collect();
+    // A colon only counts when it ends a line, as after a Python `def`
+    let has_structure =
+        text.contains(['{', ';', '(', '<']) || lines.iter().any(|l| l.trim_end().ends_with(':'));
+    if !has_structure {
+        return None;
+    }
+
+    LANGUAGE_HINTS
+        .iter()
+        .map(|hints| {
+            let starts = hints
+                .line_starts
+                .iter()
+                .filter(|h| lines.iter().any(|l| l.starts_with(*h)))
+                .count();
+            let tokens = hints.tokens.iter().filter(|h| text.contains(*h)).count();
+            (hints.language, starts + tokens)
+        })
+        .filter(|(_, score)| *score >= 2)
+        .fold(None, |best: Option<(&str, usize)>, candidate| match best {
+            Some(b) if b.1 >= candidate.1 => Some(b),
+            _ => Some(candidate),
+        })
+        .map(|(lang, _)| lang)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kind(text: &str) -> ContentKind {
+        classify("text", Some(text), &DateOptions { day_first: false }).kind
+    }
+
+    #[test]
+    fn single_line_checks_run_most_specific_first() {
+        assert_eq!(
+            kind("123e4567-e89b-12d3-a456-426614174000"),
+            ContentKind::Uuid
+        );
+        assert_eq!(kind("https://example.com/a?b=c"), ContentKind::Url);
+        assert_eq!(kind("someone@example.com"), ContentKind::Email);
+        assert_eq!(kind("2026-03-28"), ContentKind::Date);
+        // A plain number, not the shorthand color #112233
+        assert_eq!(kind("123"), ContentKind::Number);
+        assert_eq!(kind("+1 555 123 4567"), ContentKind::Phone);
+        assert_eq!(kind("~/projects/notes.md"), ContentKind::Path);
+        assert_eq!(kind("hello there"), ContentKind::Text);
+    }
+
+    #[test]
+    fn colors_and_dates_carry_their_values() {
+        let options = DateOptions { day_first: false };
+        let color = classify("text", Some("red"), &options);
+        assert_eq!(color.kind, ContentKind::Color);
+        assert_eq!(color.detected_color.as_deref(), Some("#ff0000"));
+
+        let date = classify("text", Some("2026-03-28T14:30:00Z"), &options);
+        assert_eq!(date.kind, ContentKind::Date);
+        assert_eq!(
+            date.detected_date.as_deref(),
+            Some("2026-03-28T14:30:00+00:00")
+        );
+    }
+
+    #[test]
+    fn documents_and_code() {
+        assert_eq!(kind("{\"a\": [1, 2]}"), ContentKind::Json);
+        assert_eq!(kind("API_KEY=abc\nDEBUG=true"), ContentKind::Env);
+        assert_eq!(kind("[server]\nport = 8080"), ContentKind::Toml);
+        assert_eq!(
+            kind("name: app\nitems:\n  - one\n  - two"),
+            ContentKind::Yaml
+        );
+
+        let rust = classify(
+            "text",
+            Some("fn main() {\n    let mut total = 0;\n    println!(\"{}\", total);\n}"),
+            &DateOptions { day_first: false },
+        );
+        assert_eq!(rust.kind, ContentKind::Code);
+        assert_eq!(rust.language, Some("rust"));
+        // Code before YAML: an indented Python body also parses as a mapping
+        assert_eq!(
+            detect_code_language("def area(self):\n    return self.width"),
+            Some("python")
+        );
+    }
+
+    #[test]
+    fn malformed_or_prose_input_stays_text() {
+        assert_eq!(kind("{\"a\": 1"), ContentKind::Text);
+        assert_eq!(kind("   "), ContentKind::Text);
+        assert_eq!(kind("Note: call the plumber"), ContentKind::Text);
+        assert_eq!(
+            kind("Let me know when you're free (after lunch)"),
+            ContentKind::Text
+        );
+        assert_eq!(
+            classify("image", None, &DateOptions { day_first: false }).kind,
+            ContentKind::Image
+        );
+    }
+}
@@ -1,6 +1,7 @@
 use super::wayland;
 use super::x11::X11Clipboard;
 use crate::commands::is_cosmic_data_control_enabled;
+use crate::image_ops::{decode_png_to_rgba, encode_rgba_to_png, normalize_to_png};
 use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
 use std::collections::hash_map::DefaultHasher;
 use std::hash::{Hash, Hasher};
@@ -73,7 +74,7 @@ impl ClipboardManager {
     pub async fn read_image(&self) -> Result<Option<(String, u32, u32)>, String> {
         if self.is_wayland {
             match wayland::read_image().await? {
-                Some(png_bytes) => self.process_image_bytes_png(png_bytes),
+                Some(bytes) => self.process_image_bytes(bytes),
                 None => {
                     self.clear_image_cache();
                     Ok(None)
@@ -106,19 +107,14 @@ impl ClipboardManager {
         }
     }
 
-    fn process_image_bytes_png(&self, png_bytes: Vec<u8>) -> Result<Option<(String, u32, u32)>, String> {
-        let hash = hash_bytes(&png_bytes);
+    /// Normalizes image bytes from wl-paste (PNG, JPEG, WebP, ...) to PNG.
+    fn process_image_bytes(&self, bytes: Vec<u8>) -> Result<Option<(String, u32, u32)>, String> {
+        let hash = hash_bytes(&bytes);
         if let Some(cached) = self.get_cached_image(hash) {
             return Ok(Some(cached));
         }
 
-        let decoder = png::Decoder::new(std::io::Cursor::new(&png_bytes));
-        let reader = decoder
-            .read_info()
-            .map_err(|e| format!("Failed to decode PNG: {}", e))?;
-        let info = reader.info();
-        let width = info.width;
-        let height = info.height;
+        let (png_bytes, width, height) = normalize_to_png(bytes)?;
 
         let base64_data = BASE64.encode(&png_bytes);
         self.set_image_cache(hash, base64_data.clone(), width, height);
@@ -196,82 +192,3 @@ impl ClipboardManager {
         }
     }
 }
-
-/// Encode RGBA bytes to PNG format
-fn encode_rgba_to_png(rgba_bytes: &[u8], width: u32, height: u32) -> Result<Vec<u8>, String> {
-    let mut png_bytes = Vec::new();
-    {
-        let mut encoder = png::Encoder::new(&mut png_bytes, width, height);
-        encoder.set_color(png::ColorType::Rgba);
-        encoder.set_depth(png::BitDepth::Eight);
-
-        let mut writer = encoder
-            .write_header()
-            .map_err(|e| format!("Failed to write PNG header: {}", e))?;
-
-        writer
-            .write_image_data(rgba_bytes)
-            .map_err(|e| format!("Failed to write PNG data: {}", e))?;
-    }
-    Ok(png_bytes)
-}
-
-/// Decode PNG bytes to RGBA format with dimensions
-fn decode_png_to_rgba(png_bytes: &[u8]) -> Result<(Vec<u8>, u32, u32), String> {
-    let decoder = png::Decoder::new(std::io::Cursor::new(png_bytes));
-    let mut reader = decoder
-        .read_info()
-        .map_err(|e| format!("Failed to read PNG info: {}", e))?;
-
-    let mut buf = vec![0; reader.output_buffer_size()];
-    let info = reader
-        .next_frame(&mut buf)
-        .map_err(|e| format!("Failed to decode PNG frame: {}", e))?;
-
-    let width = info.width;
-    let height = info.height;
-
-    // Ensure we have RGBA data
-    let rgba_bytes = match info.color_type {
-        png::ColorType::Rgba => buf[..info.buffer_size()].to_vec(),
-        png::ColorType::Rgb => {
-            // Convert RGB to RGBA
-            let rgb_data = &buf[..info.buffer_size()];
-            let mut rgba = Vec::with_capacity((width * height * 4) as usize);
-            for chunk in rgb_data.chunks(3) {
-                rgba.push(chunk[0]);
-                rgba.push(chunk[1]);
-                rgba.push(chunk[2]);
-                rgba.push(255);
-            }
-            rgba
-        }
-        png::ColorType::Grayscale => {
-            let gray_data = &buf[..info.buffer_size()];
-            let mut rgba = Vec::with_capacity((width * height * 4) as usize);
-            for &g in gray_data {
-                rgba.push(g);
-                rgba.push(g);
-                rgba.push(g);
-                rgba.push(255);
-            }
-            rgba
-        }
-        png::ColorType::GrayscaleAlpha => {
-            let ga_data = &buf[..info.buffer_size()];
-            let mut rgba = Vec::with_capacity((width * height * 4) as usize);
-            for chunk in ga_data.chunks(2) {
-                rgba.push(chunk[0]);
-                rgba.push(chunk[0]);
-                rgba.push(chunk[0]);
-                rgba.push(chunk[1]);
-            }
-            rgba
-        }
-        png::ColorType::Indexed => {
-            return Err("Indexed PNG not supported".to_string());
-        }
-    };
-
-    Ok((rgba_bytes, width, height))
-}
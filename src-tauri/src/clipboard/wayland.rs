@@ -26,43 +26,52 @@ pub async fn read() -> Result<String, String> {
     }
 }
 
+/// Image MIME types we can decode, most preferred first. PNG is lossless and
+/// stored as-is; the rest are converted to PNG by `image_ops::normalize_to_png`.
+const IMAGE_MIME_PREFERENCE: &[&str] = &[
+    "image/png",
+    "image/webp",
+    "image/tiff",
+    "image/bmp",
+    "image/x-bmp",
+    "image/x-ms-bmp",
+    "image/jpeg",
+    "image/jpg",
+    "image/gif",
+];
+
 pub async fn read_image() -> Result<Option<Vec<u8>>, String> {
     // First check if there's an image in the clipboard by listing MIME types
     let list_output = Command::new("wl-paste").arg("--list-types").output();
 
-    let has_image = match list_output {
+    let mime = match list_output {
         Ok(output) => {
             let types = String::from_utf8_lossy(&output.stdout);
-            types.contains("image/png") || types.contains("image/jpeg") || types.contains("image/")
+            let offered: Vec<&str> = types.lines().map(str::trim).collect();
+            IMAGE_MIME_PREFERENCE
+                .iter()
+                .find(|mime| offered.contains(mime))
+                .copied()
         }
-        Err(_) => false,
+        Err(_) => None,
     };
 
-    if !has_image {
+    let Some(mime) = mime else {
         return Ok(None);
-    }
+    };
 
-    // Read image as PNG
     match Command::new("wl-paste")
         .arg("--no-newline")
         .arg("--type")
-        .arg("image/png")
+        .arg(mime)
         .output()
     {
         Ok(output) => {
             if output.status.success() && !output.stdout.is_empty() {
                 Ok(Some(output.stdout))
             } else {
-                let stderr = String::from_utf8_lossy(&output.stderr);
-                if stderr.contains("No selection")
-                    || stderr.contains("Nothing is copied")
-                    || stderr.is_empty()
-                {
-                    Ok(None)
-                } else {
-                    // Try without specifying type as fallback
-                    Ok(None)
-                }
+                // Empty output or "No selection"/"Nothing is copied": no image
+                Ok(None)
             }
         }
         Err(e) => Err(format!(
@@ -0,0 +1,316 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ColorFormat {
+    Hex,
+    Hex8,
+    Rgb,
+    Hsl,
+    Oklch,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PaletteFormat {
+    /// GIMP/Inkscape palette
+    Gpl,
+    /// Adobe Swatch Exchange (binary, returned base64-encoded)
+    Ase,
+    /// CSS custom properties on `:root`
+    Css,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Swatch {
+    pub hex: String,
+    /// Fraction of sampled pixels that fell into this swatch
+    pub share: f64,
+}
+
+/// A named color ready for palette export.
+pub struct NamedColor {
+    pub name: String,
+    pub rgba: [u8; 4],
+}
+
+pub fn parse_rgba(text: &str) -> Result<[u8; 4], String> {
+    csscolorparser::parse(text.trim())
+        .map(|c| c.to_rgba8())
+        .map_err(|e| format!("Not a color: {}", e))
+}
+
+pub fn format_color(rgba: [u8; 4], format: ColorFormat) -> String {
+    let [r, g, b, a] = rgba;
+    let alpha = a as f64 / 255.0;
+    let alpha_suffix = if a == 255 {
+        String::new()
+    } else {
+        format!(" / {}", round(alpha, 3))
+    };
+
+    match format {
+        ColorFormat::Hex => format!("#{:02x}{:02x}{:02x}", r, g, b),
+        ColorFormat::Hex8 => format!("#{:02x}{:02x}{:02x}{:02x}", r, g, b, a),
+        ColorFormat::Rgb => format!("rgb({} {} {}{})", r, g, b, alpha_suffix),
+        ColorFormat::Hsl => {
+            let (h, s, l) = rgb_to_hsl(r, g, b);
+            format!(
+                "hsl({} {}% {}%{})",
+                round(h, 1),
+                round(s * 100.0, 1),
+                round(l * 100.0, 1),
+                alpha_suffix
+            )
+        }
+        ColorFormat::Oklch => {
+            let (l, c, h) = rgb_to_oklch(r, g, b);
+            format!(
+                "oklch({}% {} {}{})",
+                round(l * 100.0, 2),
+                round(c, 4),
+                round(h, 2),
+                alpha_suffix
+            )
+        }
+    }
+}
+
+fn round(value: f64, decimals: i32) -> f64 {
+    let factor = 10f64.powi(decimals);
+    (value * factor).round() / factor
+}
+
+fn rgb_to_hsl(r: u8, g: u8, b: u8) -> (f64, f64, f64) {
+    let (r, g, b) = (r as f64 / 255.0, g as f64 / 255.0, b as f64 / 255.0);
+    let max = r.max(g).max(b);
+    let min = r.min(g).min(b);
+    let l = (max + min) / 2.0;
+    let d = max - min;
+    if d == 0.0 {
+        return (0.0, 0.0, l);
+    }
+    let s = d / (1.0 - (2.0 * l - 1.0).abs());
+    let h = if max == r {
+        60.0 * (((g - b) / d).rem_euclid(6.0))
+    } else if max == g {
+        60.0 * ((b - r) / d + 2.0)
+    } else {
+        60.0 * ((r - g) / d + 4.0)
+    };
+    (h, s, l)
+}
+
+fn srgb_to_linear(c: u8) -> f64 {
+    let c = c as f64 / 255.0;
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// sRGB -> OKLab -> OKLCH, per Björn Ottosson's reference matrices.
+fn rgb_to_oklch(r: u8, g: u8, b: u8) -> (f64, f64, f64) {
+    let (r, g, b) = (srgb_to_linear(r), srgb_to_linear(g), srgb_to_linear(b));
+
+    let l = 0.4122214708 * r + 0.5363325363 * g + 0.0514459929 * b;
+    let m = 0.2119034982 * r + 0.6806995451 * g + 0.1073969566 * b;
+    let s = 0.0883024619 * r + 0.2817188376 * g + 0.6299787005 * b;
+    let (l, m, s) = (l.cbrt(), m.cbrt(), s.cbrt());
+
+    let ok_l = 0.2104542553 * l + 0.7936177850 * m - 0.0040720468 * s;
+    let ok_a = 1.9779984951 * l - 2.4285922050 * m + 0.4505937099 * s;
+    let ok_b = 0.0259040371 * l + 0.7827717662 * m - 0.8086757660 * s;
+
+    let chroma = (ok_a * ok_a + ok_b * ok_b).sqrt();
+    // Hue is meaningless for greys; report 0 instead of float noise
+    let hue = if chroma < 1e-4 {
+        0.0
+    } else {
+        ok_b.atan2(ok_a).to_degrees().rem_euclid(360.0)
+    };
+    (ok_l, chroma, hue)
+}
+
+/// Extracts up to `count` dominant colors from RGBA pixels using median cut.
+/// Mostly-transparent pixels are ignored.
+pub fn extract_palette(rgba: &[u8], count: usize) -> Vec<Swatch> {
+    const MAX_SAMPLES: usize = 20_000;
+
+    let pixel_count = rgba.len() / 4;
+    let step = (pixel_count / MAX_SAMPLES).max(1);
+    let pixels: Vec<[u8; 3]> = rgba
+        .chunks_exact(4)
+        .step_by(step)
+        .filter(|p| p[3] >= 128)
+        .map(|p| [p[0], p[1], p[2]])
+        .collect();
+    if pixels.is_empty() || count == 0 {
+        return Vec::new();
+    }
+    let total = pixels.len() as f64;
+
+    let mut boxes = vec![pixels];
+    while boxes.len() < count {
+        // Split the box with the widest channel range
+        let Some((index, channel)) = boxes
+            .iter()
+            .enumerate()
+            .filter(|(_, b)| b.len() > 1)
+            .map(|(i, b)| {
+                let (channel, range) = widest_channel(b);
+                (i, channel, range)
+            })
+            .filter(|(_, _, range)| *range > 0)
+            .max_by_key(|(_, _, range)| *range)
+            .map(|(i, channel, _)| (i, channel))
+        else {
+            break;
+        };
+
+        let mut bucket = boxes.swap_remove(index);
+        bucket.sort_unstable_by_key(|p| p[channel]);
+        // Cut at a value boundary near the median so one color isn't split in two
+        let median = bucket[bucket.len() / 2][channel];
+        let mut split = bucket.partition_point(|p| p[channel] < median);
+        if split == 0 {
+            split = bucket.partition_point(|p| p[channel] <= median);
+        }
+        let upper = bucket.split_off(split);
+        boxes.push(bucket);
+        boxes.push(upper);
+    }
+
+    let mut swatches: Vec<Swatch> = Vec::new();
+    for bucket in &boxes {
+        let swatch = average_swatch(bucket, total);
+        match swatches.iter_mut().find(|s| s.hex == swatch.hex) {
+            Some(existing) => existing.share += swatch.share,
+            None => swatches.push(swatch),
+        }
+    }
+    swatches.sort_by(|a, b| b.share.total_cmp(&a.share));
+    swatches
+}
+
+fn average_swatch(pixels: &[[u8; 3]], total: f64) -> Swatch {
+    let n = pixels.len() as u64;
+    let sum = pixels.iter().fold([0u64; 3], |acc, p| {
+        [
+            acc[0] + p[0] as u64,
+            acc[1] + p[1] as u64,
+            acc[2] + p[2] as u64,
+        ]
+    });
+    let avg = [
+        (sum[0] / n) as u8,
+        (sum[1] / n) as u8,
+        (sum[2] / n) as u8,
+        255,
+    ];
+    Swatch {
+        hex: format_color(avg, ColorFormat::Hex),
+        share: pixels.len() as f64 / total,
+    }
+}
+
+fn widest_channel(pixels: &[[u8; 3]]) -> (usize, u8) {
+    (0..3)
+        .map(|c| {
+            let (min, max) = pixels.iter().fold((u8::MAX, u8::MIN), |(lo, hi), p| {
+                (lo.min(p[c]), hi.max(p[c]))
+            });
+            (c, max - min)
+        })
+        .max_by_key(|(_, range)| *range)
+        .unwrap_or((0, 0))
+}
+
+pub fn export_gpl(name: &str, colors: &[NamedColor]) -> String {
+    let mut out = format!("GIMP Palette\nName: {}\nColumns: 0\n#\n", name);
+    for color in colors {
+        let [r, g, b, _] = color.rgba;
+        out.push_str(&format!("{:3} {:3} {:3}\t{}\n", r, g, b, color.name));
+    }
+    out
+}
+
+pub fn export_css(name: &str, colors: &[NamedColor]) -> String {
+    let prefix = slugify(name);
+    let mut out = String::from(":root {\n");
+    for (i, color) in colors.iter().enumerate() {
+        let slug = slugify(&color.name);
+        let var = if slug.is_empty() {
+            format!("{}-{}", prefix, i + 1)
+        } else {
+            format!("{}-{}", prefix, slug)
+        };
+        let format = if color.rgba[3] == 255 {
+            ColorFormat::Hex
+        } else {
+            ColorFormat::Hex8
+        };
+        out.push_str(&format!(
+            "  --{}: {};\n",
+            var,
+            format_color(color.rgba, format)
+        ));
+    }
+    out.push_str("}\n");
+    out
+}
+
+/// Encodes an Adobe Swatch Exchange file: a group containing one RGB global swatch per color.
+pub fn export_ase(name: &str, colors: &[NamedColor]) -> Vec<u8> {
+    fn utf16_name(name: &str) -> Vec<u8> {
+        let units: Vec<u16> = name.encode_utf16().chain(std::iter::once(0)).collect();
+        let mut out = (units.len() as u16).to_be_bytes().to_vec();
+        for unit in units {
+            out.extend(unit.to_be_bytes());
+        }
+        out
+    }
+
+    fn block(kind: u16, body: &[u8]) -> Vec<u8> {
+        let mut out = kind.to_be_bytes().to_vec();
+        out.extend((body.len() as u32).to_be_bytes());
+        out.extend(body);
+        out
+    }
+
+    const GROUP_START: u16 = 0xc001;
+    const GROUP_END: u16 = 0xc002;
+    const COLOR_ENTRY: u16 = 0x0001;
+
+    let mut out = b"ASEF".to_vec();
+    out.extend(1u16.to_be_bytes());
+    out.extend(0u16.to_be_bytes());
+    out.extend((colors.len() as u32 + 2).to_be_bytes());
+
+    out.extend(block(GROUP_START, &utf16_name(name)));
+    for color in colors {
+        let mut body = utf16_name(&color.name);
+        body.extend(b"RGB ");
+        for channel in &color.rgba[..3] {
+            body.extend((*channel as f32 / 255.0).to_be_bytes());
+        }
+        // Color type 0 = global
+        body.extend(0u16.to_be_bytes());
+        out.extend(block(COLOR_ENTRY, &body));
+    }
+    out.extend(block(GROUP_END, &[]));
+    out
+}
+
+fn slugify(text: &str) -> String {
+    let mut slug = String::new();
+    for c in text.chars() {
+        if c.is_ascii_alphanumeric() {
+            slug.push(c.to_ascii_lowercase());
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    slug.trim_end_matches('-').to_string()
+}
@@ -1,14 +1,34 @@
 use std::io::Cursor;
 
-use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+use crate::classify;
 use crate::clipboard::ClipboardManager;
-use scraper::{Html, Selector};
-use crate::db::{ClipboardItemRow, Database, InsertClipboardItemParams, UpdateSortOrderParams};
+use crate::color::{self, ColorFormat, NamedColor, PaletteFormat, Swatch};
+use crate::copy_events::{CopyEventKind, CopyEventRow, TimelineFilter};
+use crate::date::{self, DateFormat, DateOptions, DATE_ORDER_SETTING};
+use crate::db::{
+    BatchSummary, ClearOptions, ClearSummary, ClipboardItemRow, ClipboardItemSummary, Database,
+    FuzzySearchRow, InsertClipboardItemParams, ItemCursor, ItemOrder, PaletteRow, RegisterRow,
+    SimilarImageRow, TagCount, UpdateSortOrderParams,
+};
+use crate::image_ops::{self, decode_png_to_rgba, EncodeFormat, ImageOp};
+use crate::keychain;
+use crate::lan::{self, LanPeer, LanStatus, PairingCode};
+use crate::link_preview::{self, LinkPreviewData};
+use crate::ocr;
+use crate::perceptual_hash;
+use crate::registers;
+use crate::stats::{Period, UsageStats};
+use crate::structured::{self, DataFormat, StructuredOp};
+use crate::sync::{self, SyncReport};
+use crate::transform::{self, TextOp};
+use crate::undo::{MutationKind, UndoState};
 use crate::window_state::{is_visible as window_is_visible, set_visible as window_set_visible};
 use tauri::PhysicalPosition;
-use tauri::{AppHandle, Manager, State};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager, State};
 
-pub fn handle_command(app: &AppHandle, command: &str) {
+pub fn handle_command(app: &AppHandle, command: &str, arg: Option<&str>) {
     match command {
         "show" => {
             show_window_at_cursor(app.clone());
@@ -29,6 +49,19 @@ pub fn handle_command(app: &AppHandle, command: &str) {
                 show_window_at_cursor(app.clone());
             }
         }
+        registers::STORE_COMMAND | registers::PASTE_COMMAND => {
+            let app = app.clone();
+            let command = command.to_string();
+            let name = arg.unwrap_or_default().to_string();
+            // Runs without showing the window, so it can back a hotkey
+            tauri::async_runtime::spawn(async move {
+                let _ = if command == registers::STORE_COMMAND {
+                    registers::store(&app, &name).await.map(|_| ())
+                } else {
+                    registers::paste(&app, &name).await
+                };
+            });
+        }
         _ => {
             // Unknown command, default to show
             show_window_at_cursor(app.clone());
@@ -42,6 +75,11 @@ pub fn parse_command_from_args(args: &[String]) -> &str {
     args.get(1).map(|s| s.as_str()).unwrap_or("show")
 }
 
+/// The command's argument, e.g. the register in `paste-register a`.
+pub fn parse_command_arg_from_args(args: &[String]) -> Option<&str> {
+    args.get(2).map(|s| s.as_str())
+}
+
 #[tauri::command]
 pub async fn read_clipboard(manager: State<'_, ClipboardManager>) -> Result<String, String> {
     manager.read().await
@@ -211,16 +249,13 @@ pub fn get_system_theme() -> String {
 
 #[tauri::command]
 pub fn detect_env_content(text: String) -> bool {
-    let cursor = Cursor::new(text.as_bytes());
-    let iter = dotenvy::from_read_iter(cursor);
-    let mut valid_count = 0;
-    for result in iter {
-        match result {
-            Ok(_) => valid_count += 1,
-            Err(_) => return false,
-        }
-    }
-    valid_count > 0
+    classify::is_env(&text)
+}
+
+/// Detects JSON, YAML or TOML documents.
+#[tauri::command]
+pub fn detect_structured_content(text: String) -> Option<DataFormat> {
+    structured::detect_format(&text)
 }
 
 #[tauri::command]
@@ -237,176 +272,511 @@ pub fn parse_env_content(text: String) -> Vec<(String, String)> {
     pairs
 }
 
-/// Tries to parse text as a date string. Returns the ISO 8601 representation if detected.
-/// Only detects unambiguous formats: ISO 8601, RFC 2822, and JS toLocaleString() with time.
+/// Tries to parse text as a date string. Returns the RFC 3339 UTC representation if detected.
+/// Naive dates are read in the local timezone, with day/month order from the `date_order` setting.
 #[tauri::command]
-pub fn detect_date_content(text: String) -> Result<Option<String>, String> {
-    let text = text.trim();
+pub fn detect_date_content(
+    text: String,
+    database: State<'_, Database>,
+) -> Result<Option<String>, String> {
+    let order = database.get_setting(DATE_ORDER_SETTING)?;
+    Ok(date::detect(&text, &DateOptions::from_setting(order.as_deref())))
+}
 
-    // ISO 8601 with timezone: 2026-03-28T14:30:00Z
-    if let Ok(dt) = text.parse::<DateTime<Utc>>() {
-        return Ok(Some(dt.to_rfc3339()));
-    }
+/// Detects if text is a CSS color value. Returns the hex representation if detected.
+#[tauri::command]
+pub fn detect_color_content(text: String) -> Result<Option<String>, String> {
+    Ok(classify::detect_color(&text))
+}
 
-    // ISO 8601 with offset: 2026-03-28T14:30:00+05:00
-    if let Ok(dt) = DateTime::parse_from_rfc3339(text) {
-        return Ok(Some(dt.with_timezone(&Utc).to_rfc3339()));
-    }
+fn link_preview_dir(app: &AppHandle) -> Result<std::path::PathBuf, String> {
+    app.path()
+        .app_data_dir()
+        .map(|dir| dir.join("link-previews"))
+        .map_err(|e| e.to_string())
+}
 
-    // RFC 2822 / UTC string: Sat, 28 Mar 2026 14:30:00 GMT
-    if let Ok(dt) = DateTime::parse_from_rfc2822(text) {
-        return Ok(Some(dt.with_timezone(&Utc).to_rfc3339()));
-    }
+/// Returns preview metadata for a URL, served from the database cache while fresh.
+/// Returns `None` when the privacy settings only allow fetching on demand;
+/// call again with `on_demand` once the user asks for the preview.
+#[tauri::command]
+pub async fn fetch_link_preview(
+    app: AppHandle,
+    database: State<'_, Database>,
+    url: String,
+    on_demand: Option<bool>,
+) -> Result<Option<LinkPreviewData>, String> {
+    let assets_dir = link_preview_dir(&app)?;
+    link_preview::get_preview(&url, on_demand.unwrap_or(false), &database, &assets_dir).await
+}
 
-    // JS toLocaleString() US 12h: 3/28/2026, 2:30:00 PM
-    if let Ok(ndt) = NaiveDateTime::parse_from_str(text, "%m/%d/%Y, %I:%M:%S %p") {
-        return Ok(Some(ndt.and_utc().to_rfc3339()));
-    }
+#[tauri::command]
+pub fn clear_link_preview_cache(app: AppHandle, database: State<'_, Database>) -> Result<(), String> {
+    let assets_dir = link_preview_dir(&app)?;
+    link_preview::clear_cache(&database, &assets_dir)
+}
 
-    // JS toLocaleString() 24h: 3/28/2026, 14:30:00
-    if let Ok(ndt) = NaiveDateTime::parse_from_str(text, "%m/%d/%Y, %H:%M:%S") {
-        return Ok(Some(ndt.and_utc().to_rfc3339()));
-    }
+/// Where the result of a transform goes.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TransformOutput {
+    /// Write straight to the system clipboard
+    Clipboard,
+    /// Insert as a new history item at the top of the list
+    NewItem,
+}
 
-    // JS toLocaleString() without seconds 12h: 3/28/2026, 2:30 PM
-    if let Ok(ndt) = NaiveDateTime::parse_from_str(text, "%m/%d/%Y, %I:%M %p") {
-        return Ok(Some(ndt.and_utc().to_rfc3339()));
+/// Delivers derived text. Returns the inserted row for `TransformOutput::NewItem`.
+async fn deliver_text(
+    text: String,
+    output: TransformOutput,
+    manager: &ClipboardManager,
+    database: &Database,
+) -> Result<Option<ClipboardItemRow>, String> {
+    match output {
+        TransformOutput::Clipboard => {
+            manager.write(text).await?;
+            Ok(None)
+        }
+        TransformOutput::NewItem => {
+            let sort_order = database.top_sort_order()?;
+            let row = database.record_capture(InsertClipboardItemParams::text(text, sort_order))?;
+            Ok(Some(row))
+        }
     }
+}
 
-    // JS toLocaleString() without seconds 24h: 3/28/2026, 14:30
-    if let Ok(ndt) = NaiveDateTime::parse_from_str(text, "%m/%d/%Y, %H:%M") {
-        return Ok(Some(ndt.and_utc().to_rfc3339()));
-    }
+fn item_text(item: &ClipboardItemRow) -> Result<&str, String> {
+    item.text_content
+        .as_deref()
+        .filter(|_| item.content_type == "text")
+        .ok_or_else(|| format!("Item {} has no text content", item.id))
+}
 
-    // ISO date-only: 2026-03-28
-    if let Ok(nd) = NaiveDate::parse_from_str(text, "%Y-%m-%d") {
-        if let Some(ndt) = nd.and_hms_opt(0, 0, 0) {
-            return Ok(Some(ndt.and_utc().to_rfc3339()));
-        }
-    }
+/// Pretty-prints, minifies, converts or queries a JSON/YAML/TOML item.
+#[tauri::command]
+pub async fn transform_structured_item(
+    id: i64,
+    op: StructuredOp,
+    from: Option<DataFormat>,
+    output: TransformOutput,
+    manager: State<'_, ClipboardManager>,
+    database: State<'_, Database>,
+) -> Result<Option<ClipboardItemRow>, String> {
+    let item = database.get_item(id)?;
+    let result = structured::transform(item_text(&item)?, &op, from)?;
+    deliver_text(result, output, &manager, &database).await
+}
 
-    Ok(None)
+/// Applies a text operation (case change, encoding, hashing, ...) to an item.
+#[tauri::command]
+pub async fn transform_item(
+    id: i64,
+    op: TextOp,
+    output: TransformOutput,
+    manager: State<'_, ClipboardManager>,
+    database: State<'_, Database>,
+) -> Result<Option<ClipboardItemRow>, String> {
+    let item = database.get_item(id)?;
+    let result = transform::apply(item_text(&item)?, op)?;
+    deliver_text(result, output, &manager, &database).await
 }
 
-/// Detects if text is a CSS color value. Returns the hex representation if detected.
-/// Supports: hex, rgb/rgba, hsl/hsla, hwb, lab, lch, oklab, oklch, and named CSS colors.
+fn item_color(item: &ClipboardItemRow) -> Result<[u8; 4], String> {
+    item.text_content
+        .as_deref()
+        .and_then(|text| color::parse_rgba(text).ok())
+        .or_else(|| {
+            item.detected_color
+                .as_deref()
+                .and_then(|hex| color::parse_rgba(hex).ok())
+        })
+        .ok_or_else(|| format!("Item {} is not a color", item.id))
+}
+
+/// Re-formats a color item as hex, hex8, rgb(), hsl() or oklch().
 #[tauri::command]
-pub fn detect_color_content(text: String) -> Result<Option<String>, String> {
-    let text = text.trim();
-    // Reject strings that are too long or contain newlines (not a color)
-    if text.len() > 100 || text.contains('\n') {
-        return Ok(None);
-    }
-    match csscolorparser::parse(text) {
-        Ok(color) => Ok(Some(color.to_hex_string())),
-        Err(_) => Ok(None),
+pub async fn convert_color_item(
+    id: i64,
+    format: ColorFormat,
+    output: TransformOutput,
+    manager: State<'_, ClipboardManager>,
+    database: State<'_, Database>,
+) -> Result<Option<ClipboardItemRow>, String> {
+    let item = database.get_item(id)?;
+    let result = color::format_color(item_color(&item)?, format);
+    deliver_text(result, output, &manager, &database).await
+}
+
+/// Returns the dominant colors of an image item, most common first.
+#[tauri::command]
+pub async fn extract_image_palette(
+    id: i64,
+    count: Option<usize>,
+    database: State<'_, Database>,
+) -> Result<Vec<Swatch>, String> {
+    let item = database.get_item(id)?;
+    run_blocking(move || {
+        let (rgba, _, _) = decode_png_to_rgba(&item_png(&item)?)?;
+        Ok(color::extract_palette(&rgba, count.unwrap_or(6).clamp(1, 32)))
+    })
+    .await
+}
+
+/// Runs image decoding and encoding off the async runtime.
+pub(crate) async fn run_blocking<T: Send + 'static>(
+    f: impl FnOnce() -> Result<T, String> + Send + 'static,
+) -> Result<T, String> {
+    tauri::async_runtime::spawn_blocking(f)
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+/// Decodes the stored base64 PNG of an image item.
+fn item_png(item: &ClipboardItemRow) -> Result<Vec<u8>, String> {
+    let data = item
+        .image_data
+        .as_deref()
+        .ok_or_else(|| format!("Item {} has no image data", item.id))?;
+    BASE64
+        .decode(data)
+        .map_err(|e| format!("Failed to decode base64 image: {}", e))
+}
+
+/// Delivers an edited image. Returns the inserted row for `TransformOutput::NewItem`.
+async fn deliver_image(
+    app: AppHandle,
+    png_bytes: Vec<u8>,
+    (width, height): (u32, u32),
+    output: TransformOutput,
+    manager: &ClipboardManager,
+    database: &Database,
+) -> Result<Option<ClipboardItemRow>, String> {
+    let base64_data = BASE64.encode(png_bytes);
+    match output {
+        TransformOutput::Clipboard => {
+            manager.write_image(base64_data).await?;
+            Ok(None)
+        }
+        TransformOutput::NewItem => {
+            let sort_order = database.top_sort_order()?;
+            let params = InsertClipboardItemParams::image(base64_data, width, height, sort_order);
+            let handle = app.clone();
+            let row = run_blocking(move || handle.state::<Database>().record_capture(params)).await?;
+            if row.ocr_text.is_none() {
+                spawn_ocr(app, row.id);
+            }
+            Ok(Some(row))
+        }
     }
 }
 
-#[derive(serde::Serialize)]
-pub struct LinkPreviewData {
-    pub title: Option<String>,
-    pub description: Option<String>,
-    pub image: Option<String>,
-    pub favicon: Option<String>,
-    pub site_name: Option<String>,
+/// Crops, resizes, rotates or flips an image item. The result is always
+/// re-encoded, so an empty `ops` list just strips the image's metadata.
+#[tauri::command]
+pub async fn edit_image_item(
+    app: AppHandle,
+    id: i64,
+    ops: Vec<ImageOp>,
+    output: TransformOutput,
+    manager: State<'_, ClipboardManager>,
+    database: State<'_, Database>,
+) -> Result<Option<ClipboardItemRow>, String> {
+    let item = database.get_item(id)?;
+    let (png_bytes, dimensions) = run_blocking(move || {
+        let image = image_ops::edit(&item_png(&item)?, &ops)?;
+        Ok((image_ops::encode(&image, EncodeFormat::Png, None)?, image.dimensions()))
+    })
+    .await?;
+    deliver_image(app, png_bytes, dimensions, output, &manager, &database).await
+}
+
+#[derive(Serialize)]
+pub struct ImageExport {
+    pub file_name: String,
+    pub mime: String,
+    /// Base64-encoded file contents
+    pub content: String,
 }
 
+/// Converts an image item to PNG, JPEG or WebP for saving to a file.
 #[tauri::command]
-pub async fn fetch_link_preview(url: String) -> Result<LinkPreviewData, String> {
-    let client = reqwest::Client::builder()
-        .timeout(std::time::Duration::from_secs(5))
-        .build()
-        .map_err(|e| e.to_string())?;
+pub async fn export_image_item(
+    id: i64,
+    format: EncodeFormat,
+    quality: Option<u8>,
+    database: State<'_, Database>,
+) -> Result<ImageExport, String> {
+    let item = database.get_item(id)?;
+    let bytes = run_blocking(move || {
+        let image = image_ops::edit(&item_png(&item)?, &[])?;
+        image_ops::encode(&image, format, quality)
+    })
+    .await?;
 
-    let response = client
-        .get(&url)
-        .header("User-Agent", "Mozilla/5.0 (compatible; LinkPreview/1.0)")
-        .send()
-        .await
-        .map_err(|e| e.to_string())?;
+    Ok(ImageExport {
+        file_name: format!("image-{}.{}", id, format.extension()),
+        mime: format.mime().to_string(),
+        content: BASE64.encode(bytes),
+    })
+}
 
-    let html_text = response.text().await.map_err(|e| e.to_string())?;
-    let document = Html::parse_document(&html_text);
+/// Copies an image item as a `data:` URI, for pasting into HTML/CSS.
+#[tauri::command]
+pub async fn copy_image_as_data_uri(
+    id: i64,
+    format: Option<EncodeFormat>,
+    quality: Option<u8>,
+    output: TransformOutput,
+    manager: State<'_, ClipboardManager>,
+    database: State<'_, Database>,
+) -> Result<Option<ClipboardItemRow>, String> {
+    let item = database.get_item(id)?;
+    let format = format.unwrap_or(EncodeFormat::Png);
+    let bytes = run_blocking(move || {
+        let image = image_ops::edit(&item_png(&item)?, &[])?;
+        image_ops::encode(&image, format, quality)
+    })
+    .await?;
+    deliver_text(image_ops::data_uri(&bytes, format), output, &manager, &database).await
+}
 
-    let og_title = select_meta_content(&document, "og:title");
-    let og_description = select_meta_content(&document, "og:description");
-    let og_image = select_meta_content(&document, "og:image");
-    let og_site_name = select_meta_content(&document, "og:site_name");
+/// Emitted with the item id once OCR text has been stored.
+const OCR_COMPLETED_EVENT: &str = "ocr-completed";
 
-    let title = og_title.or_else(|| {
-        Selector::parse("title")
+/// Recognizes text in an image item off the async runtime, unless OCR is disabled.
+fn spawn_ocr(app: AppHandle, id: i64) {
+    tauri::async_runtime::spawn_blocking(move || {
+        let database = app.state::<Database>();
+        let enabled = database
+            .get_setting(ocr::OCR_ENABLED_SETTING)
             .ok()
-            .and_then(|sel| document.select(&sel).next())
-            .map(|el| el.text().collect::<String>())
+            .flatten();
+        if enabled.as_deref() == Some("false") {
+            return;
+        }
+        if run_ocr(&database, id).is_ok() {
+            let _ = app.emit(OCR_COMPLETED_EVENT, id);
+        }
     });
+}
 
-    let description =
-        og_description.or_else(|| select_meta_content_by_name(&document, "description"));
+fn run_ocr(database: &Database, id: i64) -> Result<String, String> {
+    let item = database.get_item(id)?;
+    let languages = database.get_setting(ocr::OCR_LANGUAGES_SETTING)?;
+    let text = ocr::recognize(&item_png(&item)?, languages.as_deref())?;
+    database.set_ocr_text(id, &text)?;
+    Ok(text)
+}
 
-    // Resolve favicon
-    let favicon = select_link_href(&document, "icon")
-        .or_else(|| select_link_href(&document, "shortcut icon"))
-        .map(|href| resolve_url(&url, &href))
-        .or_else(|| {
-            url::Url::parse(&url)
-                .ok()
-                .map(|u| format!("{}://{}/favicon.ico", u.scheme(), u.host_str().unwrap_or("")))
-        });
-
-    // Resolve og:image relative URLs
-    let image = og_image.map(|img| resolve_url(&url, &img));
-
-    Ok(LinkPreviewData {
-        title,
-        description,
-        image,
-        favicon,
-        site_name: og_site_name,
+/// Copies the text recognized in an image item, running OCR now if it hasn't run yet.
+#[tauri::command]
+pub async fn copy_image_text(
+    app: AppHandle,
+    id: i64,
+    manager: State<'_, ClipboardManager>,
+    database: State<'_, Database>,
+) -> Result<String, String> {
+    let item = database.get_item(id)?;
+    if item.content_type != "image" {
+        return Err(format!("Item {} is not an image", id));
+    }
+
+    let text = match item.ocr_text {
+        Some(text) => text,
+        None => run_blocking(move || run_ocr(&app.state::<Database>(), id)).await?,
+    };
+    if text.is_empty() {
+        return Err("No text found in image".to_string());
+    }
+
+    manager.write(text.clone()).await?;
+    Ok(text)
+}
+
+#[derive(Serialize)]
+pub struct PaletteExport {
+    pub file_name: String,
+    pub content: String,
+    /// ASE is binary, so its content is base64-encoded
+    pub is_base64: bool,
+}
+
+#[tauri::command]
+pub fn export_palette(
+    id: i64,
+    format: PaletteFormat,
+    database: State<'_, Database>,
+) -> Result<PaletteExport, String> {
+    let palette = database.get_palette(id)?;
+    let colors: Vec<NamedColor> = palette
+        .colors
+        .iter()
+        .filter_map(|c| {
+            let rgba = c
+                .text_content
+                .as_deref()
+                .or(c.detected_color.as_deref())
+                .and_then(|text| color::parse_rgba(text).ok())?;
+            let name = c
+                .kv_key
+                .clone()
+                .unwrap_or_else(|| color::format_color(rgba, ColorFormat::Hex));
+            Some(NamedColor { name, rgba })
+        })
+        .collect();
+
+    let (extension, content, is_base64) = match format {
+        PaletteFormat::Gpl => ("gpl", color::export_gpl(&palette.name, &colors), false),
+        PaletteFormat::Css => ("css", color::export_css(&palette.name, &colors), false),
+        PaletteFormat::Ase => (
+            "ase",
+            BASE64.encode(color::export_ase(&palette.name, &colors)),
+            true,
+        ),
+    };
+
+    Ok(PaletteExport {
+        file_name: format!("{}.{}", palette.name, extension),
+        content,
+        is_base64,
     })
 }
 
-fn select_meta_content(document: &Html, property: &str) -> Option<String> {
-    let selector =
-        Selector::parse(&format!("meta[property=\"{}\"]", property)).ok()?;
-    document
-        .select(&selector)
-        .next()
-        .and_then(|el| el.value().attr("content").map(|s| s.to_string()))
+/// Re-copies a date item as epoch seconds/millis, RFC 3339 or RFC 2822,
+/// optionally shifted to another timezone.
+#[tauri::command]
+pub async fn convert_date_item(
+    id: i64,
+    format: DateFormat,
+    timezone: Option<String>,
+    output: TransformOutput,
+    manager: State<'_, ClipboardManager>,
+    database: State<'_, Database>,
+) -> Result<Option<ClipboardItemRow>, String> {
+    let item = database.get_item(id)?;
+    let order = database.get_setting(DATE_ORDER_SETTING)?;
+    let options = DateOptions::from_setting(order.as_deref());
+    let instant = item
+        .detected_date
+        .as_deref()
+        .and_then(|d| date::parse(d, &options))
+        .or_else(|| item.text_content.as_deref().and_then(|t| date::parse(t, &options)))
+        .ok_or_else(|| format!("Item {} is not a date", id))?;
+    let result = date::format(instant, format, timezone.as_deref())?;
+    deliver_text(result, output, &manager, &database).await
 }
 
-fn select_meta_content_by_name(document: &Html, name: &str) -> Option<String> {
-    let selector = Selector::parse(&format!("meta[name=\"{}\"]", name)).ok()?;
-    document
-        .select(&selector)
-        .next()
-        .and_then(|el| el.value().attr("content").map(|s| s.to_string()))
+/// Exchanges changes with the other devices through the configured sync
+/// folder or WebDAV server.
+#[tauri::command]
+pub async fn sync_now(database: State<'_, Database>) -> Result<SyncReport, String> {
+    sync::sync_now(&database).await
 }
 
-fn select_link_href(document: &Html, rel: &str) -> Option<String> {
-    let selector = Selector::parse(&format!("link[rel=\"{}\"]", rel)).ok()?;
-    document
-        .select(&selector)
-        .next()
-        .and_then(|el| el.value().attr("href").map(|s| s.to_string()))
+// Register commands
+
+/// Stores the current clipboard content in register `name` (`a`–`z` or
+/// `1`–`9`).
+#[tauri::command]
+pub async fn store_register(app: AppHandle, name: String) -> Result<RegisterRow, String> {
+    registers::store(&app, &name).await
 }
 
-fn resolve_url(base: &str, href: &str) -> String {
-    if href.starts_with("http://") || href.starts_with("https://") {
-        return href.to_string();
-    }
-    if let Ok(base_url) = url::Url::parse(base) {
-        if let Ok(resolved) = base_url.join(href) {
-            return resolved.to_string();
-        }
-    }
-    href.to_string()
+/// Puts the content of register `name` on the clipboard.
+#[tauri::command]
+pub async fn paste_register(app: AppHandle, name: String) -> Result<(), String> {
+    registers::paste(&app, &name).await
+}
+
+#[tauri::command]
+pub fn db_get_registers(database: State<'_, Database>) -> Result<Vec<RegisterRow>, String> {
+    database.get_registers()
+}
+
+/// Copies a history item into register `name`.
+#[tauri::command]
+pub fn db_set_register(
+    name: String,
+    id: i64,
+    database: State<'_, Database>,
+) -> Result<RegisterRow, String> {
+    database.set_register_from_item(&registers::parse_name(&name)?, id)
+}
+
+#[tauri::command]
+pub fn db_clear_register(name: String, database: State<'_, Database>) -> Result<(), String> {
+    database.clear_register(&registers::parse_name(&name)?)
+}
+
+// LAN sharing commands
+
+/// Starts listening for paired devices and advertising over mDNS, and keeps
+/// sharing on across restarts.
+#[tauri::command]
+pub async fn lan_start(app: AppHandle) -> Result<LanStatus, String> {
+    lan::start(&app).await
+}
+
+#[tauri::command]
+pub fn lan_stop(app: AppHandle) -> Result<(), String> {
+    lan::stop(&app)
+}
+
+#[tauri::command]
+pub fn lan_status(app: AppHandle) -> LanStatus {
+    lan::status(&app)
+}
+
+/// Paired devices and unpaired ones seen on the network.
+#[tauri::command]
+pub fn lan_get_peers(app: AppHandle) -> Result<Vec<LanPeer>, String> {
+    lan::peers(&app)
+}
+
+/// Shows a one-time code for another device to pair with.
+#[tauri::command]
+pub fn lan_start_pairing(app: AppHandle) -> Result<PairingCode, String> {
+    lan::start_pairing(&app)
+}
+
+/// Pairs with the device at `address` (`host:port`) using the code it shows.
+#[tauri::command]
+pub async fn lan_pair(app: AppHandle, address: String, code: String) -> Result<LanPeer, String> {
+    lan::pair(&app, &address, &code).await
+}
+
+/// Accepts or declines the pairing waiting on this device, once the user has
+/// compared the fingerprints.
+#[tauri::command]
+pub fn lan_confirm_pairing(app: AppHandle, accept: bool) -> Result<(), String> {
+    lan::confirm_pairing(&app, accept)
+}
+
+#[tauri::command]
+pub fn lan_unpair(app: AppHandle, public_key: String) -> Result<(), String> {
+    lan::unpair(&app, &public_key)
+}
+
+/// Sends an item to the paired devices. Returns how many received it.
+#[tauri::command]
+pub async fn lan_share_item(
+    app: AppHandle,
+    id: i64,
+    database: State<'_, Database>,
+) -> Result<usize, String> {
+    let item = database.get_item(id)?;
+    lan::share(&app, &item).await
 }
 
 // Settings commands
 
+/// Credentials kept in the keychain can be set from the frontend but not read back.
 #[tauri::command]
 pub fn get_setting(key: String, database: State<'_, Database>) -> Result<Option<String>, String> {
+    if keychain::is_secret_setting(&key) {
+        return Err(format!("Setting '{}' can't be read", key));
+    }
     database.get_setting(&key)
 }
 
@@ -425,17 +795,58 @@ pub fn set_setting(
 pub fn db_get_all_items(
     limit: i64,
     offset: i64,
+    order: Option<ItemOrder>,
     database: State<'_, Database>,
 ) -> Result<Vec<ClipboardItemRow>, String> {
-    database.get_all_items(limit, offset)
+    database.get_all_items(limit, offset, order.unwrap_or_default())
 }
 
+/// Pages through history in manual order. Pass the last item's sort order
+/// and id as `after` for the next page.
 #[tauri::command]
-pub fn db_insert_item(
-    params: InsertClipboardItemParams,
+pub fn db_get_items_after(
+    limit: i64,
+    after: Option<ItemCursor>,
+    database: State<'_, Database>,
+) -> Result<Vec<ClipboardItemRow>, String> {
+    database.get_items_after(after.as_ref(), limit)
+}
+
+/// Same paging as `db_get_items_after`, without image data or full text.
+#[tauri::command]
+pub fn db_get_item_summaries(
+    limit: i64,
+    after: Option<ItemCursor>,
     database: State<'_, Database>,
+) -> Result<Vec<ClipboardItemSummary>, String> {
+    database.get_item_summaries(after.as_ref(), limit)
+}
+
+#[tauri::command]
+pub fn db_get_item(id: i64, database: State<'_, Database>) -> Result<ClipboardItemRow, String> {
+    database.get_item(id)
+}
+
+/// A sort key above every item, including ones the frontend hasn't loaded.
+#[tauri::command]
+pub fn db_top_sort_order(database: State<'_, Database>) -> Result<String, String> {
+    database.top_sort_order()
+}
+
+/// Inserts a capture, or bumps the existing item with the same content.
+/// Runs off the async runtime since new images are decoded for hashing.
+#[tauri::command]
+pub async fn db_record_capture(
+    app: AppHandle,
+    params: InsertClipboardItemParams,
 ) -> Result<ClipboardItemRow, String> {
-    database.insert_item(params)
+    let handle = app.clone();
+    let row = run_blocking(move || handle.state::<Database>().record_capture(params)).await?;
+    lan::share_capture(&app, &row);
+    if row.content_type == "image" && row.ocr_text.is_none() {
+        spawn_ocr(app, row.id);
+    }
+    Ok(row)
 }
 
 #[tauri::command]
@@ -447,16 +858,78 @@ pub fn db_bump_item(
     database.bump_item(id, &sort_order)
 }
 
+/// Moves an item to the trash.
 #[tauri::command]
 pub fn db_delete_item(id: i64, database: State<'_, Database>) -> Result<(), String> {
     database.delete_item(id)
 }
 
+/// Moves everything but favorites to the trash.
 #[tauri::command]
 pub fn db_clear_all(database: State<'_, Database>) -> Result<(), String> {
     database.clear_all()
 }
 
+/// Clears items by favorite, age, type, source app or secret detection, to
+/// the trash or for good. Without options it behaves like `db_clear_all`.
+#[tauri::command]
+pub fn db_clear_history(
+    options: Option<ClearOptions>,
+    database: State<'_, Database>,
+) -> Result<ClearSummary, String> {
+    database.clear_history(&options.unwrap_or_default())
+}
+
+#[tauri::command]
+pub fn db_get_trash(
+    limit: Option<i64>,
+    offset: Option<i64>,
+    database: State<'_, Database>,
+) -> Result<Vec<ClipboardItemRow>, String> {
+    database.get_trash(limit.unwrap_or(100), offset.unwrap_or(0))
+}
+
+#[tauri::command]
+pub fn db_restore_items(
+    ids: Vec<i64>,
+    database: State<'_, Database>,
+) -> Result<BatchSummary, String> {
+    database.restore_items(&ids)
+}
+
+/// Permanently deletes the trash. Returns the number of items deleted.
+#[tauri::command]
+pub fn db_empty_trash(database: State<'_, Database>) -> Result<i64, String> {
+    database.empty_trash()
+}
+
+/// Replaces an item with its parts (e.g. env key/value items) in one undoable step.
+#[tauri::command]
+pub fn db_split_item(
+    id: i64,
+    parts: Vec<InsertClipboardItemParams>,
+    database: State<'_, Database>,
+) -> Result<Vec<ClipboardItemRow>, String> {
+    database.split_item(id, parts)
+}
+
+/// Reverts the last delete, reorder, favorite change or split. Returns what
+/// was undone, or `None` if there was nothing to undo.
+#[tauri::command]
+pub fn db_undo(database: State<'_, Database>) -> Result<Option<MutationKind>, String> {
+    database.undo()
+}
+
+#[tauri::command]
+pub fn db_redo(database: State<'_, Database>) -> Result<Option<MutationKind>, String> {
+    database.redo()
+}
+
+#[tauri::command]
+pub fn db_get_undo_state(database: State<'_, Database>) -> Result<UndoState, String> {
+    database.undo_state()
+}
+
 #[tauri::command]
 pub fn db_toggle_favorite(
     id: i64,
@@ -473,12 +946,234 @@ pub fn db_update_sort_orders(
     database.update_sort_orders(items)
 }
 
+/// Joins items with a newline unless a separator is given.
+const DEFAULT_SEPARATOR: &str = "\n";
+
+#[tauri::command]
+pub fn db_batch_delete(
+    ids: Vec<i64>,
+    database: State<'_, Database>,
+) -> Result<BatchSummary, String> {
+    database.batch_delete(&ids)
+}
+
+#[tauri::command]
+pub fn db_batch_set_favorite(
+    ids: Vec<i64>,
+    favorite: bool,
+    database: State<'_, Database>,
+) -> Result<BatchSummary, String> {
+    database.batch_set_favorite(&ids, favorite)
+}
+
+#[tauri::command]
+pub fn db_batch_add_tag(
+    ids: Vec<i64>,
+    tag: String,
+    database: State<'_, Database>,
+) -> Result<BatchSummary, String> {
+    database.batch_add_tag(&ids, &tag)
+}
+
+#[tauri::command]
+pub fn db_batch_remove_tag(
+    ids: Vec<i64>,
+    tag: String,
+    database: State<'_, Database>,
+) -> Result<BatchSummary, String> {
+    database.batch_remove_tag(&ids, &tag)
+}
+
+/// Merges text items into one, joined in the order given.
+#[tauri::command]
+pub fn db_batch_merge(
+    ids: Vec<i64>,
+    separator: Option<String>,
+    database: State<'_, Database>,
+) -> Result<BatchSummary, String> {
+    database.batch_merge(&ids, separator.as_deref().unwrap_or(DEFAULT_SEPARATOR))
+}
+
+/// Copies the text of the items joined together, to the clipboard or as a
+/// new history item.
+#[tauri::command]
+pub async fn db_batch_copy(
+    ids: Vec<i64>,
+    separator: Option<String>,
+    output: TransformOutput,
+    manager: State<'_, ClipboardManager>,
+    database: State<'_, Database>,
+) -> Result<BatchSummary, String> {
+    let separator = separator.as_deref().unwrap_or(DEFAULT_SEPARATOR);
+    let (text, mut summary) = database.batch_joined_text(&ids, separator)?;
+    summary.item = deliver_text(text, output, &manager, &database).await?;
+    Ok(summary)
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BatchExportFormat {
+    /// Full rows, images included as base64
+    Json,
+    /// Text items joined by the separator
+    Text,
+}
+
+#[derive(Serialize)]
+pub struct BatchExport {
+    pub file_name: String,
+    pub content: String,
+    pub summary: BatchSummary,
+}
+
+#[tauri::command]
+pub fn db_batch_export(
+    ids: Vec<i64>,
+    format: BatchExportFormat,
+    separator: Option<String>,
+    database: State<'_, Database>,
+) -> Result<BatchExport, String> {
+    let (file_name, content, summary) = match format {
+        BatchExportFormat::Json => {
+            let (items, summary) = database.batch_items(&ids)?;
+            let content = serde_json::to_string_pretty(&items).map_err(|e| e.to_string())?;
+            ("clipboard-items.json", content, summary)
+        }
+        BatchExportFormat::Text => {
+            let separator = separator.as_deref().unwrap_or(DEFAULT_SEPARATOR);
+            let (content, summary) = database.batch_joined_text(&ids, separator)?;
+            ("clipboard-items.txt", content, summary)
+        }
+    };
+
+    Ok(BatchExport {
+        file_name: file_name.to_string(),
+        content,
+        summary,
+    })
+}
+
+#[tauri::command]
+pub fn db_get_item_tags(id: i64, database: State<'_, Database>) -> Result<Vec<String>, String> {
+    database.get_item_tags(id)
+}
+
+#[tauri::command]
+pub fn db_get_tags(database: State<'_, Database>) -> Result<Vec<TagCount>, String> {
+    database.get_tags()
+}
+
 #[tauri::command]
 pub fn db_get_item_count(database: State<'_, Database>) -> Result<i64, String> {
     database.get_item_count()
 }
 
+#[tauri::command]
+pub fn db_create_palette(
+    name: String,
+    item_ids: Vec<i64>,
+    database: State<'_, Database>,
+) -> Result<PaletteRow, String> {
+    database.create_palette(&name, &item_ids)
+}
+
+#[tauri::command]
+pub fn db_add_palette_items(
+    palette_id: i64,
+    item_ids: Vec<i64>,
+    database: State<'_, Database>,
+) -> Result<PaletteRow, String> {
+    database.add_palette_items(palette_id, &item_ids)
+}
+
+#[tauri::command]
+pub fn db_get_palettes(database: State<'_, Database>) -> Result<Vec<PaletteRow>, String> {
+    database.get_palettes()
+}
+
+#[tauri::command]
+pub fn db_delete_palette(id: i64, database: State<'_, Database>) -> Result<(), String> {
+    database.delete_palette(id)
+}
+
+#[tauri::command]
+pub fn db_record_copy_event(
+    id: i64,
+    kind: CopyEventKind,
+    source_app: Option<String>,
+    selection: Option<String>,
+    database: State<'_, Database>,
+) -> Result<(), String> {
+    database.record_copy_event(id, kind, source_app.as_deref(), selection.as_deref())
+}
+
+/// Copy events, newest first, optionally for one item, kind or time range.
+#[tauri::command]
+pub fn db_get_copy_timeline(
+    filter: Option<TimelineFilter>,
+    limit: Option<i64>,
+    offset: Option<i64>,
+    database: State<'_, Database>,
+) -> Result<Vec<CopyEventRow>, String> {
+    database.copy_timeline(
+        &filter.unwrap_or_default(),
+        limit.unwrap_or(100),
+        offset.unwrap_or(0),
+    )
+}
+
+/// Dashboard aggregates: `periods` most recent day/week buckets and the top
+/// `limit` entries of each ranked list.
+#[tauri::command]
+pub fn db_get_usage_stats(
+    period: Option<Period>,
+    periods: Option<i64>,
+    limit: Option<i64>,
+    database: State<'_, Database>,
+) -> Result<UsageStats, String> {
+    database.usage_stats(
+        period.unwrap_or(Period::Day),
+        periods.unwrap_or(30),
+        limit.unwrap_or(10),
+    )
+}
+
 #[tauri::command]
 pub fn db_dedup_item(id: i64, database: State<'_, Database>) -> Result<i64, String> {
     database.delete_duplicates(id)
 }
+
+/// Searches with filters like `type:image app:firefox fav:yes after:2026-09-01
+/// color:* len>200 "exact phrase"`.
+#[tauri::command]
+pub fn search_items(
+    query: String,
+    limit: Option<i64>,
+    offset: Option<i64>,
+    database: State<'_, Database>,
+) -> Result<Vec<ClipboardItemRow>, String> {
+    database.search_items(&query, limit.unwrap_or(100), offset.unwrap_or(0))
+}
+
+/// fzf-style search over item text (and OCR text of images) with match
+/// offsets for highlighting.
+#[tauri::command]
+pub fn search_items_fuzzy(
+    query: String,
+    limit: Option<usize>,
+    database: State<'_, Database>,
+) -> Result<Vec<FuzzySearchRow>, String> {
+    database.search_fuzzy(&query, limit.unwrap_or(50))
+}
+
+#[tauri::command]
+pub fn db_find_similar_images(
+    id: i64,
+    max_distance: Option<u32>,
+    database: State<'_, Database>,
+) -> Result<Vec<SimilarImageRow>, String> {
+    database.find_similar_images(
+        id,
+        max_distance.unwrap_or(perceptual_hash::DEFAULT_SIMILAR_DISTANCE),
+    )
+}
@@ -0,0 +1,212 @@
+//! Log of when each item was captured, restored or pasted, kept apart from
+//! the items themselves so reuse history survives bumps and merges.
+
+use std::collections::HashMap;
+
+use rusqlite::{Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+
+use crate::db::setting_value;
+
+/// Settings key: days of events to keep, default 90
+pub const MAX_AGE_DAYS_SETTING: &str = "copy_events_max_age_days";
+/// Settings key: maximum number of events kept, default 10000
+pub const MAX_EVENTS_SETTING: &str = "copy_events_max_rows";
+/// Settings key: hours for a copy's frecency weight to halve, default 72
+pub const FRECENCY_HALF_LIFE_SETTING: &str = "frecency_half_life_hours";
+
+const DEFAULT_MAX_AGE_DAYS: i64 = 90;
+const DEFAULT_MAX_EVENTS: i64 = 10_000;
+const DEFAULT_HALF_LIFE_HOURS: f64 = 72.0;
+const HOUR_MS: i64 = 60 * 60 * 1000;
+const DAY_MS: i64 = 24 * HOUR_MS;
+
+/// Restoring an item puts it back on the clipboard, where the monitor picks
+/// it up again. A capture this soon after a restore is that echo, not a copy.
+const RESTORE_ECHO_MS: i64 = 5_000;
+
+/// Characters of text kept in `CopyEventRow::preview`
+const PREVIEW_CHARS: i64 = 120;
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CopyEventKind {
+    /// Copied in another app and recorded by the monitor
+    Captured,
+    /// Copied back to the clipboard from history
+    Restored,
+    /// Pasted into another app
+    Pasted,
+}
+
+impl CopyEventKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Captured => "captured",
+            Self::Restored => "restored",
+            Self::Pasted => "pasted",
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct CopyEventRow {
+    pub id: i64,
+    pub item_id: i64,
+    pub kind: String,
+    pub source_app: Option<String>,
+    pub selection: Option<String>,
+    pub created_at: String,
+    pub content_type: String,
+    /// Start of the item's text, `None` for images
+    pub preview: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct TimelineFilter {
+    pub item_id: Option<i64>,
+    pub kind: Option<CopyEventKind>,
+    /// Epoch milliseconds, inclusive
+    pub since: Option<i64>,
+    /// Epoch milliseconds, exclusive
+    pub until: Option<i64>,
+}
+
+pub fn record(
+    conn: &Connection,
+    item_id: i64,
+    kind: CopyEventKind,
+    source_app: Option<&str>,
+    selection: Option<&str>,
+    at: &str,
+) -> rusqlite::Result<()> {
+    conn.execute(
+        "INSERT INTO copy_events (item_id, kind, source_app, selection, created_at) VALUES (?1, ?2, ?3, ?4, ?5)",
+        rusqlite::params![item_id, kind.as_str(), source_app, selection, at],
+    )?;
+    Ok(())
+}
+
+/// Whether a capture of `item_id` at `at` is the monitor seeing a restore.
+pub fn is_restore_echo(conn: &Connection, item_id: i64, at: &str) -> rusqlite::Result<bool> {
+    let Ok(at) = at.parse::<i64>() else {
+        return Ok(false);
+    };
+    let last_restore: Option<i64> = conn
+        .query_row(
+            "SELECT MAX(CAST(created_at AS INTEGER)) FROM copy_events WHERE item_id = ?1 AND kind = 'restored'",
+            rusqlite::params![item_id],
+            |row| row.get(0),
+        )
+        .optional()?
+        .flatten();
+    Ok(last_restore.is_some_and(|t| (0..=RESTORE_ECHO_MS).contains(&(at - t))))
+}
+
+/// Events matching `filter`, newest first.
+pub fn timeline(
+    conn: &Connection,
+    filter: &TimelineFilter,
+    limit: i64,
+    offset: i64,
+) -> rusqlite::Result<Vec<CopyEventRow>> {
+    conn.prepare(
+        "SELECT e.id, e.item_id, e.kind, e.source_app, e.selection, e.created_at, i.content_type, substr(i.text_content, 1, ?7)
+         FROM copy_events e JOIN clipboard_items i ON i.id = e.item_id
+         WHERE (?1 IS NULL OR e.item_id = ?1)
+           AND (?2 IS NULL OR e.kind = ?2)
+           AND (?3 IS NULL OR CAST(e.created_at AS INTEGER) >= ?3)
+           AND (?4 IS NULL OR CAST(e.created_at AS INTEGER) < ?4)
+         ORDER BY CAST(e.created_at AS INTEGER) DESC, e.id DESC
+         LIMIT ?5 OFFSET ?6",
+    )?
+    .query_map(
+        rusqlite::params![
+            filter.item_id,
+            filter.kind.map(CopyEventKind::as_str),
+            filter.since,
+            filter.until,
+            limit,
+            offset,
+            PREVIEW_CHARS,
+        ],
+        |row| {
+            Ok(CopyEventRow {
+                id: row.get(0)?,
+                item_id: row.get(1)?,
+                kind: row.get(2)?,
+                source_app: row.get(3)?,
+                selection: row.get(4)?,
+                created_at: row.get(5)?,
+                content_type: row.get(6)?,
+                preview: row.get(7)?,
+            })
+        },
+    )?
+    .collect()
+}
+
+/// Applies the retention settings: drops events of deleted items, events
+/// older than the maximum age, then the oldest beyond the maximum count.
+pub fn prune(conn: &Connection, now_ms: i64) -> rusqlite::Result<()> {
+    let setting = |key: &str, default: i64| -> rusqlite::Result<i64> {
+        Ok(setting_value(conn, key)?
+            .and_then(|v| v.trim().parse().ok())
+            .filter(|n| *n > 0)
+            .unwrap_or(default))
+    };
+    let max_age_days = setting(MAX_AGE_DAYS_SETTING, DEFAULT_MAX_AGE_DAYS)?;
+    let max_events = setting(MAX_EVENTS_SETTING, DEFAULT_MAX_EVENTS)?;
+
+    conn.execute(
+        "DELETE FROM copy_events WHERE item_id NOT IN (SELECT id FROM clipboard_items)",
+        [],
+    )?;
+    conn.execute(
+        "DELETE FROM copy_events WHERE CAST(created_at AS INTEGER) < ?1",
+        rusqlite::params![now_ms - max_age_days * DAY_MS],
+    )?;
+    conn.execute(
+        "DELETE FROM copy_events WHERE id IN (SELECT id FROM copy_events ORDER BY CAST(created_at AS INTEGER) DESC, id DESC LIMIT -1 OFFSET ?1)",
+        rusqlite::params![max_events],
+    )?;
+    Ok(())
+}
+
+/// Item ids ranked by frecency, highest first. Every logged copy adds a
+/// weight that halves each half-life; copies from before the log existed
+/// (`copy_count` beyond the logged events) count as of the item's last use.
+/// Ties fall back to the manual order.
+pub fn frecency_ranking(conn: &Connection, now_ms: i64) -> rusqlite::Result<Vec<i64>> {
+    let half_life_ms = setting_value(conn, FRECENCY_HALF_LIFE_SETTING)?
+        .and_then(|v| v.trim().parse::<f64>().ok())
+        .filter(|h| *h > 0.0)
+        .unwrap_or(DEFAULT_HALF_LIFE_HOURS)
+        * HOUR_MS as f64;
+    let weight = |at: i64| 0.5f64.powf((now_ms - at).max(0) as f64 / half_life_ms);
+
+    let mut logged: HashMap<i64, (f64, i64)> = HashMap::new();
+    let mut stmt = conn.prepare("SELECT item_id, CAST(created_at AS INTEGER) FROM copy_events")?;
+    let mut rows = stmt.query([])?;
+    while let Some(row) = rows.next()? {
+        let entry = logged.entry(row.get(0)?).or_default();
+        entry.0 += weight(row.get(1)?);
+        entry.1 += 1;
+    }
+
+    let mut scored: Vec<(i64, f64, String)> = conn
+        .prepare(
+            "SELECT id, copy_count, CAST(updated_at AS INTEGER), sort_order FROM clipboard_items WHERE deleted_at IS NULL",
+        )?
+        .query_map([], |row| {
+            let id: i64 = row.get(0)?;
+            let copy_count: i64 = row.get(1)?;
+            let (score, events) = logged.get(&id).copied().unwrap_or_default();
+            let unlogged = (copy_count - events).max(0) as f64;
+            Ok((id, score + unlogged * weight(row.get(2)?), row.get(3)?))
+        })?
+        .collect::<Result<_, _>>()?;
+
+    scored.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.2.cmp(&b.2)));
+    Ok(scored.into_iter().map(|(id, _, _)| id).collect())
+}
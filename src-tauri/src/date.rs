@@ -0,0 +1,336 @@
+use chrono::{
+    DateTime, Local, NaiveDate, NaiveDateTime, NaiveTime, SecondsFormat, TimeZone, Utc, Weekday,
+};
+use serde::Deserialize;
+
+/// Settings key holding "mdy", "dmy" or "auto" (locale-based)
+pub const DATE_ORDER_SETTING: &str = "date_order";
+
+/// Locales that write numeric dates month-first; everyone else is day-first.
+const MONTH_FIRST_LOCALES: &[&str] = &["en_US", "en_PH", "fil_PH", "en_AS", "en_GU", "en_UM"];
+
+/// Epoch values outside 2000..2100 are far more likely to be plain numbers.
+const EPOCH_MIN_SECS: i64 = 946_684_800;
+const EPOCH_MAX_SECS: i64 = 4_102_444_800;
+
+#[derive(Debug, Clone, Copy)]
+pub struct DateOptions {
+    /// Read `03/04/2026` as 3 April rather than March 4
+    pub day_first: bool,
+}
+
+impl DateOptions {
+    pub fn from_locale() -> Self {
+        let locale = ["LC_ALL", "LC_TIME", "LANG"]
+            .iter()
+            .filter_map(|key| std::env::var(key).ok())
+            .find(|value| !value.is_empty())
+            .unwrap_or_default();
+        // `C.UTF-8` and `en_US.UTF-8@euro` name the same locales as `C` and `en_US`
+        let name = locale.split(['.', '@']).next().unwrap_or_default();
+        let day_first = !name.is_empty()
+            && name != "C"
+            && name != "POSIX"
+            && !MONTH_FIRST_LOCALES.iter().any(|l| name.starts_with(l));
+        Self { day_first }
+    }
+
+    /// Resolves the `date_order` setting, falling back to the locale for "auto"/unset.
+    pub fn from_setting(value: Option<&str>) -> Self {
+        match value {
+            Some("dmy") => Self { day_first: true },
+            Some("mdy") => Self { day_first: false },
+            _ => Self::from_locale(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DateFormat {
+    EpochSeconds,
+    EpochMillis,
+    Rfc3339,
+    Rfc2822,
+}
+
+/// Tries to parse text as a date. Returns the instant as an RFC 3339 UTC string.
+/// Dates without an offset are interpreted in the local timezone.
+pub fn detect(text: &str, options: &DateOptions) -> Option<String> {
+    parse(text, options).map(|dt| dt.to_rfc3339())
+}
+
+pub fn parse(text: &str, options: &DateOptions) -> Option<DateTime<Utc>> {
+    let text = text.trim();
+    if text.is_empty() || text.len() > 64 || text.contains('\n') {
+        return None;
+    }
+
+    // ISO 8601 with timezone: 2026-03-28T14:30:00Z
+    if let Ok(dt) = text.parse::<DateTime<Utc>>() {
+        return Some(dt);
+    }
+
+    // ISO 8601 with offset: 2026-03-28T14:30:00+05:00
+    if let Ok(dt) = DateTime::parse_from_rfc3339(text) {
+        return Some(dt.with_timezone(&Utc));
+    }
+
+    // RFC 2822 / UTC string: Sat, 28 Mar 2026 14:30:00 GMT
+    if let Ok(dt) = DateTime::parse_from_rfc2822(text) {
+        return Some(dt.with_timezone(&Utc));
+    }
+
+    if let Some(dt) = parse_epoch(text) {
+        return Some(dt);
+    }
+
+    // ISO 8601 without offset: 2026-03-28T14:30:00, 2026-03-28 14:30
+    for format in [
+        "%Y-%m-%dT%H:%M:%S%.f",
+        "%Y-%m-%dT%H:%M",
+        "%Y-%m-%d %H:%M:%S%.f",
+        "%Y-%m-%d %H:%M",
+    ] {
+        if let Ok(ndt) = NaiveDateTime::parse_from_str(text, format) {
+            return local_to_utc(ndt);
+        }
+    }
+
+    // ISO date-only: 2026-03-28
+    if let Ok(nd) = NaiveDate::parse_from_str(text, "%Y-%m-%d") {
+        return local_to_utc(nd.and_time(NaiveTime::MIN));
+    }
+
+    if let Some(nd) = parse_iso_week_or_ordinal(text) {
+        return local_to_utc(nd.and_time(NaiveTime::MIN));
+    }
+
+    parse_numeric_date(text, options)
+}
+
+/// Unix timestamps: 10 digits of seconds or 13 digits of milliseconds, optionally fractional.
+fn parse_epoch(text: &str) -> Option<DateTime<Utc>> {
+    let (int_part, frac_part) = text.split_once('.').unwrap_or((text, ""));
+    if !int_part.chars().all(|c| c.is_ascii_digit())
+        || !frac_part.chars().all(|c| c.is_ascii_digit())
+    {
+        return None;
+    }
+
+    let value: i64 = int_part.parse().ok()?;
+    let frac: f64 = format!("0.{}", if frac_part.is_empty() { "0" } else { frac_part })
+        .parse()
+        .ok()?;
+    match int_part.len() {
+        10 if (EPOCH_MIN_SECS..EPOCH_MAX_SECS).contains(&value) => {
+            DateTime::from_timestamp(value, (frac * 1e9) as u32)
+        }
+        13 if (EPOCH_MIN_SECS * 1000..EPOCH_MAX_SECS * 1000).contains(&value) => {
+            DateTime::from_timestamp_millis(value)
+        }
+        _ => None,
+    }
+}
+
+/// ISO week dates (`2026-W13`, `2026-W13-6`, `2026W136`) and ordinal dates (`2026-087`).
+fn parse_iso_week_or_ordinal(text: &str) -> Option<NaiveDate> {
+    let (year, rest) = text.split_at_checked(4)?;
+    let year: i32 = year.parse().ok()?;
+    let rest = rest.strip_prefix('-').unwrap_or(rest);
+
+    if let Some(week_part) = rest.strip_prefix('W') {
+        let digits: String = week_part.chars().filter(|c| *c != '-').collect();
+        if !digits.chars().all(|c| c.is_ascii_digit()) {
+            return None;
+        }
+        let (week, day) = match digits.len() {
+            2 => (digits.parse().ok()?, 1),
+            3 => (digits[..2].parse().ok()?, digits[2..].parse().ok()?),
+            _ => return None,
+        };
+        let weekday = match day {
+            1 => Weekday::Mon,
+            2 => Weekday::Tue,
+            3 => Weekday::Wed,
+            4 => Weekday::Thu,
+            5 => Weekday::Fri,
+            6 => Weekday::Sat,
+            7 => Weekday::Sun,
+            _ => return None,
+        };
+        return NaiveDate::from_isoywd_opt(year, week, weekday);
+    }
+
+    // Ordinal needs the dash, otherwise `2026087` is just a number
+    if text.as_bytes().get(4) == Some(&b'-')
+        && rest.len() == 3
+        && rest.chars().all(|c| c.is_ascii_digit())
+    {
+        return NaiveDate::from_yo_opt(year, rest.parse().ok()?);
+    }
+
+    None
+}
+
+/// Numeric dates like `3/28/2026`, `28.03.2026` or `28-03-2026, 14:30`.
+/// The locale's order is tried first; the other order only matches when it is
+/// the sole valid reading (e.g. day > 12).
+fn parse_numeric_date(text: &str, options: &DateOptions) -> Option<DateTime<Utc>> {
+    const TIME_SUFFIXES: &[&str] = &[
+        ", %I:%M:%S %p",
+        ", %I:%M %p",
+        ", %H:%M:%S",
+        ", %H:%M",
+        " %I:%M:%S %p",
+        " %I:%M %p",
+        " %H:%M:%S",
+        " %H:%M",
+    ];
+
+    let orders: [&str; 2] = if options.day_first {
+        ["%d{s}%m{s}%Y", "%m{s}%d{s}%Y"]
+    } else {
+        ["%m{s}%d{s}%Y", "%d{s}%m{s}%Y"]
+    };
+
+    for order in orders {
+        for separator in ["/", ".", "-"] {
+            let date_format = order.replace("{s}", separator);
+            for suffix in TIME_SUFFIXES {
+                let format = format!("{}{}", date_format, suffix);
+                if let Ok(ndt) = NaiveDateTime::parse_from_str(text, &format) {
+                    return local_to_utc(ndt);
+                }
+            }
+            if let Ok(nd) = NaiveDate::parse_from_str(text, &date_format) {
+                return local_to_utc(nd.and_time(NaiveTime::MIN));
+            }
+        }
+    }
+
+    None
+}
+
+fn local_to_utc(ndt: NaiveDateTime) -> Option<DateTime<Utc>> {
+    // `earliest` resolves DST overlaps; times skipped by DST fall back to UTC
+    match Local.from_local_datetime(&ndt).earliest() {
+        Some(dt) => Some(dt.with_timezone(&Utc)),
+        None => Some(ndt.and_utc()),
+    }
+}
+
+/// Formats an instant for copying. `timezone` is an IANA name, "local" or "UTC"
+/// and only affects the RFC formats.
+pub fn format(
+    dt: DateTime<Utc>,
+    format: DateFormat,
+    timezone: Option<&str>,
+) -> Result<String, String> {
+    match format {
+        DateFormat::EpochSeconds => Ok(dt.timestamp().to_string()),
+        DateFormat::EpochMillis => Ok(dt.timestamp_millis().to_string()),
+        DateFormat::Rfc3339 => with_timezone(dt, timezone, |d| {
+            d.to_rfc3339_opts(SecondsFormat::AutoSi, true)
+        }),
+        DateFormat::Rfc2822 => with_timezone(dt, timezone, |d| d.to_rfc2822()),
+    }
+}
+
+fn with_timezone(
+    dt: DateTime<Utc>,
+    timezone: Option<&str>,
+    f: impl Fn(DateTime<chrono::FixedOffset>) -> String,
+) -> Result<String, String> {
+    match timezone {
+        None | Some("UTC") | Some("utc") => Ok(f(dt.fixed_offset())),
+        Some("local") => Ok(f(dt.with_timezone(&Local).fixed_offset())),
+        Some(name) => {
+            let tz: chrono_tz::Tz = name
+                .parse()
+                .map_err(|_| format!("Unknown timezone: {}", name))?;
+            Ok(f(dt.with_timezone(&tz).fixed_offset()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MDY: DateOptions = DateOptions { day_first: false };
+    const DMY: DateOptions = DateOptions { day_first: true };
+
+    fn local_date(text: &str, options: &DateOptions) -> Option<NaiveDate> {
+        parse(text, options).map(|dt| dt.with_timezone(&Local).date_naive())
+    }
+
+    fn ymd(year: i32, month: u32, day: u32) -> Option<NaiveDate> {
+        NaiveDate::from_ymd_opt(year, month, day)
+    }
+
+    #[test]
+    fn epochs_only_count_between_2000_and_2100() {
+        assert_eq!(
+            detect("1774708200", &MDY).as_deref(),
+            Some("2026-03-28T14:30:00+00:00")
+        );
+        assert_eq!(
+            detect("1774708200500", &MDY).as_deref(),
+            Some("2026-03-28T14:30:00.500+00:00")
+        );
+        // 1999 and 2100 are out of range, and other lengths are plain numbers
+        assert_eq!(detect("946684799", &MDY), None);
+        assert_eq!(detect("0946684799", &MDY), None);
+        assert_eq!(detect("4102444800", &MDY), None);
+        assert_eq!(detect("17747082", &MDY), None);
+    }
+
+    #[test]
+    fn numeric_dates_follow_the_configured_order() {
+        assert_eq!(local_date("03/04/2026", &MDY), ymd(2026, 3, 4));
+        assert_eq!(local_date("03/04/2026", &DMY), ymd(2026, 4, 3));
+        // Only one reading is valid, whatever the order
+        assert_eq!(local_date("13.04.2026", &MDY), ymd(2026, 4, 13));
+        assert_eq!(local_date("04-13-2026, 14:30", &DMY), ymd(2026, 4, 13));
+    }
+
+    #[test]
+    fn iso_formats() {
+        assert_eq!(local_date("2026-03-28", &MDY), ymd(2026, 3, 28));
+        assert_eq!(local_date("2026-W13-6", &MDY), ymd(2026, 3, 28));
+        assert_eq!(local_date("2026-087", &MDY), ymd(2026, 3, 28));
+        assert_eq!(
+            detect("Sat, 28 Mar 2026 14:30:00 GMT", &MDY).as_deref(),
+            Some("2026-03-28T14:30:00+00:00")
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_dates() {
+        for text in [
+            "",
+            "2026-13-45",
+            "32/01/2026",
+            "2026-W54",
+            "2026087",
+            "not a date",
+        ] {
+            assert_eq!(parse(text, &MDY), None, "{:?}", text);
+        }
+        assert!(format(Utc::now(), DateFormat::Rfc3339, Some("Mars/Olympus")).is_err());
+    }
+
+    #[test]
+    fn formats_in_a_timezone() {
+        let dt = parse("2026-03-28T14:30:00Z", &MDY).unwrap();
+        assert_eq!(
+            format(dt, DateFormat::EpochSeconds, None).unwrap(),
+            "1774708200"
+        );
+        assert_eq!(
+            format(dt, DateFormat::Rfc3339, Some("Asia/Tokyo")).unwrap(),
+            "2026-03-28T23:30:00+09:00"
+        );
+    }
+}
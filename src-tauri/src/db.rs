@@ -1,13 +1,33 @@
+use std::collections::HashSet;
 use std::sync::Mutex;
 
 use drizzle::core::expr::*;
 use drizzle::sqlite::prelude::*;
 use drizzle::sqlite::rusqlite::Drizzle;
-use rusqlite::Connection;
+use rusqlite::{Connection, OptionalExtension};
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
 
+use crate::classify::classify;
+use crate::copy_events::{self, CopyEventKind, CopyEventRow, TimelineFilter};
+use crate::date::{DateOptions, DATE_ORDER_SETTING};
+use crate::fractional_index;
+use crate::fuzzy::FuzzyIndex;
+use crate::keychain::{self, Keychain};
+use crate::perceptual_hash::{self, MERGE_DISTANCE_SETTING};
 use crate::schema::*;
+use crate::search_query;
+use crate::secrets;
+use crate::stats::{self, Period, UsageStats};
+use crate::sync::{self, SyncRecord, SyncScope};
+use crate::undo::{self, MutationKind, UndoStack, UndoState};
+
+/// Settings key: maximum number of non-favorite items kept. Unset keeps everything.
+pub const MAX_HISTORY_SETTING: &str = "max_history_items";
+/// Settings key: days a deleted item stays in the trash, default 30
+pub const TRASH_RETENTION_SETTING: &str = "trash_retention_days";
+
+const DEFAULT_TRASH_RETENTION_DAYS: i64 = 30;
 
 type DbResult<T> = Result<T, String>;
 
@@ -26,6 +46,8 @@ pub struct ClipboardItemRow {
     pub char_count: Option<i64>,
     pub line_count: Option<i64>,
     pub source_app: Option<String>,
+    /// Name of the paired LAN device the item was received from
+    pub source_device: Option<String>,
     pub is_favorite: bool,
     pub sort_order: String,
     pub copy_count: i64,
@@ -33,6 +55,12 @@ pub struct ClipboardItemRow {
     pub detected_date: Option<String>,
     pub detected_color: Option<String>,
     pub content_hash: Option<String>,
+    pub content_kind: Option<String>,
+    pub code_language: Option<String>,
+    pub ocr_text: Option<String>,
+    pub perceptual_hash: Option<String>,
+    /// Epoch milliseconds when moved to the trash
+    pub deleted_at: Option<String>,
     pub created_at: String,
     pub updated_at: String,
 }
@@ -49,6 +77,7 @@ impl From<SelectClipboardItems> for ClipboardItemRow {
             char_count: row.char_count,
             line_count: row.line_count,
             source_app: row.source_app,
+            source_device: row.source_device,
             is_favorite: row.is_favorite != 0,
             sort_order: row.sort_order,
             copy_count: row.copy_count,
@@ -56,16 +85,63 @@ impl From<SelectClipboardItems> for ClipboardItemRow {
             detected_date: row.detected_date,
             detected_color: row.detected_color,
             content_hash: row.content_hash,
+            content_kind: row.content_kind,
+            code_language: row.code_language,
+            ocr_text: row.ocr_text,
+            perceptual_hash: row.perceptual_hash,
+            deleted_at: row.deleted_at,
             created_at: row.created_at,
             updated_at: row.updated_at,
         }
     }
 }
 
-fn compute_content_hash(content_type: &str, text_content: &Option<String>, image_data: &Option<String>) -> String {
+/// Maps a raw `SELECT * FROM clipboard_items` row for queries drizzle can't express.
+fn map_item_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<SelectClipboardItems> {
+    Ok(SelectClipboardItems {
+        id: row.get("id")?,
+        content_type: row.get("content_type")?,
+        text_content: row.get("text_content")?,
+        image_data: row.get("image_data")?,
+        image_width: row.get("image_width")?,
+        image_height: row.get("image_height")?,
+        char_count: row.get("char_count")?,
+        line_count: row.get("line_count")?,
+        source_app: row.get("source_app")?,
+        source_device: row.get("source_device")?,
+        is_favorite: row.get("is_favorite")?,
+        sort_order: row.get("sort_order")?,
+        copy_count: row.get("copy_count")?,
+        kv_key: row.get("kv_key")?,
+        detected_date: row.get("detected_date")?,
+        detected_color: row.get("detected_color")?,
+        content_hash: row.get("content_hash")?,
+        content_kind: row.get("content_kind")?,
+        code_language: row.get("code_language")?,
+        ocr_text: row.get("ocr_text")?,
+        perceptual_hash: row.get("perceptual_hash")?,
+        deleted_at: row.get("deleted_at")?,
+        created_at: row.get("created_at")?,
+        updated_at: row.get("updated_at")?,
+    })
+}
+
+/// Hash identifying an item's content. Key-value items include their key, so
+/// a split-out value doesn't collide with the same text copied on its own.
+fn compute_content_hash(
+    content_type: &str,
+    text_content: &Option<String>,
+    image_data: &Option<String>,
+    kv_key: &Option<String>,
+) -> String {
     let mut hasher = Sha256::new();
     hasher.update(content_type.as_bytes());
     hasher.update(b":");
+    if let Some(key) = kv_key {
+        hasher.update(b"kv:");
+        hasher.update(key.as_bytes());
+        hasher.update(b":");
+    }
     match content_type {
         "text" => {
             if let Some(text) = text_content {
@@ -92,27 +168,272 @@ pub struct InsertClipboardItemParams {
     pub char_count: Option<i64>,
     pub line_count: Option<i64>,
     pub source_app: Option<String>,
+    /// Paired LAN device the content was received from
+    #[serde(default)]
+    pub source_device: Option<String>,
+    pub sort_order: String,
+    pub kv_key: Option<String>,
+    /// Selection the content was captured from, for the copy event log
+    #[serde(default)]
+    pub selection: Option<String>,
+    /// Filled in by `classify` when not provided
+    pub detected_date: Option<String>,
+    /// Filled in by `classify` when not provided
+    pub detected_color: Option<String>,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+impl InsertClipboardItemParams {
+    /// Params for a text item created by the backend (transforms, conversions, ...).
+    pub fn text(text: String, sort_order: String) -> Self {
+        let now = timestamp_now();
+        Self {
+            content_type: "text".to_string(),
+            char_count: Some(text.chars().count() as i64),
+            line_count: Some(text.split('\n').count() as i64),
+            text_content: Some(text),
+            image_data: None,
+            image_width: None,
+            image_height: None,
+            source_app: None,
+            source_device: None,
+            sort_order,
+            kv_key: None,
+            selection: None,
+            detected_date: None,
+            detected_color: None,
+            created_at: now.clone(),
+            updated_at: now,
+        }
+    }
+
+    /// Params for an image item created by the backend (edits, conversions, ...).
+    pub fn image(base64_png: String, width: u32, height: u32, sort_order: String) -> Self {
+        let now = timestamp_now();
+        Self {
+            content_type: "image".to_string(),
+            text_content: None,
+            image_data: Some(base64_png),
+            image_width: Some(width as i64),
+            image_height: Some(height as i64),
+            char_count: None,
+            line_count: None,
+            source_app: None,
+            source_device: None,
+            sort_order,
+            kv_key: None,
+            selection: None,
+            detected_date: None,
+            detected_color: None,
+            created_at: now.clone(),
+            updated_at: now,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct PaletteColorRow {
+    pub item_id: i64,
+    pub text_content: Option<String>,
+    pub detected_color: Option<String>,
+    pub kv_key: Option<String>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct PaletteRow {
+    pub id: i64,
+    pub name: String,
+    pub created_at: String,
+    pub colors: Vec<PaletteColorRow>,
+}
+
+#[derive(Debug, Clone)]
+pub struct LinkPreviewRow {
+    pub url: String,
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub image: Option<String>,
+    pub favicon: Option<String>,
+    pub site_name: Option<String>,
+    pub image_path: Option<String>,
+    pub favicon_path: Option<String>,
+    pub fetched_at: String,
+}
+
+impl From<SelectLinkPreviews> for LinkPreviewRow {
+    fn from(row: SelectLinkPreviews) -> Self {
+        Self {
+            url: row.url,
+            title: row.title,
+            description: row.description,
+            image: row.image,
+            favicon: row.favicon,
+            site_name: row.site_name,
+            image_path: row.image_path,
+            favicon_path: row.favicon_path,
+            fetched_at: row.fetched_at,
+        }
+    }
+}
+
+/// List projection of an item for scrolling history: no image data, OCR text
+/// or hashes, and only the start of long text. Fetch the full row with
+/// `get_item` when it's needed.
+#[derive(Debug, Serialize)]
+pub struct ClipboardItemSummary {
+    pub id: i64,
+    pub content_type: String,
+    /// First `SUMMARY_TEXT_CHARS` chars of `text_content`
+    pub text_preview: Option<String>,
+    pub image_width: Option<i64>,
+    pub image_height: Option<i64>,
+    pub char_count: Option<i64>,
+    pub line_count: Option<i64>,
+    pub source_app: Option<String>,
+    pub source_device: Option<String>,
+    pub is_favorite: bool,
     pub sort_order: String,
+    pub copy_count: i64,
     pub kv_key: Option<String>,
     pub detected_date: Option<String>,
     pub detected_color: Option<String>,
+    pub content_kind: Option<String>,
+    pub code_language: Option<String>,
     pub created_at: String,
     pub updated_at: String,
 }
 
+const SUMMARY_TEXT_CHARS: i64 = 500;
+
+/// Position in the manual order to continue paging from. Unlike an offset it
+/// stays valid when items above it are bumped or deleted.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ItemCursor {
+    pub sort_order: String,
+    pub id: i64,
+}
+
+/// Keyset condition for `?1` = cursor sort order and `?2` = cursor id, both
+/// NULL for the first page. Ids break ties between equal sort orders.
+const AFTER_CURSOR: &str =
+    "deleted_at IS NULL AND (?1 IS NULL OR sort_order > ?1 OR (sort_order = ?1 AND id > ?2)) ORDER BY sort_order ASC, id ASC LIMIT ?3";
+
 #[derive(Debug, Deserialize)]
 pub struct UpdateSortOrderParams {
     pub id: i64,
     pub sort_order: String,
 }
 
+/// How `get_all_items` orders history.
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ItemOrder {
+    /// Fractional `sort_order`, as arranged by bumps and drag-and-drop
+    #[default]
+    Manual,
+    /// Most recently captured or reused first
+    Recent,
+    /// Copy events weighted by recency, see `copy_events::frecency_ranking`
+    Frecency,
+    /// Highest `copy_count` first
+    MostUsed,
+}
+
+impl ItemOrder {
+    /// The ORDER BY clause, or `None` when ranking happens in Rust.
+    fn order_by(self) -> Option<&'static str> {
+        match self {
+            Self::Manual => Some("sort_order ASC"),
+            Self::Recent => Some("CAST(updated_at AS INTEGER) DESC, id DESC"),
+            Self::MostUsed => Some("copy_count DESC, CAST(updated_at AS INTEGER) DESC, id DESC"),
+            Self::Frecency => None,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct FuzzySearchRow {
+    pub item: ClipboardItemRow,
+    pub score: u32,
+    /// Matched char offsets into `text_content`, or `ocr_text` for images
+    pub indices: Vec<u32>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SimilarImageRow {
+    pub item: ClipboardItemRow,
+    /// Hamming distance between the perceptual hashes, 0-64
+    pub distance: u32,
+}
+
+/// Outcome of a batch operation. Ids keep the order they were given in.
+#[derive(Debug, Default, Serialize)]
+pub struct BatchSummary {
+    pub requested: usize,
+    /// Ids the operation changed, or read for copies and exports
+    pub affected: Vec<i64>,
+    /// Ids no longer in history, or in the trash unless restoring
+    pub missing: Vec<i64>,
+    /// Ids the operation doesn't apply to, e.g. images when joining text
+    pub skipped: Vec<i64>,
+    /// Item created by a merge, or by copying into history
+    pub item: Option<ClipboardItemRow>,
+}
+
+/// Which items `clear_history` removes. Set filters all have to match.
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct ClearOptions {
+    pub keep_favorites: bool,
+    /// Epoch milliseconds; only items last used before this
+    pub before: Option<i64>,
+    /// "text" or "image"
+    pub content_type: Option<String>,
+    pub source_app: Option<String>,
+    /// Only text that looks like a credential, see `secrets::is_secret`
+    pub secrets_only: bool,
+    /// Delete for good, trash included, and reclaim the space instead of
+    /// moving to the trash
+    pub permanent: bool,
+}
+
+impl Default for ClearOptions {
+    fn default() -> Self {
+        Self {
+            keep_favorites: true,
+            before: None,
+            content_type: None,
+            source_app: None,
+            secrets_only: false,
+            permanent: false,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct ClearSummary {
+    pub removed: i64,
+    /// Bytes the database file shrank by; 0 unless `permanent`
+    pub bytes_reclaimed: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TagCount {
+    pub tag: String,
+    pub count: i64,
+}
+
 pub struct Database {
     inner: Mutex<DatabaseInner>,
+    keychain: Keychain,
 }
 
 struct DatabaseInner {
     db: Drizzle,
     schema: Schema,
+    fuzzy: FuzzyIndex,
+    undo: UndoStack,
 }
 
 impl Database {
@@ -127,16 +448,61 @@ impl Database {
         let schema = Schema::new();
         db.push(&schema).map_err(e2s)?;
 
-        // Create index on content_hash for fast deduplication
+        // Unique index on content_hash backs deduplication in `record_capture`
+        ensure_unique_content_hash(db.conn()).map_err(e2s)?;
+
+        db.conn().execute(
+            "CREATE INDEX IF NOT EXISTS idx_clipboard_items_content_kind ON clipboard_items(content_kind)",
+            [],
+        ).map_err(e2s)?;
+
+        // Substring search over text and OCR text, see `search_query`
+        ensure_search_index(db.conn()).map_err(e2s)?;
+
+        // Partial index: only the trash view and purge look up deleted rows
+        db.conn().execute(
+            "CREATE INDEX IF NOT EXISTS idx_clipboard_items_deleted_at ON clipboard_items(deleted_at) WHERE deleted_at IS NOT NULL",
+            [],
+        ).map_err(e2s)?;
+
+        // Backs keyset pagination in `get_items_after`
+        db.conn().execute(
+            "CREATE INDEX IF NOT EXISTS idx_clipboard_items_sort_order_id ON clipboard_items(sort_order, id)",
+            [],
+        ).map_err(e2s)?;
+
+        db.conn().execute(
+            "CREATE UNIQUE INDEX IF NOT EXISTS idx_item_tags_item_id_tag ON item_tags(item_id, tag)",
+            [],
+        ).map_err(e2s)?;
+
+        db.conn().execute(
+            "CREATE INDEX IF NOT EXISTS idx_copy_events_item_id ON copy_events(item_id)",
+            [],
+        ).map_err(e2s)?;
+
+        backfill_content_kind(db.conn()).map_err(e2s)?;
+        copy_events::prune(db.conn(), now_millis()).map_err(e2s)?;
+        purge_trash(db.conn(), now_millis()).map_err(e2s)?;
+        // Single deletes and history trimming leave tags behind
         db.conn().execute(
-            "CREATE INDEX IF NOT EXISTS idx_clipboard_items_content_hash ON clipboard_items(content_hash)",
+            "DELETE FROM item_tags WHERE item_id NOT IN (SELECT id FROM clipboard_items)",
             [],
         ).map_err(e2s)?;
 
+        let keychain = Keychain::new(db_path);
+        move_secrets_to_keychain(db.conn(), &keychain).map_err(e2s)?;
+
         let schema = Schema::new();
-        let inner = DatabaseInner { db, schema };
+        let inner = DatabaseInner {
+            db,
+            schema,
+            fuzzy: FuzzyIndex::new(),
+            undo: UndoStack::new(),
+        };
         Ok(Self {
             inner: Mutex::new(inner),
+            keychain,
         })
     }
 
@@ -144,37 +510,57 @@ impl Database {
         self.inner.lock().map_err(e2s)
     }
 
-    pub fn get_all_items(&self, limit: i64, offset: i64) -> DbResult<Vec<ClipboardItemRow>> {
+    pub fn get_all_items(&self, limit: i64, offset: i64, order: ItemOrder) -> DbResult<Vec<ClipboardItemRow>> {
+        let inner = self.lock()?;
+        let conn = inner.db.conn();
+
+        let rows: Vec<SelectClipboardItems> = match order.order_by() {
+            Some(order_by) => conn
+                .prepare(&format!(
+                    "SELECT * FROM clipboard_items WHERE deleted_at IS NULL ORDER BY {} LIMIT ?1 OFFSET ?2",
+                    order_by
+                ))
+                .and_then(|mut stmt| {
+                    stmt.query_map(rusqlite::params![limit, offset], map_item_row)
+                        .and_then(|rows| rows.collect::<Result<Vec<_>, _>>())
+                }),
+            None => copy_events::frecency_ranking(conn, now_millis()).and_then(|ranking| {
+                let page: Vec<i64> = ranking
+                    .into_iter()
+                    .skip(offset.max(0) as usize)
+                    .take(limit.max(0) as usize)
+                    .collect();
+                let ids = serde_json::to_string(&page).unwrap_or_default();
+                // json_each keeps the ranking order through the join
+                conn.prepare("SELECT c.* FROM json_each(?1) j JOIN clipboard_items c ON c.id = j.value ORDER BY j.key")
+                    .and_then(|mut stmt| {
+                        stmt.query_map(rusqlite::params![ids], map_item_row)
+                            .and_then(|rows| rows.collect::<Result<Vec<_>, _>>())
+                    })
+            }),
+        }
+        .map_err(e2s)?;
+
+        Ok(rows.into_iter().map(ClipboardItemRow::from).collect())
+    }
+
+    /// The page of items in manual order after `after`, or the first page.
+    pub fn get_items_after(
+        &self,
+        after: Option<&ItemCursor>,
+        limit: i64,
+    ) -> DbResult<Vec<ClipboardItemRow>> {
         let inner = self.lock()?;
-        let _ci = &inner.schema.clipboard_items;
 
         let rows: Vec<SelectClipboardItems> = inner
             .db
             .conn()
-            .prepare("SELECT * FROM clipboard_items ORDER BY sort_order ASC LIMIT ?1 OFFSET ?2")
+            .prepare(&format!("SELECT * FROM clipboard_items WHERE {}", AFTER_CURSOR))
             .and_then(|mut stmt| {
-                stmt.query_map(rusqlite::params![limit, offset], |row| {
-                    Ok(SelectClipboardItems {
-                        id: row.get("id")?,
-                        content_type: row.get("content_type")?,
-                        text_content: row.get("text_content")?,
-                        image_data: row.get("image_data")?,
-                        image_width: row.get("image_width")?,
-                        image_height: row.get("image_height")?,
-                        char_count: row.get("char_count")?,
-                        line_count: row.get("line_count")?,
-                        source_app: row.get("source_app")?,
-                        is_favorite: row.get("is_favorite")?,
-                        sort_order: row.get("sort_order")?,
-                        copy_count: row.get("copy_count")?,
-                        kv_key: row.get("kv_key")?,
-                        detected_date: row.get("detected_date")?,
-                        detected_color: row.get("detected_color")?,
-                        content_hash: row.get("content_hash")?,
-                        created_at: row.get("created_at")?,
-                        updated_at: row.get("updated_at")?,
-                    })
-                })
+                stmt.query_map(
+                    rusqlite::params![after.map(|c| &c.sort_order), after.map(|c| c.id), limit],
+                    map_item_row,
+                )
                 .and_then(|rows| rows.collect::<Result<Vec<_>, _>>())
             })
             .map_err(e2s)?;
@@ -182,78 +568,269 @@ impl Database {
         Ok(rows.into_iter().map(ClipboardItemRow::from).collect())
     }
 
-    pub fn insert_item(&self, params: InsertClipboardItemParams) -> DbResult<ClipboardItemRow> {
+    /// Like `get_items_after`, but as lightweight `ClipboardItemSummary` rows.
+    pub fn get_item_summaries(
+        &self,
+        after: Option<&ItemCursor>,
+        limit: i64,
+    ) -> DbResult<Vec<ClipboardItemSummary>> {
         let inner = self.lock()?;
-        let ci = &inner.schema.clipboard_items;
-
-        let content_hash = compute_content_hash(&params.content_type, &params.text_content, &params.image_data);
 
         inner
             .db
             .conn()
-            .execute(
-                "INSERT INTO clipboard_items (content_type, text_content, image_data, image_width, image_height, char_count, line_count, source_app, is_favorite, sort_order, copy_count, kv_key, detected_date, detected_color, content_hash, created_at, updated_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, 0, ?9, 1, ?10, ?11, ?12, ?13, ?14, ?15)",
-                rusqlite::params![
-                    params.content_type,
-                    params.text_content,
-                    params.image_data,
-                    params.image_width,
-                    params.image_height,
-                    params.char_count,
-                    params.line_count,
-                    params.source_app,
-                    params.sort_order,
-                    params.kv_key,
-                    params.detected_date,
-                    params.detected_color,
-                    content_hash,
-                    params.created_at,
-                    params.updated_at,
-                ],
-            )
-            .map_err(e2s)?;
+            .prepare(&format!(
+                "SELECT id, content_type, substr(text_content, 1, ?4), image_width, image_height, char_count,
+                        line_count, source_app, is_favorite, sort_order, copy_count, kv_key, detected_date,
+                        detected_color, content_kind, code_language, created_at, updated_at, source_device
+                 FROM clipboard_items WHERE {}",
+                AFTER_CURSOR
+            ))
+            .and_then(|mut stmt| {
+                stmt.query_map(
+                    rusqlite::params![
+                        after.map(|c| &c.sort_order),
+                        after.map(|c| c.id),
+                        limit,
+                        SUMMARY_TEXT_CHARS
+                    ],
+                    |row| {
+                        Ok(ClipboardItemSummary {
+                            id: row.get(0)?,
+                            content_type: row.get(1)?,
+                            text_preview: row.get(2)?,
+                            image_width: row.get(3)?,
+                            image_height: row.get(4)?,
+                            char_count: row.get(5)?,
+                            line_count: row.get(6)?,
+                            source_app: row.get(7)?,
+                            source_device: row.get(18)?,
+                            is_favorite: row.get::<_, i64>(8)? != 0,
+                            sort_order: row.get(9)?,
+                            copy_count: row.get(10)?,
+                            kv_key: row.get(11)?,
+                            detected_date: row.get(12)?,
+                            detected_color: row.get(13)?,
+                            content_kind: row.get(14)?,
+                            code_language: row.get(15)?,
+                            created_at: row.get(16)?,
+                            updated_at: row.get(17)?,
+                        })
+                    },
+                )
+                .and_then(|rows| rows.collect::<Result<Vec<_>, _>>())
+            })
+            .map_err(e2s)
+    }
+
+    pub fn get_item(&self, id: i64) -> DbResult<ClipboardItemRow> {
+        let inner = self.lock()?;
+        let ci = &inner.schema.clipboard_items;
 
         let row: SelectClipboardItems = inner
             .db
             .select(())
             .from(*ci)
-            .order_by(desc(ci.id))
-            .limit(1)
+            .r#where(eq(ci.id, id))
             .get()
             .map_err(e2s)?;
 
         Ok(ClipboardItemRow::from(row))
     }
 
-    /// Delete all duplicates of an item by content_hash, keeping only the given ID.
-    /// Returns the number of deleted rows.
-    pub fn delete_duplicates(&self, id: i64) -> DbResult<i64> {
+    /// Returns a sort order that places a new item above everything in history.
+    pub fn top_sort_order(&self) -> DbResult<String> {
         let inner = self.lock()?;
-        let ci = &inner.schema.clipboard_items;
 
-        // Get the item's content_hash
-        let item: SelectClipboardItems = inner
+        let first: Option<String> = inner
             .db
-            .select(())
-            .from(*ci)
-            .r#where(eq(ci.id, id))
-            .get()
+            .conn()
+            .query_row("SELECT MIN(sort_order) FROM clipboard_items", [], |row| row.get(0))
+            .map_err(e2s)?;
+
+        fractional_index::key_between(None, first.as_deref())
+    }
+
+    /// Records a new clipboard entry in one transaction. Content already in
+    /// history is bumped to `params.sort_order` instead of duplicated, an
+    /// image may absorb near-duplicates (see `image_merge_distance`), and
+    /// history is trimmed to `max_history_items`. Returns the final row.
+    pub fn record_capture(&self, params: InsertClipboardItemParams) -> DbResult<ClipboardItemRow> {
+        let mut guard = self.lock()?;
+        let inner = &mut *guard;
+        let tx = inner.db.conn().unchecked_transaction().map_err(e2s)?;
+
+        let id = upsert_item(&tx, params).map_err(e2s)?;
+        merge_similar_images(&tx, &mut inner.undo, id, &timestamp_now()).map_err(e2s)?;
+        trim_history(&tx, id).map_err(e2s)?;
+        copy_events::prune(&tx, now_millis()).map_err(e2s)?;
+        purge_trash(&tx, now_millis()).map_err(e2s)?;
+        let row = tx
+            .query_row(
+                "SELECT * FROM clipboard_items WHERE id = ?1",
+                rusqlite::params![id],
+                map_item_row,
+            )
             .map_err(e2s)?;
 
-        let Some(hash) = item.content_hash else {
-            return Ok(0);
+        tx.commit().map_err(e2s)?;
+        Ok(ClipboardItemRow::from(row))
+    }
+
+    /// Merges near-duplicate images into the given item when
+    /// `image_merge_distance` is set. Exact duplicates can't exist since
+    /// `content_hash` is unique. Returns the number of trashed rows.
+    pub fn delete_duplicates(&self, id: i64) -> DbResult<i64> {
+        let mut guard = self.lock()?;
+        let inner = &mut *guard;
+        let tx = inner.db.conn().unchecked_transaction().map_err(e2s)?;
+
+        let trashed = merge_similar_images(&tx, &mut inner.undo, id, &timestamp_now()).map_err(e2s)?;
+        tx.commit().map_err(e2s)?;
+        Ok(trashed)
+    }
+
+    /// Items matching a filter query (see `search_query`), in manual order.
+    pub fn search_items(
+        &self,
+        query: &str,
+        limit: i64,
+        offset: i64,
+    ) -> DbResult<Vec<ClipboardItemRow>> {
+        let inner = self.lock()?;
+        let conn = inner.db.conn();
+
+        let terms = search_query::parse(query)?;
+        let date_options = date_options(conn).map_err(e2s)?;
+        let (where_clause, mut params) = search_query::compile(&terms, &date_options)?;
+        params.push(rusqlite::types::Value::Integer(limit));
+        params.push(rusqlite::types::Value::Integer(offset));
+
+        let rows: Vec<SelectClipboardItems> = conn
+            .prepare(&format!(
+                "SELECT * FROM clipboard_items WHERE deleted_at IS NULL AND {} ORDER BY sort_order ASC LIMIT ? OFFSET ?",
+                where_clause
+            ))
+            .and_then(|mut stmt| {
+                stmt.query_map(rusqlite::params_from_iter(params), map_item_row)
+                    .and_then(|rows| rows.collect::<Result<Vec<_>, _>>())
+            })
+            .map_err(e2s)?;
+
+        Ok(rows.into_iter().map(ClipboardItemRow::from).collect())
+    }
+
+    /// Fuzzy-matches `query` against item text, best matches first.
+    pub fn search_fuzzy(&self, query: &str, limit: usize) -> DbResult<Vec<FuzzySearchRow>> {
+        let mut guard = self.lock()?;
+        let inner = &mut *guard;
+        let conn = inner.db.conn();
+
+        let matches = inner.fuzzy.search(conn, query, limit).map_err(e2s)?;
+
+        let mut results = Vec::with_capacity(matches.len());
+        for m in matches {
+            let row = conn
+                .query_row(
+                    "SELECT * FROM clipboard_items WHERE id = ?1",
+                    rusqlite::params![m.id],
+                    map_item_row,
+                )
+                .map_err(e2s)?;
+            results.push(FuzzySearchRow {
+                item: ClipboardItemRow::from(row),
+                score: m.score,
+                indices: m.indices,
+            });
+        }
+
+        Ok(results)
+    }
+
+    /// Image items whose perceptual hash is within `max_distance` bits of the
+    /// given item's, closest first.
+    pub fn find_similar_images(&self, id: i64, max_distance: u32) -> DbResult<Vec<SimilarImageRow>> {
+        let inner = self.lock()?;
+        let conn = inner.db.conn();
+
+        let image_hash: Option<String> = conn
+            .query_row(
+                "SELECT perceptual_hash FROM clipboard_items WHERE id = ?1",
+                rusqlite::params![id],
+                |row| row.get(0),
+            )
+            .map_err(e2s)?;
+        let Some(image_hash) = image_hash.filter(|h| !h.is_empty()) else {
+            return Ok(Vec::new());
         };
 
-        let deleted = inner
+        let mut similar = Vec::new();
+        for (other_id, distance) in similar_image_ids(conn, id, &image_hash, max_distance).map_err(e2s)? {
+            let row = conn
+                .query_row(
+                    "SELECT * FROM clipboard_items WHERE id = ?1",
+                    rusqlite::params![other_id],
+                    map_item_row,
+                )
+                .map_err(e2s)?;
+            similar.push(SimilarImageRow {
+                item: ClipboardItemRow::from(row),
+                distance,
+            });
+        }
+
+        Ok(similar)
+    }
+
+    /// Hashes image rows stored before `perceptual_hash` existed. Decoding runs
+    /// without holding the lock, so this can run in the background; the hashes
+    /// are written in one transaction. Images that can't be decoded get an
+    /// empty hash so they aren't retried on every start.
+    pub fn backfill_perceptual_hashes(&self) -> DbResult<()> {
+        let pending: Vec<i64> = self
+            .lock()?
             .db
             .conn()
-            .execute(
-                "DELETE FROM clipboard_items WHERE content_hash = ?1 AND id != ?2",
+            .prepare("SELECT id FROM clipboard_items WHERE content_type = 'image' AND perceptual_hash IS NULL")
+            .and_then(|mut stmt| {
+                stmt.query_map([], |row| row.get(0))
+                    .and_then(|rows| rows.collect::<Result<Vec<_>, _>>())
+            })
+            .map_err(e2s)?;
+        if pending.is_empty() {
+            return Ok(());
+        }
+
+        let mut hashes = Vec::with_capacity(pending.len());
+        for id in pending {
+            let image_data: Option<String> = self
+                .lock()?
+                .db
+                .conn()
+                .query_row(
+                    "SELECT image_data FROM clipboard_items WHERE id = ?1",
+                    rusqlite::params![id],
+                    |row| row.get(0),
+                )
+                .optional()
+                .map_err(e2s)?
+                .flatten();
+            let hash = image_data
+                .and_then(|data| perceptual_hash::hash_base64_image(&data))
+                .unwrap_or_default();
+            hashes.push((id, hash));
+        }
+
+        let inner = self.lock()?;
+        let tx = inner.db.conn().unchecked_transaction().map_err(e2s)?;
+        for (id, hash) in hashes {
+            tx.execute(
+                "UPDATE clipboard_items SET perceptual_hash = ?1 WHERE id = ?2 AND perceptual_hash IS NULL",
                 rusqlite::params![hash, id],
             )
             .map_err(e2s)?;
-
-        Ok(deleted as i64)
+        }
+        tx.commit().map_err(e2s)
     }
 
     pub fn bump_item(&self, id: i64, sort_order: &str) -> DbResult<ClipboardItemRow> {
@@ -310,15 +887,32 @@ impl Database {
     }
 
     pub fn update_sort_orders(&self, items: Vec<UpdateSortOrderParams>) -> DbResult<()> {
-        for item in items {
-            self.update_sort_order(item.id, &item.sort_order)?;
+        let mut guard = self.lock()?;
+        let inner = &mut *guard;
+        let tx = inner.db.conn().unchecked_transaction().map_err(e2s)?;
+
+        let ids: Vec<i64> = items.iter().map(|item| item.id).collect();
+        let pending = undo::begin(&tx, MutationKind::Reorder, &ids).map_err(e2s)?;
+        {
+            let mut stmt = tx
+                .prepare("UPDATE clipboard_items SET sort_order = ?1 WHERE id = ?2")
+                .map_err(e2s)?;
+            for item in &items {
+                stmt.execute(rusqlite::params![item.sort_order, item.id])
+                    .map_err(e2s)?;
+            }
         }
+        inner.undo.record(&tx, pending, &[], &timestamp_now()).map_err(e2s)?;
+        tx.commit().map_err(e2s)?;
+
         Ok(())
     }
 
     pub fn toggle_favorite(&self, id: i64) -> DbResult<ClipboardItemRow> {
-        let inner = self.lock()?;
+        let mut guard = self.lock()?;
+        let inner = &mut *guard;
         let ci = &inner.schema.clipboard_items;
+        let pending = undo::begin(inner.db.conn(), MutationKind::Favorite, &[id]).map_err(e2s)?;
 
         let current: SelectClipboardItems = inner
             .db
@@ -337,6 +931,10 @@ impl Database {
             .r#where(eq(ci.id, id))
             .execute()
             .map_err(e2s)?;
+        inner
+            .undo
+            .record(inner.db.conn(), pending, &[], &timestamp_now())
+            .map_err(e2s)?;
 
         let row: SelectClipboardItems = inner
             .db
@@ -349,79 +947,1441 @@ impl Database {
         Ok(ClipboardItemRow::from(row))
     }
 
+    /// Moves the item to the trash.
     pub fn delete_item(&self, id: i64) -> DbResult<()> {
-        let inner = self.lock()?;
-        let ci = &inner.schema.clipboard_items;
-
-        inner
-            .db
-            .delete(*ci)
-            .r#where(eq(ci.id, id))
-            .execute()
-            .map_err(e2s)?;
-
-        Ok(())
+        self.batch_delete(&[id]).map(|_| ())
     }
 
+    /// Moves every item except favorites to the trash.
     pub fn clear_all(&self) -> DbResult<()> {
-        let inner = self.lock()?;
-        let ci = &inner.schema.clipboard_items;
+        self.clear_history(&ClearOptions::default()).map(|_| ())
+    }
 
-        inner.db.delete(*ci).execute().map_err(e2s)?;
+    /// Removes the items matching `options`. Moving them to the trash can
+    /// be undone; a permanent clear vacuums the database afterwards.
+    pub fn clear_history(&self, options: &ClearOptions) -> DbResult<ClearSummary> {
+        let mut guard = self.lock()?;
+        let inner = &mut *guard;
+        let conn = inner.db.conn();
+        let size_before = database_size(conn).map_err(e2s)?;
 
-        Ok(())
+        let tx = conn.unchecked_transaction().map_err(e2s)?;
+        let ids = clear_candidates(&tx, options).map_err(e2s)?;
+        if options.permanent {
+            delete_items(&tx, &ids).map_err(e2s)?;
+        } else {
+            let now = timestamp_now();
+            let pending = undo::begin(&tx, MutationKind::Delete, &ids).map_err(e2s)?;
+            trash_items(&tx, &ids, &now).map_err(e2s)?;
+            inner.undo.record(&tx, pending, &[], &now).map_err(e2s)?;
+        }
+        tx.commit().map_err(e2s)?;
+
+        let mut bytes_reclaimed = 0;
+        if options.permanent && !ids.is_empty() {
+            reclaim_space(conn).map_err(e2s)?;
+            bytes_reclaimed = (size_before - database_size(conn).map_err(e2s)?).max(0);
+        }
+
+        Ok(ClearSummary {
+            removed: ids.len() as i64,
+            bytes_reclaimed,
+        })
     }
 
-    pub fn get_item_count(&self) -> DbResult<i64> {
+    /// Items in the trash, most recently deleted first.
+    pub fn get_trash(&self, limit: i64, offset: i64) -> DbResult<Vec<ClipboardItemRow>> {
         let inner = self.lock()?;
-        let ci = &inner.schema.clipboard_items;
 
-        let result: (i64,) = inner
+        let rows: Vec<SelectClipboardItems> = inner
             .db
-            .select((count(ci.id),))
-            .from(*ci)
-            .get()
+            .conn()
+            .prepare(
+                "SELECT * FROM clipboard_items WHERE deleted_at IS NOT NULL
+                 ORDER BY CAST(deleted_at AS INTEGER) DESC, id DESC LIMIT ?1 OFFSET ?2",
+            )
+            .and_then(|mut stmt| {
+                stmt.query_map(rusqlite::params![limit, offset], map_item_row)
+                    .and_then(|rows| rows.collect::<Result<Vec<_>, _>>())
+            })
             .map_err(e2s)?;
 
-        Ok(result.0)
+        Ok(rows.into_iter().map(ClipboardItemRow::from).collect())
     }
 
-    pub fn get_setting(&self, key: &str) -> DbResult<Option<String>> {
-        let inner = self.lock()?;
-        let s = &inner.schema.settings;
-
-        let rows: Vec<SelectSettings> = inner
-            .db
-            .select(())
-            .from(*s)
-            .r#where(eq(s.key, key))
-            .limit(1)
-            .all()
+    /// Takes items back out of the trash, at their old place in history.
+    pub fn restore_items(&self, ids: &[i64]) -> DbResult<BatchSummary> {
+        self.batch_scoped(ids, false, None, |conn, ids, summary| {
+            conn.execute(
+                "UPDATE clipboard_items SET deleted_at = NULL WHERE id IN (SELECT value FROM json_each(?1))",
+                rusqlite::params![id_list(ids)],
+            )
             .map_err(e2s)?;
-
-        Ok(rows.into_iter().next().map(|r| r.value))
+            summary.affected = ids.to_vec();
+            Ok(())
+        })
     }
 
-    pub fn set_setting(&self, key: &str, value: &str) -> DbResult<()> {
+    /// Permanently deletes everything in the trash. Returns the number of items.
+    pub fn empty_trash(&self) -> DbResult<i64> {
         let inner = self.lock()?;
+        let conn = inner.db.conn();
+        let tx = conn.unchecked_transaction().map_err(e2s)?;
 
-        // Upsert via raw SQL since drizzle may not support ON CONFLICT
-        inner
-            .db
-            .conn()
-            .execute(
-                "INSERT INTO settings (key, value) VALUES (?1, ?2) ON CONFLICT(key) DO UPDATE SET value = ?2",
-                rusqlite::params![key, value],
-            )
+        let ids: Vec<i64> = tx
+            .prepare("SELECT id FROM clipboard_items WHERE deleted_at IS NOT NULL")
+            .and_then(|mut stmt| {
+                stmt.query_map([], |row| row.get(0))
+                    .and_then(|rows| rows.collect::<Result<Vec<_>, _>>())
+            })
             .map_err(e2s)?;
+        let deleted = delete_items(&tx, &ids).map_err(e2s)?;
+        tx.commit().map_err(e2s)?;
 
-        Ok(())
+        Ok(deleted as i64)
     }
-}
 
-fn timestamp_now() -> String {
-    let duration = std::time::SystemTime::now()
-        .duration_since(std::time::UNIX_EPOCH)
-        .unwrap_or_default();
-    format!("{}", duration.as_millis())
+    /// Replaces an item with the parts it was split into, e.g. the key and
+    /// value items of an env file, then trashes it. Parts already in history
+    /// are bumped. Undo brings the original back and trashes the new parts.
+    pub fn split_item(
+        &self,
+        id: i64,
+        parts: Vec<InsertClipboardItemParams>,
+    ) -> DbResult<Vec<ClipboardItemRow>> {
+        let mut guard = self.lock()?;
+        let inner = &mut *guard;
+        let tx = inner.db.conn().unchecked_transaction().map_err(e2s)?;
+
+        let mut pending = undo::begin(&tx, MutationKind::Split, &[id]).map_err(e2s)?;
+        let mut part_ids = Vec::with_capacity(parts.len());
+        for params in parts {
+            // Snapshot a part that's already in history before it's bumped
+            if let Some(existing) = find_by_hash(&tx, &params_hash(&params)).map_err(e2s)? {
+                pending.add(&tx, &[existing]).map_err(e2s)?;
+            }
+            part_ids.push(upsert_item(&tx, params).map_err(e2s)?);
+        }
+        let now = timestamp_now();
+        trash_items(&tx, &[id], &now).map_err(e2s)?;
+        inner.undo.record(&tx, pending, &part_ids, &now).map_err(e2s)?;
+
+        let rows = tx
+            .prepare("SELECT c.* FROM json_each(?1) j JOIN clipboard_items c ON c.id = j.value ORDER BY j.key")
+            .and_then(|mut stmt| {
+                stmt.query_map(rusqlite::params![id_list(&part_ids)], map_item_row)
+                    .and_then(|rows| rows.collect::<Result<Vec<_>, _>>())
+            })
+            .map_err(e2s)?;
+        tx.commit().map_err(e2s)?;
+
+        Ok(rows.into_iter().map(ClipboardItemRow::from).collect())
+    }
+
+    /// Reverts the last delete, reorder, favorite change, split or merge.
+    pub fn undo(&self) -> DbResult<Option<MutationKind>> {
+        let mut guard = self.lock()?;
+        let inner = &mut *guard;
+        let tx = inner.db.conn().unchecked_transaction().map_err(e2s)?;
+
+        let kind = inner.undo.undo(&tx).map_err(e2s)?;
+        tx.commit().map_err(e2s)?;
+
+        Ok(kind)
+    }
+
+    pub fn redo(&self) -> DbResult<Option<MutationKind>> {
+        let mut guard = self.lock()?;
+        let inner = &mut *guard;
+        let tx = inner.db.conn().unchecked_transaction().map_err(e2s)?;
+
+        let kind = inner.undo.redo(&tx).map_err(e2s)?;
+        tx.commit().map_err(e2s)?;
+
+        Ok(kind)
+    }
+
+    pub fn undo_state(&self) -> DbResult<UndoState> {
+        Ok(self.lock()?.undo.state())
+    }
+
+    /// Runs `op` in one transaction over the requested ids still in history
+    /// and not in the trash, after filling in the summary's `requested` and
+    /// `missing`. With a mutation kind, the change goes on the undo stack.
+    fn batch<F>(
+        &self,
+        ids: &[i64],
+        undoable: Option<MutationKind>,
+        op: F,
+    ) -> DbResult<BatchSummary>
+    where
+        F: FnOnce(&Connection, &[i64], &mut BatchSummary) -> DbResult<()>,
+    {
+        self.batch_scoped(ids, true, undoable, op)
+    }
+
+    /// `batch`, but trashed items are only left out when `live_only` is set.
+    fn batch_scoped<F>(
+        &self,
+        ids: &[i64],
+        live_only: bool,
+        undoable: Option<MutationKind>,
+        op: F,
+    ) -> DbResult<BatchSummary>
+    where
+        F: FnOnce(&Connection, &[i64], &mut BatchSummary) -> DbResult<()>,
+    {
+        let mut guard = self.lock()?;
+        let inner = &mut *guard;
+        let tx = inner.db.conn().unchecked_transaction().map_err(e2s)?;
+
+        let (existing, missing) = partition_existing(&tx, ids, live_only).map_err(e2s)?;
+        let mut summary = BatchSummary {
+            requested: ids.len(),
+            missing,
+            ..Default::default()
+        };
+        let pending = match undoable {
+            Some(kind) => Some(undo::begin(&tx, kind, &existing).map_err(e2s)?),
+            None => None,
+        };
+        op(&tx, &existing, &mut summary)?;
+        if let Some(pending) = pending {
+            inner.undo.record(&tx, pending, &[], &timestamp_now()).map_err(e2s)?;
+        }
+        tx.commit().map_err(e2s)?;
+
+        Ok(summary)
+    }
+
+    /// Moves the items to the trash.
+    pub fn batch_delete(&self, ids: &[i64]) -> DbResult<BatchSummary> {
+        self.batch(ids, Some(MutationKind::Delete), |conn, ids, summary| {
+            trash_items(conn, ids, &timestamp_now()).map_err(e2s)?;
+            summary.affected = ids.to_vec();
+            Ok(())
+        })
+    }
+
+    pub fn batch_set_favorite(&self, ids: &[i64], favorite: bool) -> DbResult<BatchSummary> {
+        self.batch(ids, Some(MutationKind::Favorite), |conn, ids, summary| {
+            conn.execute(
+                "UPDATE clipboard_items SET is_favorite = ?1 WHERE id IN (SELECT value FROM json_each(?2))",
+                rusqlite::params![favorite as i64, id_list(ids)],
+            )
+            .map_err(e2s)?;
+            summary.affected = ids.to_vec();
+            Ok(())
+        })
+    }
+
+    /// Tags the items. Those already carrying the tag are left out of `affected`.
+    pub fn batch_add_tag(&self, ids: &[i64], tag: &str) -> DbResult<BatchSummary> {
+        let tag = normalize_tag(tag)?;
+        self.batch(ids, None, |conn, ids, summary| {
+            for &id in ids {
+                let inserted = conn
+                    .execute(
+                        "INSERT OR IGNORE INTO item_tags (item_id, tag) VALUES (?1, ?2)",
+                        rusqlite::params![id, tag],
+                    )
+                    .map_err(e2s)?;
+                if inserted > 0 {
+                    summary.affected.push(id);
+                }
+            }
+            Ok(())
+        })
+    }
+
+    pub fn batch_remove_tag(&self, ids: &[i64], tag: &str) -> DbResult<BatchSummary> {
+        let tag = normalize_tag(tag)?;
+        self.batch(ids, None, |conn, ids, summary| {
+            for &id in ids {
+                let deleted = conn
+                    .execute(
+                        "DELETE FROM item_tags WHERE item_id = ?1 AND tag = ?2",
+                        rusqlite::params![id, tag],
+                    )
+                    .map_err(e2s)?;
+                if deleted > 0 {
+                    summary.affected.push(id);
+                }
+            }
+            Ok(())
+        })
+    }
+
+    /// Replaces text items with one item holding their text joined by
+    /// `separator`, in the order given, and trashes them. The new item goes
+    /// to the top with their tags, favorited if any of them was. Undo brings
+    /// the originals back and trashes the merged item.
+    pub fn batch_merge(&self, ids: &[i64], separator: &str) -> DbResult<BatchSummary> {
+        let mut guard = self.lock()?;
+        let inner = &mut *guard;
+        let tx = inner.db.conn().unchecked_transaction().map_err(e2s)?;
+
+        let (existing, missing) = partition_existing(&tx, ids, true).map_err(e2s)?;
+        let (text, used, skipped) = join_texts(&tx, &existing, separator).map_err(e2s)?;
+        if used.len() < 2 {
+            return Err("Select at least two text items to merge".to_string());
+        }
+
+        let mut pending = undo::begin(&tx, MutationKind::Merge, &used).map_err(e2s)?;
+        let first: Option<String> = tx
+            .query_row("SELECT MIN(sort_order) FROM clipboard_items", [], |row| row.get(0))
+            .map_err(e2s)?;
+        let sort_order = fractional_index::key_between(None, first.as_deref())?;
+        let params = InsertClipboardItemParams::text(text, sort_order);
+        // Snapshot the merged text if it's already in history before it's bumped
+        if let Some(existing) = find_by_hash(&tx, &params_hash(&params)).map_err(e2s)? {
+            pending.add(&tx, &[existing]).map_err(e2s)?;
+        }
+        let id = upsert_item(&tx, params).map_err(e2s)?;
+        let sources: Vec<i64> = used.iter().copied().filter(|&source| source != id).collect();
+
+        inherit_tags_and_favorite(&tx, id, &sources).map_err(e2s)?;
+        let now = timestamp_now();
+        trash_items(&tx, &sources, &now).map_err(e2s)?;
+        inner.undo.record(&tx, pending, &[id], &now).map_err(e2s)?;
+
+        let row = tx
+            .query_row(
+                "SELECT * FROM clipboard_items WHERE id = ?1",
+                rusqlite::params![id],
+                map_item_row,
+            )
+            .map_err(e2s)?;
+        tx.commit().map_err(e2s)?;
+
+        Ok(BatchSummary {
+            requested: ids.len(),
+            affected: used,
+            skipped,
+            missing,
+            item: Some(ClipboardItemRow::from(row)),
+        })
+    }
+
+    /// The text of the items joined by `separator`, for copying or exporting.
+    pub fn batch_joined_text(
+        &self,
+        ids: &[i64],
+        separator: &str,
+    ) -> DbResult<(String, BatchSummary)> {
+        let mut joined = String::new();
+        let summary = self.batch(ids, None, |conn, ids, summary| {
+            let (text, used, skipped) = join_texts(conn, ids, separator).map_err(e2s)?;
+            if used.is_empty() {
+                return Err("No text items selected".to_string());
+            }
+            joined = text;
+            summary.affected = used;
+            summary.skipped = skipped;
+            Ok(())
+        })?;
+        Ok((joined, summary))
+    }
+
+    /// Full rows of the items in the order given, for exporting.
+    pub fn batch_items(&self, ids: &[i64]) -> DbResult<(Vec<ClipboardItemRow>, BatchSummary)> {
+        let mut items = Vec::new();
+        let summary = self.batch(ids, None, |conn, ids, summary| {
+            items = conn
+                .prepare("SELECT c.* FROM json_each(?1) j JOIN clipboard_items c ON c.id = j.value ORDER BY j.key")
+                .and_then(|mut stmt| {
+                    stmt.query_map(rusqlite::params![id_list(ids)], map_item_row)
+                        .and_then(|rows| rows.collect::<Result<Vec<_>, _>>())
+                })
+                .map_err(e2s)?
+                .into_iter()
+                .map(ClipboardItemRow::from)
+                .collect();
+            summary.affected = ids.to_vec();
+            Ok(())
+        })?;
+        Ok((items, summary))
+    }
+
+    pub fn get_item_tags(&self, id: i64) -> DbResult<Vec<String>> {
+        let inner = self.lock()?;
+
+        inner
+            .db
+            .conn()
+            .prepare("SELECT tag FROM item_tags WHERE item_id = ?1 ORDER BY tag")
+            .and_then(|mut stmt| {
+                stmt.query_map(rusqlite::params![id], |row| row.get(0))
+                    .and_then(|rows| rows.collect::<Result<Vec<_>, _>>())
+            })
+            .map_err(e2s)
+    }
+
+    /// Every tag in use, most used first.
+    pub fn get_tags(&self) -> DbResult<Vec<TagCount>> {
+        let inner = self.lock()?;
+
+        inner
+            .db
+            .conn()
+            .prepare("SELECT tag, COUNT(*) AS count FROM item_tags GROUP BY tag ORDER BY count DESC, tag ASC")
+            .and_then(|mut stmt| {
+                stmt.query_map([], |row| {
+                    Ok(TagCount {
+                        tag: row.get(0)?,
+                        count: row.get(1)?,
+                    })
+                })
+                .and_then(|rows| rows.collect::<Result<Vec<_>, _>>())
+            })
+            .map_err(e2s)
+    }
+
+    /// Stores OCR output. A no-op if the item was deleted while OCR was running.
+    pub fn set_ocr_text(&self, id: i64, text: &str) -> DbResult<()> {
+        let inner = self.lock()?;
+
+        inner
+            .db
+            .conn()
+            .execute(
+                "UPDATE clipboard_items SET ocr_text = ?1 WHERE id = ?2 AND content_type = 'image'",
+                rusqlite::params![text, id],
+            )
+            .map_err(e2s)?;
+
+        Ok(())
+    }
+
+    /// Items in history, not counting the trash.
+    pub fn get_item_count(&self) -> DbResult<i64> {
+        let inner = self.lock()?;
+
+        inner
+            .db
+            .conn()
+            .query_row(
+                "SELECT COUNT(*) FROM clipboard_items WHERE deleted_at IS NULL",
+                [],
+                |row| row.get(0),
+            )
+            .map_err(e2s)
+    }
+
+    pub fn record_copy_event(
+        &self,
+        item_id: i64,
+        kind: CopyEventKind,
+        source_app: Option<&str>,
+        selection: Option<&str>,
+    ) -> DbResult<()> {
+        let inner = self.lock()?;
+        copy_events::record(
+            inner.db.conn(),
+            item_id,
+            kind,
+            source_app,
+            selection,
+            &timestamp_now(),
+        )
+        .map_err(e2s)
+    }
+
+    pub fn copy_timeline(
+        &self,
+        filter: &TimelineFilter,
+        limit: i64,
+        offset: i64,
+    ) -> DbResult<Vec<CopyEventRow>> {
+        let inner = self.lock()?;
+        copy_events::timeline(inner.db.conn(), filter, limit, offset).map_err(e2s)
+    }
+
+    pub fn usage_stats(&self, period: Period, periods: i64, limit: i64) -> DbResult<UsageStats> {
+        let inner = self.lock()?;
+        stats::collect(inner.db.conn(), period, periods, limit).map_err(e2s)
+    }
+
+    /// Picks up local changes, applies records from other devices that are
+    /// newer, and returns how many were applied with the records to publish.
+    pub fn sync_merge(
+        &self,
+        device_id: &str,
+        scope: SyncScope,
+        remote: &[SyncRecord],
+    ) -> DbResult<(usize, Vec<SyncRecord>)> {
+        let inner = self.lock()?;
+        let conn = inner.db.conn();
+        let tx = conn.unchecked_transaction().map_err(e2s)?;
+        let merged = sync::merge(&tx, device_id, scope, remote, now_millis()).map_err(e2s)?;
+        tx.commit().map_err(e2s)?;
+        Ok(merged)
+    }
+
+    /// Credentials (see `keychain::KEYS`) come from the OS keychain instead.
+    pub fn get_setting(&self, key: &str) -> DbResult<Option<String>> {
+        if keychain::is_secret_setting(key) {
+            return self.keychain.get(key);
+        }
+        let inner = self.lock()?;
+        let s = &inner.schema.settings;
+
+        let rows: Vec<SelectSettings> = inner
+            .db
+            .select(())
+            .from(*s)
+            .r#where(eq(s.key, key))
+            .limit(1)
+            .all()
+            .map_err(e2s)?;
+
+        Ok(rows.into_iter().next().map(|r| r.value))
+    }
+
+    pub fn set_setting(&self, key: &str, value: &str) -> DbResult<()> {
+        if keychain::is_secret_setting(key) {
+            return self.keychain.set(key, value);
+        }
+        let inner = self.lock()?;
+
+        // Upsert via raw SQL since drizzle may not support ON CONFLICT
+        inner
+            .db
+            .conn()
+            .execute(
+                "INSERT INTO settings (key, value) VALUES (?1, ?2) ON CONFLICT(key) DO UPDATE SET value = ?2",
+                rusqlite::params![key, value],
+            )
+            .map_err(e2s)?;
+
+        Ok(())
+    }
+}
+
+// Palettes
+
+impl Database {
+    pub fn create_palette(&self, name: &str, item_ids: &[i64]) -> DbResult<PaletteRow> {
+        let inner = self.lock()?;
+        let conn = inner.db.conn();
+        let tx = conn.unchecked_transaction().map_err(e2s)?;
+
+        tx.execute(
+            "INSERT INTO palettes (name, created_at) VALUES (?1, ?2)",
+            rusqlite::params![name, timestamp_now()],
+        )
+        .map_err(e2s)?;
+        let palette_id = tx.last_insert_rowid();
+        append_palette_items(&tx, palette_id, item_ids).map_err(e2s)?;
+        tx.commit().map_err(e2s)?;
+
+        load_palette(conn, palette_id).map_err(e2s)
+    }
+
+    pub fn add_palette_items(&self, palette_id: i64, item_ids: &[i64]) -> DbResult<PaletteRow> {
+        let inner = self.lock()?;
+        let conn = inner.db.conn();
+        let tx = conn.unchecked_transaction().map_err(e2s)?;
+        append_palette_items(&tx, palette_id, item_ids).map_err(e2s)?;
+        tx.commit().map_err(e2s)?;
+
+        load_palette(conn, palette_id).map_err(e2s)
+    }
+
+    pub fn get_palette(&self, id: i64) -> DbResult<PaletteRow> {
+        let inner = self.lock()?;
+        load_palette(inner.db.conn(), id).map_err(e2s)
+    }
+
+    pub fn get_palettes(&self) -> DbResult<Vec<PaletteRow>> {
+        let inner = self.lock()?;
+        let conn = inner.db.conn();
+
+        let ids: Vec<i64> = conn
+            .prepare("SELECT id FROM palettes ORDER BY created_at DESC, id DESC")
+            .and_then(|mut stmt| {
+                stmt.query_map([], |row| row.get(0))
+                    .and_then(|rows| rows.collect::<Result<Vec<_>, _>>())
+            })
+            .map_err(e2s)?;
+
+        ids.into_iter()
+            .map(|id| load_palette(conn, id).map_err(e2s))
+            .collect()
+    }
+
+    pub fn delete_palette(&self, id: i64) -> DbResult<()> {
+        let inner = self.lock()?;
+        let conn = inner.db.conn();
+        let tx = conn.unchecked_transaction().map_err(e2s)?;
+
+        tx.execute(
+            "DELETE FROM palette_items WHERE palette_id = ?1",
+            rusqlite::params![id],
+        )
+        .map_err(e2s)?;
+        tx.execute("DELETE FROM palettes WHERE id = ?1", rusqlite::params![id])
+            .map_err(e2s)?;
+        tx.commit().map_err(e2s)?;
+
+        Ok(())
+    }
+}
+
+fn append_palette_items(conn: &Connection, palette_id: i64, item_ids: &[i64]) -> rusqlite::Result<()> {
+    let next_position: i64 = conn.query_row(
+        "SELECT COALESCE(MAX(position) + 1, 0) FROM palette_items WHERE palette_id = ?1",
+        rusqlite::params![palette_id],
+        |row| row.get(0),
+    )?;
+
+    for (offset, item_id) in item_ids.iter().enumerate() {
+        conn.execute(
+            "INSERT INTO palette_items (palette_id, item_id, position) VALUES (?1, ?2, ?3)",
+            rusqlite::params![palette_id, item_id, next_position + offset as i64],
+        )?;
+    }
+
+    Ok(())
+}
+
+fn load_palette(conn: &Connection, id: i64) -> rusqlite::Result<PaletteRow> {
+    let (name, created_at): (String, String) = conn.query_row(
+        "SELECT name, created_at FROM palettes WHERE id = ?1",
+        rusqlite::params![id],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )?;
+
+    // Inner join so colors whose item was deleted simply drop out of the palette
+    let colors = conn
+        .prepare(
+            "SELECT ci.id, ci.text_content, ci.detected_color, ci.kv_key FROM palette_items pi JOIN clipboard_items ci ON ci.id = pi.item_id WHERE pi.palette_id = ?1 ORDER BY pi.position ASC",
+        )?
+        .query_map(rusqlite::params![id], |row| {
+            Ok(PaletteColorRow {
+                item_id: row.get(0)?,
+                text_content: row.get(1)?,
+                detected_color: row.get(2)?,
+                kv_key: row.get(3)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(PaletteRow {
+        id,
+        name,
+        created_at,
+        colors,
+    })
+}
+
+// Link previews
+
+impl Database {
+    pub fn get_link_preview(&self, url: &str) -> DbResult<Option<LinkPreviewRow>> {
+        let inner = self.lock()?;
+        let lp = &inner.schema.link_previews;
+
+        let rows: Vec<SelectLinkPreviews> = inner
+            .db
+            .select(())
+            .from(*lp)
+            .r#where(eq(lp.url, url))
+            .limit(1)
+            .all()
+            .map_err(e2s)?;
+
+        Ok(rows.into_iter().next().map(LinkPreviewRow::from))
+    }
+
+    pub fn upsert_link_preview(&self, row: &LinkPreviewRow) -> DbResult<()> {
+        let inner = self.lock()?;
+
+        inner
+            .db
+            .conn()
+            .execute(
+                "INSERT INTO link_previews (url, title, description, image, favicon, site_name, image_path, favicon_path, fetched_at) \
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9) \
+                 ON CONFLICT(url) DO UPDATE SET title = ?2, description = ?3, image = ?4, favicon = ?5, \
+                 site_name = ?6, image_path = ?7, favicon_path = ?8, fetched_at = ?9",
+                rusqlite::params![
+                    row.url,
+                    row.title,
+                    row.description,
+                    row.image,
+                    row.favicon,
+                    row.site_name,
+                    row.image_path,
+                    row.favicon_path,
+                    row.fetched_at,
+                ],
+            )
+            .map_err(e2s)?;
+
+        Ok(())
+    }
+
+    pub fn clear_link_previews(&self) -> DbResult<()> {
+        let inner = self.lock()?;
+        let lp = &inner.schema.link_previews;
+
+        inner.db.delete(*lp).execute().map_err(e2s)?;
+
+        Ok(())
+    }
+}
+
+// LAN peers
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LanPeerRow {
+    pub public_key: String,
+    pub name: String,
+    pub address: Option<String>,
+    pub paired_at: String,
+}
+
+impl From<SelectLanPeers> for LanPeerRow {
+    fn from(row: SelectLanPeers) -> Self {
+        Self {
+            public_key: row.public_key,
+            name: row.name,
+            address: row.address,
+            paired_at: row.paired_at,
+        }
+    }
+}
+
+impl Database {
+    pub fn get_lan_peers(&self) -> DbResult<Vec<LanPeerRow>> {
+        let inner = self.lock()?;
+        let lp = &inner.schema.lan_peers;
+
+        let rows: Vec<SelectLanPeers> = inner.db.select(()).from(*lp).all().map_err(e2s)?;
+
+        let mut peers: Vec<LanPeerRow> = rows.into_iter().map(LanPeerRow::from).collect();
+        peers.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(peers)
+    }
+
+    pub fn get_lan_peer(&self, public_key: &str) -> DbResult<Option<LanPeerRow>> {
+        let inner = self.lock()?;
+        let lp = &inner.schema.lan_peers;
+
+        let rows: Vec<SelectLanPeers> = inner
+            .db
+            .select(())
+            .from(*lp)
+            .r#where(eq(lp.public_key, public_key))
+            .limit(1)
+            .all()
+            .map_err(e2s)?;
+
+        Ok(rows.into_iter().next().map(LanPeerRow::from))
+    }
+
+    /// Adds a peer, or renames and re-addresses one paired before.
+    pub fn save_lan_peer(&self, row: &LanPeerRow) -> DbResult<()> {
+        let inner = self.lock()?;
+
+        inner
+            .db
+            .conn()
+            .execute(
+                "INSERT INTO lan_peers (public_key, name, address, paired_at) VALUES (?1, ?2, ?3, ?4) \
+                 ON CONFLICT(public_key) DO UPDATE SET name = ?2, address = COALESCE(?3, address), paired_at = ?4",
+                rusqlite::params![row.public_key, row.name, row.address, row.paired_at],
+            )
+            .map_err(e2s)?;
+
+        Ok(())
+    }
+
+    pub fn set_lan_peer_address(&self, public_key: &str, address: &str) -> DbResult<()> {
+        let inner = self.lock()?;
+
+        inner
+            .db
+            .conn()
+            .execute(
+                "UPDATE lan_peers SET address = ?1 WHERE public_key = ?2",
+                rusqlite::params![address, public_key],
+            )
+            .map_err(e2s)?;
+
+        Ok(())
+    }
+
+    pub fn delete_lan_peer(&self, public_key: &str) -> DbResult<()> {
+        let inner = self.lock()?;
+
+        inner
+            .db
+            .conn()
+            .execute(
+                "DELETE FROM lan_peers WHERE public_key = ?1",
+                rusqlite::params![public_key],
+            )
+            .map_err(e2s)?;
+
+        Ok(())
+    }
+}
+
+// Registers
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RegisterRow {
+    pub name: String,
+    pub content_type: String,
+    pub text_content: Option<String>,
+    pub image_data: Option<String>,
+    pub image_width: Option<i64>,
+    pub image_height: Option<i64>,
+    pub updated_at: String,
+}
+
+impl From<SelectRegisters> for RegisterRow {
+    fn from(row: SelectRegisters) -> Self {
+        Self {
+            name: row.name,
+            content_type: row.content_type,
+            text_content: row.text_content,
+            image_data: row.image_data,
+            image_width: row.image_width,
+            image_height: row.image_height,
+            updated_at: row.updated_at,
+        }
+    }
+}
+
+impl Database {
+    /// The filled registers, digits first.
+    pub fn get_registers(&self) -> DbResult<Vec<RegisterRow>> {
+        let inner = self.lock()?;
+        let r = &inner.schema.registers;
+
+        let rows: Vec<SelectRegisters> = inner.db.select(()).from(*r).all().map_err(e2s)?;
+
+        let mut registers: Vec<RegisterRow> = rows.into_iter().map(RegisterRow::from).collect();
+        registers.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(registers)
+    }
+
+    pub fn get_register(&self, name: &str) -> DbResult<Option<RegisterRow>> {
+        let inner = self.lock()?;
+        let r = &inner.schema.registers;
+
+        let rows: Vec<SelectRegisters> = inner
+            .db
+            .select(())
+            .from(*r)
+            .r#where(eq(r.name, name))
+            .limit(1)
+            .all()
+            .map_err(e2s)?;
+
+        Ok(rows.into_iter().next().map(RegisterRow::from))
+    }
+
+    /// Fills a register, replacing what it held.
+    pub fn set_register(&self, row: &RegisterRow) -> DbResult<()> {
+        let inner = self.lock()?;
+
+        inner
+            .db
+            .conn()
+            .execute(
+                "INSERT OR REPLACE INTO registers (name, content_type, text_content, image_data, image_width, image_height, updated_at) \
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                rusqlite::params![
+                    row.name,
+                    row.content_type,
+                    row.text_content,
+                    row.image_data,
+                    row.image_width,
+                    row.image_height,
+                    row.updated_at
+                ],
+            )
+            .map_err(e2s)?;
+
+        Ok(())
+    }
+
+    /// Copies a history item into a register. The register keeps its copy
+    /// when the item is deleted.
+    pub fn set_register_from_item(&self, name: &str, id: i64) -> DbResult<RegisterRow> {
+        let item = self.get_item(id)?;
+        let row = RegisterRow {
+            name: name.to_string(),
+            content_type: item.content_type,
+            text_content: item.text_content,
+            image_data: item.image_data,
+            image_width: item.image_width,
+            image_height: item.image_height,
+            updated_at: now_millis().to_string(),
+        };
+        self.set_register(&row)?;
+        Ok(row)
+    }
+
+    pub fn clear_register(&self, name: &str) -> DbResult<()> {
+        let inner = self.lock()?;
+
+        inner
+            .db
+            .conn()
+            .execute(
+                "DELETE FROM registers WHERE name = ?1",
+                rusqlite::params![name],
+            )
+            .map_err(e2s)?;
+
+        Ok(())
+    }
+}
+
+/// Reads a setting without going through `get_setting`, which would re-lock
+/// the database.
+pub(crate) fn setting_value(conn: &Connection, key: &str) -> rusqlite::Result<Option<String>> {
+    conn.query_row(
+        "SELECT value FROM settings WHERE key = ?1",
+        rusqlite::params![key],
+        |row| row.get(0),
+    )
+    .optional()
+}
+
+fn date_options(conn: &Connection) -> rusqlite::Result<DateOptions> {
+    let order = setting_value(conn, DATE_ORDER_SETTING)?;
+    Ok(DateOptions::from_setting(order.as_deref()))
+}
+
+/// Ids of other image items within `max_distance` of `image_hash`, closest first.
+fn similar_image_ids(
+    conn: &Connection,
+    id: i64,
+    image_hash: &str,
+    max_distance: u32,
+) -> rusqlite::Result<Vec<(i64, u32)>> {
+    let mut matches: Vec<(i64, u32)> = conn
+        .prepare("SELECT id, perceptual_hash FROM clipboard_items WHERE content_type = 'image' AND perceptual_hash IS NOT NULL AND perceptual_hash != '' AND deleted_at IS NULL AND id != ?1")?
+        .query_map(rusqlite::params![id], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?)))?
+        .filter_map(|row| match row {
+            Ok((other_id, other_hash)) => perceptual_hash::distance(image_hash, &other_hash)
+                .filter(|d| *d <= max_distance)
+                .map(|d| Ok((other_id, d))),
+            Err(e) => Some(Err(e)),
+        })
+        .collect::<Result<_, _>>()?;
+
+    matches.sort_by_key(|&(other_id, distance)| (distance, other_id));
+    Ok(matches)
+}
+
+/// The `image_merge_distance` setting, if merging is enabled.
+fn merge_distance(conn: &Connection) -> rusqlite::Result<Option<u32>> {
+    Ok(setting_value(conn, MERGE_DISTANCE_SETTING)?.and_then(|v| v.trim().parse().ok()))
+}
+
+/// Trashes images within `image_merge_distance` of `id`, carrying their tags
+/// and favorite flag over to it, as one undoable merge. Returns the number of
+/// rows trashed.
+fn merge_similar_images(conn: &Connection, undo: &mut UndoStack, id: i64, now: &str) -> rusqlite::Result<i64> {
+    let Some(max_distance) = merge_distance(conn)? else {
+        return Ok(0);
+    };
+    let image_hash: Option<String> = conn
+        .query_row(
+            "SELECT perceptual_hash FROM clipboard_items WHERE id = ?1",
+            rusqlite::params![id],
+            |row| row.get(0),
+        )
+        .optional()?
+        .flatten();
+    let Some(image_hash) = image_hash.filter(|h| !h.is_empty()) else {
+        return Ok(0);
+    };
+
+    let duplicates: Vec<i64> = similar_image_ids(conn, id, &image_hash, max_distance)?
+        .into_iter()
+        .map(|(other_id, _)| other_id)
+        .collect();
+    if duplicates.is_empty() {
+        return Ok(0);
+    }
+
+    let mut touched = duplicates.clone();
+    touched.push(id);
+    let pending = undo::begin(conn, MutationKind::Merge, &touched)?;
+    inherit_tags_and_favorite(conn, id, &duplicates)?;
+    let trashed = trash_items(conn, &duplicates, now)?;
+    undo.record(conn, pending, &[], now)?;
+    Ok(trashed as i64)
+}
+
+/// Gives `id` the tags of `sources`, and favorites it if any of them is.
+fn inherit_tags_and_favorite(conn: &Connection, id: i64, sources: &[i64]) -> rusqlite::Result<()> {
+    conn.execute(
+        "INSERT OR IGNORE INTO item_tags (item_id, tag)
+         SELECT ?1, tag FROM item_tags WHERE item_id IN (SELECT value FROM json_each(?2))",
+        rusqlite::params![id, id_list(sources)],
+    )?;
+    conn.execute(
+        "UPDATE clipboard_items SET is_favorite = 1 WHERE id = ?1
+         AND EXISTS(SELECT 1 FROM clipboard_items WHERE is_favorite = 1 AND id IN (SELECT value FROM json_each(?2)))",
+        rusqlite::params![id, id_list(sources)],
+    )?;
+    Ok(())
+}
+
+/// Deletes `duplicate` after moving its copy count, favorite flag, palette
+/// memberships and copy events onto `keep`. Returns the number of rows removed.
+fn merge_item_into(conn: &Connection, keep: i64, duplicate: i64) -> rusqlite::Result<i64> {
+    conn.execute(
+        "UPDATE clipboard_items SET copy_count = copy_count + (SELECT copy_count FROM clipboard_items WHERE id = ?2), is_favorite = MAX(is_favorite, (SELECT is_favorite FROM clipboard_items WHERE id = ?2)) WHERE id = ?1",
+        rusqlite::params![keep, duplicate],
+    )?;
+    conn.execute(
+        "UPDATE palette_items SET item_id = ?1 WHERE item_id = ?2",
+        rusqlite::params![keep, duplicate],
+    )?;
+    conn.execute(
+        "UPDATE copy_events SET item_id = ?1 WHERE item_id = ?2",
+        rusqlite::params![keep, duplicate],
+    )?;
+    // Tags `keep` already has stay behind and go with the duplicate
+    conn.execute(
+        "UPDATE OR IGNORE item_tags SET item_id = ?1 WHERE item_id = ?2",
+        rusqlite::params![keep, duplicate],
+    )?;
+    conn.execute("DELETE FROM item_tags WHERE item_id = ?1", rusqlite::params![duplicate])?;
+    let deleted = conn.execute("DELETE FROM clipboard_items WHERE id = ?1", rusqlite::params![duplicate])?;
+    Ok(deleted as i64)
+}
+
+pub(crate) fn params_hash(params: &InsertClipboardItemParams) -> String {
+    compute_content_hash(
+        &params.content_type,
+        &params.text_content,
+        &params.image_data,
+        &params.kv_key,
+    )
+}
+
+/// The item with this content, trashed or not.
+pub(crate) fn find_by_hash(conn: &Connection, content_hash: &str) -> rusqlite::Result<Option<i64>> {
+    conn.query_row(
+        "SELECT id FROM clipboard_items WHERE content_hash = ?1",
+        rusqlite::params![content_hash],
+        |row| row.get(0),
+    )
+    .optional()
+}
+
+/// Bumps the row with the same content, or inserts a new one. Returns its id.
+/// Copying something that's in the trash takes it back out.
+fn upsert_item(conn: &Connection, params: InsertClipboardItemParams) -> rusqlite::Result<i64> {
+    let content_hash = params_hash(&params);
+
+    if let Some(id) = find_by_hash(conn, &content_hash)? {
+        conn.execute(
+            "UPDATE clipboard_items SET sort_order = ?1, updated_at = ?2, copy_count = copy_count + 1, deleted_at = NULL WHERE id = ?3",
+            rusqlite::params![params.sort_order, params.updated_at, id],
+        )?;
+        if !copy_events::is_restore_echo(conn, id, &params.updated_at)? {
+            record_captured(conn, id, &params)?;
+        }
+        return Ok(id);
+    }
+
+    let id = insert_item(conn, &params, &content_hash)?;
+    record_captured(conn, id, &params)?;
+    Ok(id)
+}
+
+/// Inserts a row for content not yet in history, classifying and hashing it
+/// on the way. Returns its id.
+pub(crate) fn insert_item(
+    conn: &Connection,
+    params: &InsertClipboardItemParams,
+    content_hash: &str,
+) -> rusqlite::Result<i64> {
+    let date_options = date_options(conn)?;
+    let classification = classify(
+        &params.content_type,
+        params.text_content.as_deref(),
+        &date_options,
+    );
+    let detected_date = params.detected_date.clone().or(classification.detected_date);
+    let detected_color = params.detected_color.clone().or(classification.detected_color);
+    let image_hash = match (params.content_type.as_str(), &params.image_data) {
+        // Empty when the image can't be decoded, so the backfill skips it
+        ("image", Some(data)) => Some(perceptual_hash::hash_base64_image(data).unwrap_or_default()),
+        _ => None,
+    };
+
+    conn.execute(
+        "INSERT INTO clipboard_items (content_type, text_content, image_data, image_width, image_height, char_count, line_count, source_app, source_device, is_favorite, sort_order, copy_count, kv_key, detected_date, detected_color, content_hash, content_kind, code_language, perceptual_hash, created_at, updated_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, 0, ?10, 1, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19)",
+        rusqlite::params![
+            params.content_type,
+            params.text_content,
+            params.image_data,
+            params.image_width,
+            params.image_height,
+            params.char_count,
+            params.line_count,
+            params.source_app,
+            params.source_device,
+            params.sort_order,
+            params.kv_key,
+            detected_date,
+            detected_color,
+            content_hash,
+            classification.kind.as_str(),
+            classification.language,
+            image_hash,
+            params.created_at,
+            params.updated_at,
+        ],
+    )?;
+    Ok(conn.last_insert_rowid())
+}
+
+fn record_captured(conn: &Connection, id: i64, params: &InsertClipboardItemParams) -> rusqlite::Result<()> {
+    copy_events::record(
+        conn,
+        id,
+        CopyEventKind::Captured,
+        params.source_app.as_deref(),
+        params.selection.as_deref(),
+        &params.updated_at,
+    )
+}
+
+/// Deletes the lowest non-favorite items beyond `max_history_items`, never
+/// touching `keep_id`. Goes through `delete_items` so nothing is left pointing
+/// at them.
+fn trim_history(conn: &Connection, keep_id: i64) -> rusqlite::Result<()> {
+    let Some(max_items) = setting_value(conn, MAX_HISTORY_SETTING)?
+        .and_then(|v| v.trim().parse::<i64>().ok())
+        .filter(|n| *n > 0)
+    else {
+        return Ok(());
+    };
+
+    let ids: Vec<i64> = conn
+        .prepare(
+            "SELECT id FROM clipboard_items WHERE is_favorite = 0 AND deleted_at IS NULL AND id != ?2
+             ORDER BY sort_order ASC LIMIT -1 OFFSET ?1",
+        )?
+        .query_map(rusqlite::params![max_items - 1, keep_id], |row| row.get(0))?
+        .collect::<Result<_, _>>()?;
+    if !ids.is_empty() {
+        delete_items(conn, &ids)?;
+    }
+    Ok(())
+}
+
+/// JSON array of ids, for `json_each` in place of a variable-length IN list.
+pub(crate) fn id_list(ids: &[i64]) -> String {
+    serde_json::to_string(ids).unwrap_or_default()
+}
+
+/// Splits `ids` into those in history and those not, in order, without repeats.
+/// Trashed items count as missing when `live_only` is set.
+fn partition_existing(
+    conn: &Connection,
+    ids: &[i64],
+    live_only: bool,
+) -> rusqlite::Result<(Vec<i64>, Vec<i64>)> {
+    let mut stmt = conn.prepare(
+        "SELECT EXISTS(SELECT 1 FROM clipboard_items WHERE id = ?1 AND (?2 = 0 OR deleted_at IS NULL))",
+    )?;
+    let mut seen = HashSet::new();
+    let (mut existing, mut missing) = (Vec::new(), Vec::new());
+    for &id in ids {
+        if !seen.insert(id) {
+            continue;
+        }
+        if stmt.query_row(rusqlite::params![id, live_only], |row| row.get(0))? {
+            existing.push(id);
+        } else {
+            missing.push(id);
+        }
+    }
+    Ok((existing, missing))
+}
+
+/// Ids of the items `options` selects for clearing.
+fn clear_candidates(conn: &Connection, options: &ClearOptions) -> rusqlite::Result<Vec<i64>> {
+    let candidates: Vec<(i64, Option<String>)> = conn
+        .prepare(
+            "SELECT id, text_content FROM clipboard_items
+             WHERE (?1 OR deleted_at IS NULL)
+               AND (?2 = 0 OR is_favorite = 0)
+               AND (?3 IS NULL OR CAST(updated_at AS INTEGER) < ?3)
+               AND (?4 IS NULL OR content_type = ?4)
+               AND (?5 IS NULL OR source_app = ?5)",
+        )?
+        .query_map(
+            rusqlite::params![
+                options.permanent,
+                options.keep_favorites,
+                options.before,
+                options.content_type,
+                options.source_app
+            ],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )?
+        .collect::<Result<_, _>>()?;
+
+    Ok(candidates
+        .into_iter()
+        .filter(|(_, text)| !options.secrets_only || text.as_deref().is_some_and(secrets::is_secret))
+        .map(|(id, _)| id)
+        .collect())
+}
+
+/// Size of the database in bytes, excluding the WAL.
+fn database_size(conn: &Connection) -> rusqlite::Result<i64> {
+    let page_count: i64 = conn.query_row("PRAGMA page_count", [], |row| row.get(0))?;
+    let page_size: i64 = conn.query_row("PRAGMA page_size", [], |row| row.get(0))?;
+    Ok(page_count * page_size)
+}
+
+/// Returns free pages to the filesystem: incrementally when the database
+/// was created with `auto_vacuum = INCREMENTAL`, otherwise with a full VACUUM.
+fn reclaim_space(conn: &Connection) -> rusqlite::Result<()> {
+    let auto_vacuum: i64 = conn.query_row("PRAGMA auto_vacuum", [], |row| row.get(0))?;
+    if auto_vacuum == 2 {
+        conn.execute_batch("PRAGMA incremental_vacuum;")
+    } else {
+        conn.execute_batch("VACUUM;")
+    }
+}
+
+fn trash_items(conn: &Connection, ids: &[i64], now: &str) -> rusqlite::Result<usize> {
+    conn.execute(
+        "UPDATE clipboard_items SET deleted_at = ?1 WHERE id IN (SELECT value FROM json_each(?2))",
+        rusqlite::params![now, id_list(ids)],
+    )
+}
+
+/// Permanently deletes items trashed longer ago than `trash_retention_days`.
+fn purge_trash(conn: &Connection, now_ms: i64) -> rusqlite::Result<usize> {
+    let days = setting_value(conn, TRASH_RETENTION_SETTING)?
+        .and_then(|v| v.trim().parse::<i64>().ok())
+        .filter(|n| *n >= 0)
+        .unwrap_or(DEFAULT_TRASH_RETENTION_DAYS);
+    let cutoff = now_ms - days * 24 * 60 * 60 * 1000;
+
+    let ids: Vec<i64> = conn
+        .prepare("SELECT id FROM clipboard_items WHERE deleted_at IS NOT NULL AND CAST(deleted_at AS INTEGER) < ?1")?
+        .query_map(rusqlite::params![cutoff], |row| row.get(0))?
+        .collect::<Result<_, _>>()?;
+    if ids.is_empty() {
+        return Ok(0);
+    }
+    delete_items(conn, &ids)
+}
+
+/// Deletes items along with their palette memberships, tags and copy events.
+fn delete_items(conn: &Connection, ids: &[i64]) -> rusqlite::Result<usize> {
+    let ids = id_list(ids);
+    for table in ["palette_items", "item_tags", "copy_events"] {
+        conn.execute(
+            &format!("DELETE FROM {} WHERE item_id IN (SELECT value FROM json_each(?1))", table),
+            rusqlite::params![ids],
+        )?;
+    }
+    conn.execute(
+        "DELETE FROM clipboard_items WHERE id IN (SELECT value FROM json_each(?1))",
+        rusqlite::params![ids],
+    )
+}
+
+/// Joins the text of text items in the order given. Returns the text, the
+/// ids it came from and the ids skipped for having none.
+fn join_texts(
+    conn: &Connection,
+    ids: &[i64],
+    separator: &str,
+) -> rusqlite::Result<(String, Vec<i64>, Vec<i64>)> {
+    let mut stmt = conn.prepare(
+        "SELECT c.id, c.text_content FROM json_each(?1) j JOIN clipboard_items c ON c.id = j.value
+         WHERE c.content_type = 'text' ORDER BY j.key",
+    )?;
+    let texts: Vec<(i64, Option<String>)> = stmt
+        .query_map(rusqlite::params![id_list(ids)], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<Result<_, _>>()?;
+
+    let mut parts = Vec::new();
+    let mut used = Vec::new();
+    for (id, text) in texts {
+        if let Some(text) = text {
+            parts.push(text);
+            used.push(id);
+        }
+    }
+    let skipped = ids.iter().copied().filter(|id| !used.contains(id)).collect();
+    Ok((parts.join(separator), used, skipped))
+}
+
+fn normalize_tag(tag: &str) -> DbResult<String> {
+    let tag = tag.trim();
+    if tag.is_empty() {
+        return Err("Tag cannot be empty".to_string());
+    }
+    Ok(tag.to_string())
+}
+
+/// Full-text index over `text_content` and `ocr_text`. The trigram tokenizer
+/// matches any substring of three or more characters, which is what plain-text
+/// search asks for; triggers keep it in step with `clipboard_items`.
+fn ensure_search_index(conn: &Connection) -> rusqlite::Result<()> {
+    // Superseded: a B-tree can't serve `LIKE '%q%'`
+    conn.execute("DROP INDEX IF EXISTS idx_clipboard_items_ocr_text", [])?;
+
+    let exists: bool = conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'clipboard_items_fts')",
+        [],
+        |row| row.get(0),
+    )?;
+
+    conn.execute_batch(
+        "CREATE VIRTUAL TABLE IF NOT EXISTS clipboard_items_fts USING fts5(
+             text_content, ocr_text, content = 'clipboard_items', content_rowid = 'id', tokenize = 'trigram'
+         );
+         CREATE TRIGGER IF NOT EXISTS clipboard_items_fts_insert AFTER INSERT ON clipboard_items BEGIN
+             INSERT INTO clipboard_items_fts (rowid, text_content, ocr_text)
+             VALUES (new.id, new.text_content, new.ocr_text);
+         END;
+         CREATE TRIGGER IF NOT EXISTS clipboard_items_fts_delete AFTER DELETE ON clipboard_items BEGIN
+             INSERT INTO clipboard_items_fts (clipboard_items_fts, rowid, text_content, ocr_text)
+             VALUES ('delete', old.id, old.text_content, old.ocr_text);
+         END;
+         CREATE TRIGGER IF NOT EXISTS clipboard_items_fts_update AFTER UPDATE OF text_content, ocr_text ON clipboard_items BEGIN
+             INSERT INTO clipboard_items_fts (clipboard_items_fts, rowid, text_content, ocr_text)
+             VALUES ('delete', old.id, old.text_content, old.ocr_text);
+             INSERT INTO clipboard_items_fts (rowid, text_content, ocr_text)
+             VALUES (new.id, new.text_content, new.ocr_text);
+         END;",
+    )?;
+
+    // Index the rows stored before the table existed
+    if !exists {
+        conn.execute(
+            "INSERT INTO clipboard_items_fts (clipboard_items_fts) VALUES ('rebuild')",
+            [],
+        )?;
+    }
+    Ok(())
+}
+
+/// One-time migration to a unique `content_hash` index: rehashes key-value
+/// rows, whose hash now includes the key, then merges rows sharing a hash
+/// into the top-most one.
+fn ensure_unique_content_hash(conn: &Connection) -> rusqlite::Result<()> {
+    let migrated: bool = conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM sqlite_master WHERE type = 'index' AND name = 'idx_clipboard_items_content_hash_unique')",
+        [],
+        |row| row.get(0),
+    )?;
+    if migrated {
+        return Ok(());
+    }
+
+    let tx = conn.unchecked_transaction()?;
+
+    let kv_rows: Vec<(i64, String, Option<String>, Option<String>, Option<String>)> = tx
+        .prepare("SELECT id, content_type, text_content, image_data, kv_key FROM clipboard_items WHERE kv_key IS NOT NULL")?
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?, row.get(4)?)))?
+        .collect::<Result<_, _>>()?;
+    for (id, content_type, text_content, image_data, kv_key) in kv_rows {
+        let hash = compute_content_hash(&content_type, &text_content, &image_data, &kv_key);
+        tx.execute(
+            "UPDATE clipboard_items SET content_hash = ?1 WHERE id = ?2",
+            rusqlite::params![hash, id],
+        )?;
+    }
+
+    let duplicates: Vec<(i64, i64)> = tx
+        .prepare("SELECT keep_id, id FROM (SELECT id, FIRST_VALUE(id) OVER (PARTITION BY content_hash ORDER BY sort_order, id) AS keep_id FROM clipboard_items WHERE content_hash IS NOT NULL) WHERE id != keep_id")?
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<Result<_, _>>()?;
+    for (keep, duplicate) in duplicates {
+        merge_item_into(&tx, keep, duplicate)?;
+    }
+
+    tx.execute_batch(
+        "DROP INDEX IF EXISTS idx_clipboard_items_content_hash;
+         CREATE UNIQUE INDEX idx_clipboard_items_content_hash_unique ON clipboard_items(content_hash);",
+    )?;
+    tx.commit()
+}
+
+/// Moves credentials stored in the settings table before they went to the
+/// keychain. Rows stay put while the keychain is unavailable.
+fn move_secrets_to_keychain(conn: &Connection, keychain: &Keychain) -> rusqlite::Result<()> {
+    for &key in keychain::KEYS {
+        let Some(value) = setting_value(conn, key)? else {
+            continue;
+        };
+        if keychain.set(key, &value).is_ok() {
+            conn.execute("DELETE FROM settings WHERE key = ?1", rusqlite::params![key])?;
+        }
+    }
+    Ok(())
+}
+
+/// Classifies rows stored before `content_kind` existed so the frontend never has to.
+fn backfill_content_kind(conn: &Connection) -> rusqlite::Result<()> {
+    let date_options = date_options(conn)?;
+    let pending: Vec<(i64, String, Option<String>)> = conn
+        .prepare("SELECT id, content_type, text_content FROM clipboard_items WHERE content_kind IS NULL")?
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?
+        .collect::<Result<_, _>>()?;
+
+    // One transaction, not a commit per row
+    let tx = conn.unchecked_transaction()?;
+    for (id, content_type, text_content) in pending {
+        let classification = classify(&content_type, text_content.as_deref(), &date_options);
+        tx.execute(
+            "UPDATE clipboard_items SET content_kind = ?1, code_language = ?2 WHERE id = ?3",
+            rusqlite::params![classification.kind.as_str(), classification.language, id],
+        )?;
+    }
+    tx.commit()
+}
+
+fn timestamp_now() -> String {
+    format!("{}", now_millis())
+}
+
+/// Epoch milliseconds, the unit every stored timestamp uses.
+pub(crate) fn now_millis() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as i64
+}
+
+/// Lower-case hex of `bytes`.
+pub(crate) fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Bytes of a hex string, or `None` unless it's pairs of hex digits.
+pub(crate) fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    let hex = hex.trim();
+    if hex.len() % 2 != 0 || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).ok())
+        .collect()
 }
@@ -0,0 +1,206 @@
+//! Port of the fractional-indexing key generator used by the frontend
+//! (`jittered-fractional-indexing`), so the backend can place items it
+//! creates itself without a round trip through the UI.
+
+const DIGITS: &[u8] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+const ZERO: u8 = DIGITS[0];
+const SMALLEST_INTEGER: &str = "A00000000000000000000000000";
+
+fn digit_index(c: u8) -> Result<usize, String> {
+    DIGITS
+        .iter()
+        .position(|&d| d == c)
+        .ok_or_else(|| format!("Invalid order key digit: {}", c as char))
+}
+
+fn integer_length(head: u8) -> Result<usize, String> {
+    match head {
+        b'a'..=b'z' => Ok((head - b'a') as usize + 2),
+        b'A'..=b'Z' => Ok((b'Z' - head) as usize + 2),
+        _ => Err(format!("Invalid order key head: {}", head as char)),
+    }
+}
+
+fn integer_part(key: &str) -> Result<&str, String> {
+    let head = *key.as_bytes().first().ok_or("Empty order key")?;
+    let len = integer_length(head)?;
+    if len > key.len() {
+        return Err(format!("Invalid order key: {}", key));
+    }
+    Ok(&key[..len])
+}
+
+fn validate_key(key: &str) -> Result<(), String> {
+    if key == SMALLEST_INTEGER {
+        return Err(format!("Invalid order key: {}", key));
+    }
+    let int = integer_part(key)?;
+    if key.len() > int.len() && key.as_bytes().last() == Some(&ZERO) {
+        return Err(format!("Invalid order key: {}", key));
+    }
+    Ok(())
+}
+
+/// Returns a fractional part strictly between `a` and `b` (`None` meaning +infinity).
+fn midpoint(a: &[u8], b: Option<&[u8]>) -> Result<Vec<u8>, String> {
+    if let Some(b) = b {
+        let mut n = 0;
+        while n < b.len() && a.get(n).copied().unwrap_or(ZERO) == b[n] {
+            n += 1;
+        }
+        if n > 0 {
+            let mut out = b[..n].to_vec();
+            out.extend(midpoint(a.get(n..).unwrap_or(&[]), Some(&b[n..]))?);
+            return Ok(out);
+        }
+    }
+
+    let digit_a = match a.first() {
+        Some(&c) => digit_index(c)?,
+        None => 0,
+    };
+    let digit_b = match b.and_then(|b| b.first()) {
+        Some(&c) => digit_index(c)?,
+        None => DIGITS.len(),
+    };
+
+    if digit_b - digit_a > 1 {
+        let mid = (digit_a + digit_b).div_ceil(2);
+        Ok(vec![DIGITS[mid]])
+    } else if let Some(b) = b.filter(|b| b.len() > 1) {
+        Ok(vec![b[0]])
+    } else {
+        let mut out = vec![DIGITS[digit_a]];
+        out.extend(midpoint(a.get(1..).unwrap_or(&[]), None)?);
+        Ok(out)
+    }
+}
+
+fn increment_integer(x: &str) -> Result<Option<String>, String> {
+    let bytes = x.as_bytes();
+    let head = bytes[0];
+    let mut digits = bytes[1..].to_vec();
+    let mut carry = true;
+    for d in digits.iter_mut().rev() {
+        let next = digit_index(*d)? + 1;
+        if next == DIGITS.len() {
+            *d = ZERO;
+        } else {
+            *d = DIGITS[next];
+            carry = false;
+            break;
+        }
+    }
+    if !carry {
+        return Ok(Some(to_string(head, digits)));
+    }
+    match head {
+        b'Z' => Ok(Some("a0".to_string())),
+        b'z' => Ok(None),
+        _ => {
+            let h = head + 1;
+            if h > b'a' {
+                digits.push(ZERO);
+            } else {
+                digits.pop();
+            }
+            Ok(Some(to_string(h, digits)))
+        }
+    }
+}
+
+fn decrement_integer(x: &str) -> Result<Option<String>, String> {
+    let bytes = x.as_bytes();
+    let head = bytes[0];
+    let last = DIGITS[DIGITS.len() - 1];
+    let mut digits = bytes[1..].to_vec();
+    let mut borrow = true;
+    for d in digits.iter_mut().rev() {
+        let idx = digit_index(*d)?;
+        if idx == 0 {
+            *d = last;
+        } else {
+            *d = DIGITS[idx - 1];
+            borrow = false;
+            break;
+        }
+    }
+    if !borrow {
+        return Ok(Some(to_string(head, digits)));
+    }
+    match head {
+        b'a' => Ok(Some(to_string(b'Z', vec![last]))),
+        b'A' => Ok(None),
+        _ => {
+            let h = head - 1;
+            if h < b'Z' {
+                digits.push(last);
+            } else {
+                digits.pop();
+            }
+            Ok(Some(to_string(h, digits)))
+        }
+    }
+}
+
+fn to_string(head: u8, digits: Vec<u8>) -> String {
+    let mut out = vec![head];
+    out.extend(digits);
+    String::from_utf8(out).unwrap_or_default()
+}
+
+/// Generates an order key strictly between `a` and `b`. `None` means unbounded,
+/// so `key_between(None, Some(first))` places an item at the top of the list.
+pub fn key_between(a: Option<&str>, b: Option<&str>) -> Result<String, String> {
+    if let Some(a) = a {
+        validate_key(a)?;
+    }
+    if let Some(b) = b {
+        validate_key(b)?;
+    }
+
+    match (a, b) {
+        (Some(a), Some(b)) if a >= b => Err(format!("Order key {} is not less than {}", a, b)),
+        (None, None) => Ok("a0".to_string()),
+        (None, Some(b)) => {
+            let ib = integer_part(b)?;
+            let fb = &b[ib.len()..];
+            if ib == SMALLEST_INTEGER {
+                let mid = midpoint(&[], Some(fb.as_bytes()))?;
+                return Ok(format!("{}{}", ib, String::from_utf8_lossy(&mid)));
+            }
+            if ib < b {
+                return Ok(ib.to_string());
+            }
+            decrement_integer(ib)?.ok_or_else(|| "Cannot decrement any more".to_string())
+        }
+        (Some(a), None) => {
+            let ia = integer_part(a)?;
+            let fa = &a[ia.len()..];
+            match increment_integer(ia)? {
+                Some(i) => Ok(i),
+                None => {
+                    let mid = midpoint(fa.as_bytes(), None)?;
+                    Ok(format!("{}{}", ia, String::from_utf8_lossy(&mid)))
+                }
+            }
+        }
+        (Some(a), Some(b)) => {
+            let ia = integer_part(a)?;
+            let fa = &a[ia.len()..];
+            let ib = integer_part(b)?;
+            let fb = &b[ib.len()..];
+            if ia == ib {
+                let mid = midpoint(fa.as_bytes(), Some(fb.as_bytes()))?;
+                return Ok(format!("{}{}", ia, String::from_utf8_lossy(&mid)));
+            }
+            let i =
+                increment_integer(ia)?.ok_or_else(|| "Cannot increment any more".to_string())?;
+            if i.as_str() < b {
+                return Ok(i);
+            }
+            let mid = midpoint(fa.as_bytes(), None)?;
+            Ok(format!("{}{}", ia, String::from_utf8_lossy(&mid)))
+        }
+    }
+}
@@ -0,0 +1,101 @@
+//! fzf-style fuzzy matching over item text, using nucleo's matcher. The index
+//! holds the searchable text of every item in memory and is rebuilt lazily
+//! whenever the database has changed since it was built.
+
+use nucleo_matcher::pattern::{CaseMatching, Normalization, Pattern};
+use nucleo_matcher::{Config, Matcher, Utf32Str};
+use rusqlite::Connection;
+use serde::Serialize;
+
+/// Only the start of long items is indexed, so match offsets past this don't occur
+const MAX_INDEXED_CHARS: usize = 4096;
+
+struct Entry {
+    id: i64,
+    text: String,
+}
+
+/// A matched item. `indices` are char offsets into the item's text (OCR text
+/// for images), sorted and deduplicated, for highlighting.
+#[derive(Debug, Serialize)]
+pub struct FuzzyMatch {
+    pub id: i64,
+    pub score: u32,
+    pub indices: Vec<u32>,
+}
+
+pub struct FuzzyIndex {
+    entries: Vec<Entry>,
+    /// `Connection::total_changes` when the entries were loaded
+    built_at: Option<u64>,
+    matcher: Matcher,
+}
+
+impl FuzzyIndex {
+    pub fn new() -> Self {
+        Self {
+            entries: Vec::new(),
+            built_at: None,
+            matcher: Matcher::new(Config::DEFAULT),
+        }
+    }
+
+    /// Every write goes through the one connection, so its change counter
+    /// tells us when the entries are stale.
+    fn refresh(&mut self, conn: &Connection) -> rusqlite::Result<()> {
+        let changes = conn.total_changes();
+        if self.built_at == Some(changes) {
+            return Ok(());
+        }
+
+        self.entries = conn
+            .prepare(
+                "SELECT id, CASE WHEN content_type = 'image' THEN ocr_text ELSE text_content END AS text
+                 FROM clipboard_items WHERE deleted_at IS NULL AND text IS NOT NULL AND text != ''
+                 ORDER BY sort_order ASC",
+            )?
+            .query_map([], |row| {
+                let text: String = row.get(1)?;
+                Ok(Entry {
+                    id: row.get(0)?,
+                    text: text.chars().take(MAX_INDEXED_CHARS).collect(),
+                })
+            })?
+            .collect::<Result<_, _>>()?;
+        self.built_at = Some(changes);
+        Ok(())
+    }
+
+    /// Best matches first. The query uses fzf syntax: space-separated terms,
+    /// `'exact`, `^prefix`, `suffix$` and `!negation`, smart case.
+    pub fn search(
+        &mut self,
+        conn: &Connection,
+        query: &str,
+        limit: usize,
+    ) -> rusqlite::Result<Vec<FuzzyMatch>> {
+        self.refresh(conn)?;
+
+        let pattern = Pattern::parse(query, CaseMatching::Smart, Normalization::Smart);
+        let mut buf = Vec::new();
+        let mut matches: Vec<FuzzyMatch> = Vec::new();
+        for entry in &self.entries {
+            let mut indices = Vec::new();
+            let haystack = Utf32Str::new(&entry.text, &mut buf);
+            if let Some(score) = pattern.indices(haystack, &mut self.matcher, &mut indices) {
+                indices.sort_unstable();
+                indices.dedup();
+                matches.push(FuzzyMatch {
+                    id: entry.id,
+                    score,
+                    indices,
+                });
+            }
+        }
+
+        // Stable sort keeps the manual order among equal scores
+        matches.sort_by(|a, b| b.score.cmp(&a.score));
+        matches.truncate(limit);
+        Ok(matches)
+    }
+}
@@ -0,0 +1,347 @@
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use image::codecs::jpeg::JpegEncoder;
+use image::codecs::webp::WebPEncoder;
+use image::{imageops, ExtendedColorType, ImageEncoder, Rgb, RgbImage, RgbaImage};
+use serde::Deserialize;
+
+/// Larger outputs are refused rather than risking a huge allocation
+const MAX_DIMENSION: u32 = 16_384;
+const DEFAULT_JPEG_QUALITY: u8 = 85;
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum ImageOp {
+    Crop {
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+    },
+    /// A missing dimension is derived from the other, keeping the aspect ratio
+    Resize {
+        width: Option<u32>,
+        height: Option<u32>,
+    },
+    Scale {
+        factor: f32,
+    },
+    /// Clockwise, in multiples of 90
+    Rotate {
+        degrees: i32,
+    },
+    FlipHorizontal,
+    FlipVertical,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EncodeFormat {
+    Png,
+    /// Alpha is flattened onto white
+    Jpeg,
+    /// Lossless
+    Webp,
+}
+
+impl EncodeFormat {
+    pub fn mime(self) -> &'static str {
+        match self {
+            Self::Png => "image/png",
+            Self::Jpeg => "image/jpeg",
+            Self::Webp => "image/webp",
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Png => "png",
+            Self::Jpeg => "jpg",
+            Self::Webp => "webp",
+        }
+    }
+}
+
+/// Decodes an image and applies `ops` in order.
+pub fn edit(bytes: &[u8], ops: &[ImageOp]) -> Result<RgbaImage, String> {
+    let (rgba, width, height) = decode_to_rgba(bytes)?;
+    let mut image =
+        RgbaImage::from_raw(width, height, rgba).ok_or("Decoded image has an unexpected size")?;
+    for op in ops {
+        image = apply(image, op)?;
+    }
+    Ok(image)
+}
+
+fn apply(image: RgbaImage, op: &ImageOp) -> Result<RgbaImage, String> {
+    let (width, height) = image.dimensions();
+    match *op {
+        ImageOp::Crop {
+            x,
+            y,
+            width: w,
+            height: h,
+        } => {
+            if w == 0 || h == 0 || x.saturating_add(w) > width || y.saturating_add(h) > height {
+                return Err(format!(
+                    "Crop {}x{}+{}+{} is outside the {}x{} image",
+                    w, h, x, y, width, height
+                ));
+            }
+            Ok(imageops::crop_imm(&image, x, y, w, h).to_image())
+        }
+        ImageOp::Resize {
+            width: w,
+            height: h,
+        } => {
+            let (w, h) = match (w, h) {
+                (Some(w), Some(h)) => (w, h),
+                (Some(w), None) => (w, scale_dimension(height, w as f64 / width as f64)),
+                (None, Some(h)) => (scale_dimension(width, h as f64 / height as f64), h),
+                (None, None) => return Err("Resize needs a width or a height".to_string()),
+            };
+            resize(&image, w, h)
+        }
+        ImageOp::Scale { factor } => {
+            if !factor.is_finite() || factor <= 0.0 {
+                return Err(format!("Invalid scale factor: {}", factor));
+            }
+            let factor = factor as f64;
+            resize(
+                &image,
+                scale_dimension(width, factor),
+                scale_dimension(height, factor),
+            )
+        }
+        ImageOp::Rotate { degrees } => match degrees.rem_euclid(360) {
+            0 => Ok(image),
+            90 => Ok(imageops::rotate90(&image)),
+            180 => Ok(imageops::rotate180(&image)),
+            270 => Ok(imageops::rotate270(&image)),
+            _ => Err(format!(
+                "Rotation must be a multiple of 90, got {}",
+                degrees
+            )),
+        },
+        ImageOp::FlipHorizontal => Ok(imageops::flip_horizontal(&image)),
+        ImageOp::FlipVertical => Ok(imageops::flip_vertical(&image)),
+    }
+}
+
+fn scale_dimension(value: u32, factor: f64) -> u32 {
+    ((value as f64 * factor).round() as u32).max(1)
+}
+
+fn resize(image: &RgbaImage, width: u32, height: u32) -> Result<RgbaImage, String> {
+    if width == 0 || height == 0 || width > MAX_DIMENSION || height > MAX_DIMENSION {
+        return Err(format!(
+            "Size {}x{} must be between 1 and {} pixels per side",
+            width, height, MAX_DIMENSION
+        ));
+    }
+    Ok(imageops::resize(
+        image,
+        width,
+        height,
+        imageops::FilterType::Lanczos3,
+    ))
+}
+
+/// Encodes an image. Only pixel data is written, so any metadata the source
+/// carried (EXIF, text chunks, color profiles) is dropped.
+pub fn encode(
+    image: &RgbaImage,
+    format: EncodeFormat,
+    quality: Option<u8>,
+) -> Result<Vec<u8>, String> {
+    let (width, height) = image.dimensions();
+    match format {
+        EncodeFormat::Png => encode_rgba_to_png(image.as_raw(), width, height),
+        EncodeFormat::Jpeg => {
+            let rgb = flatten_alpha(image);
+            let mut out = Vec::new();
+            let quality = quality.unwrap_or(DEFAULT_JPEG_QUALITY).clamp(1, 100);
+            JpegEncoder::new_with_quality(&mut out, quality)
+                .write_image(rgb.as_raw(), width, height, ExtendedColorType::Rgb8)
+                .map_err(|e| format!("Failed to encode JPEG: {}", e))?;
+            Ok(out)
+        }
+        EncodeFormat::Webp => {
+            let mut out = Vec::new();
+            WebPEncoder::new_lossless(&mut out)
+                .write_image(image.as_raw(), width, height, ExtendedColorType::Rgba8)
+                .map_err(|e| format!("Failed to encode WebP: {}", e))?;
+            Ok(out)
+        }
+    }
+}
+
+/// Composites onto white, for formats without an alpha channel.
+fn flatten_alpha(image: &RgbaImage) -> RgbImage {
+    RgbImage::from_fn(image.width(), image.height(), |x, y| {
+        let [r, g, b, a] = image.get_pixel(x, y).0;
+        let blend = |c: u8| ((c as u32 * a as u32 + 255 * (255 - a as u32)) / 255) as u8;
+        Rgb([blend(r), blend(g), blend(b)])
+    })
+}
+
+pub fn data_uri(bytes: &[u8], format: EncodeFormat) -> String {
+    format!("data:{};base64,{}", format.mime(), BASE64.encode(bytes))
+}
+
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+/// Decodes PNG, JPEG, BMP, GIF (first frame), WebP or TIFF to 8-bit RGBA.
+pub fn decode_to_rgba(bytes: &[u8]) -> Result<(Vec<u8>, u32, u32), String> {
+    if bytes.starts_with(PNG_SIGNATURE) {
+        return decode_png_to_rgba(bytes);
+    }
+    let format =
+        image::guess_format(bytes).map_err(|e| format!("Unrecognized image format: {}", e))?;
+    let image = image::load_from_memory_with_format(bytes, format)
+        .map_err(|e| format!("Failed to decode {:?} image: {}", format, e))?;
+    let rgba = image.to_rgba8();
+    let (width, height) = rgba.dimensions();
+    Ok((rgba.into_raw(), width, height))
+}
+
+/// Normalizes clipboard image bytes to 8-bit RGBA PNG for storage. Such PNGs are
+/// kept as-is so their content hash stays stable; everything else, including
+/// palette, grayscale or 16-bit PNGs, is decoded and re-encoded.
+pub fn normalize_to_png(bytes: Vec<u8>) -> Result<(Vec<u8>, u32, u32), String> {
+    if bytes.starts_with(PNG_SIGNATURE) {
+        let reader = png::Decoder::new(std::io::Cursor::new(&bytes))
+            .read_info()
+            .map_err(|e| format!("Failed to decode PNG: {}", e))?;
+        let info = reader.info();
+        if info.color_type == png::ColorType::Rgba && info.bit_depth == png::BitDepth::Eight {
+            let (width, height) = (info.width, info.height);
+            return Ok((bytes, width, height));
+        }
+    }
+    let (rgba, width, height) = decode_to_rgba(&bytes)?;
+    Ok((encode_rgba_to_png(&rgba, width, height)?, width, height))
+}
+
+/// Encode RGBA bytes to PNG format
+pub fn encode_rgba_to_png(rgba_bytes: &[u8], width: u32, height: u32) -> Result<Vec<u8>, String> {
+    let mut png_bytes = Vec::new();
+    {
+        let mut encoder = png::Encoder::new(&mut png_bytes, width, height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+
+        let mut writer = encoder
+            .write_header()
+            .map_err(|e| format!("Failed to write PNG header: {}", e))?;
+
+        writer
+            .write_image_data(rgba_bytes)
+            .map_err(|e| format!("Failed to write PNG data: {}", e))?;
+    }
+    Ok(png_bytes)
+}
+
+/// Decode PNG bytes to RGBA format with dimensions.
+/// Palette images are expanded and 16-bit channels reduced to 8 bits.
+pub fn decode_png_to_rgba(png_bytes: &[u8]) -> Result<(Vec<u8>, u32, u32), String> {
+    let mut decoder = png::Decoder::new(std::io::Cursor::new(png_bytes));
+    decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+    let mut reader = decoder
+        .read_info()
+        .map_err(|e| format!("Failed to read PNG info: {}", e))?;
+
+    let mut buf = vec![0; reader.output_buffer_size()];
+    let info = reader
+        .next_frame(&mut buf)
+        .map_err(|e| format!("Failed to decode PNG frame: {}", e))?;
+
+    let width = info.width;
+    let height = info.height;
+
+    // Ensure we have RGBA data
+    let rgba_bytes = match info.color_type {
+        png::ColorType::Rgba => buf[..info.buffer_size()].to_vec(),
+        png::ColorType::Rgb => {
+            // Convert RGB to RGBA
+            let rgb_data = &buf[..info.buffer_size()];
+            let mut rgba = Vec::with_capacity((width * height * 4) as usize);
+            for chunk in rgb_data.chunks(3) {
+                rgba.push(chunk[0]);
+                rgba.push(chunk[1]);
+                rgba.push(chunk[2]);
+                rgba.push(255);
+            }
+            rgba
+        }
+        png::ColorType::Grayscale => {
+            let gray_data = &buf[..info.buffer_size()];
+            let mut rgba = Vec::with_capacity((width * height * 4) as usize);
+            for &g in gray_data {
+                rgba.push(g);
+                rgba.push(g);
+                rgba.push(g);
+                rgba.push(255);
+            }
+            rgba
+        }
+        png::ColorType::GrayscaleAlpha => {
+            let ga_data = &buf[..info.buffer_size()];
+            let mut rgba = Vec::with_capacity((width * height * 4) as usize);
+            for chunk in ga_data.chunks(2) {
+                rgba.push(chunk[0]);
+                rgba.push(chunk[0]);
+                rgba.push(chunk[0]);
+                rgba.push(chunk[1]);
+            }
+            rgba
+        }
+        png::ColorType::Indexed => {
+            return Err("Indexed PNG was not expanded".to_string());
+        }
+    };
+
+    Ok((rgba_bytes, width, height))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gray16_png() -> Vec<u8> {
+        let mut bytes = Vec::new();
+        let mut encoder = png::Encoder::new(&mut bytes, 2, 1);
+        encoder.set_color(png::ColorType::Grayscale);
+        encoder.set_depth(png::BitDepth::Sixteen);
+        let mut writer = encoder.write_header().unwrap();
+        writer.write_image_data(&[0xff, 0xff, 0x00, 0x00]).unwrap();
+        writer.finish().unwrap();
+        bytes
+    }
+
+    #[test]
+    fn rgba8_pngs_are_stored_unchanged() {
+        let png = encode_rgba_to_png(&[1, 2, 3, 4, 5, 6, 7, 8], 2, 1).unwrap();
+        assert_eq!(normalize_to_png(png.clone()).unwrap(), (png, 2, 1));
+    }
+
+    #[test]
+    fn other_pngs_are_reencoded_as_rgba8() {
+        let (png, width, height) = normalize_to_png(gray16_png()).unwrap();
+        assert_eq!((width, height), (2, 1));
+        let reader = png::Decoder::new(std::io::Cursor::new(&png))
+            .read_info()
+            .unwrap();
+        assert_eq!(reader.info().color_type, png::ColorType::Rgba);
+        assert_eq!(reader.info().bit_depth, png::BitDepth::Eight);
+        assert_eq!(
+            decode_png_to_rgba(&png).unwrap().0,
+            vec![255, 255, 255, 255, 0, 0, 0, 255]
+        );
+    }
+
+    #[test]
+    fn rejects_truncated_pngs() {
+        assert!(normalize_to_png(PNG_SIGNATURE.to_vec()).is_err());
+        assert!(normalize_to_png(b"not an image".to_vec()).is_err());
+    }
+}
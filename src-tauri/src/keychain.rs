@@ -0,0 +1,103 @@
+//! Credentials kept in the OS keychain (macOS Keychain, Windows Credential
+//! Manager, Secret Service on Linux) instead of the settings table, where
+//! anyone who can read the database file would see them. `Database` routes
+//! the settings keys in `KEYS` here.
+
+use crate::lan::PRIVATE_KEY_SETTING;
+use crate::sync::{PASSPHRASE_SETTING, PASSWORD_SETTING};
+
+/// Settings keys whose values live in the keychain
+pub const KEYS: &[&str] = &[PASSPHRASE_SETTING, PASSWORD_SETTING, PRIVATE_KEY_SETTING];
+
+pub fn is_secret_setting(key: &str) -> bool {
+    KEYS.contains(&key)
+}
+
+/// Entries for one database file, so separate profiles keep separate credentials.
+pub struct Keychain {
+    scope: String,
+}
+
+impl Keychain {
+    pub fn new(db_path: &str) -> Self {
+        Self {
+            scope: db_path.to_string(),
+        }
+    }
+
+    fn account(&self, key: &str) -> String {
+        format!("{}:{}", self.scope, key)
+    }
+
+    pub fn get(&self, key: &str) -> Result<Option<String>, String> {
+        backend::get(&self.account(key))
+    }
+
+    /// Stores `value`, or removes the entry when it's empty.
+    pub fn set(&self, key: &str, value: &str) -> Result<(), String> {
+        if value.is_empty() {
+            backend::delete(&self.account(key))
+        } else {
+            backend::set(&self.account(key), value)
+        }
+    }
+}
+
+#[cfg(not(test))]
+mod backend {
+    use keyring::{Entry, Error};
+
+    /// Service name the entries are filed under
+    const SERVICE: &str = "mexdeclip";
+
+    fn entry(account: &str) -> Result<Entry, String> {
+        Entry::new(SERVICE, account).map_err(|e| format!("Keychain unavailable: {}", e))
+    }
+
+    pub fn get(account: &str) -> Result<Option<String>, String> {
+        match entry(account)?.get_password() {
+            Ok(value) => Ok(Some(value)),
+            Err(Error::NoEntry) => Ok(None),
+            Err(e) => Err(format!("Keychain unavailable: {}", e)),
+        }
+    }
+
+    pub fn set(account: &str, value: &str) -> Result<(), String> {
+        entry(account)?
+            .set_password(value)
+            .map_err(|e| format!("Keychain unavailable: {}", e))
+    }
+
+    pub fn delete(account: &str) -> Result<(), String> {
+        match entry(account)?.delete_credential() {
+            Ok(()) | Err(Error::NoEntry) => Ok(()),
+            Err(e) => Err(format!("Keychain unavailable: {}", e)),
+        }
+    }
+}
+
+/// Tests run without a keychain daemon, so entries live in memory.
+#[cfg(test)]
+mod backend {
+    use std::collections::HashMap;
+    use std::sync::{LazyLock, Mutex};
+
+    static ENTRIES: LazyLock<Mutex<HashMap<String, String>>> = LazyLock::new(Default::default);
+
+    pub fn get(account: &str) -> Result<Option<String>, String> {
+        Ok(ENTRIES.lock().unwrap().get(account).cloned())
+    }
+
+    pub fn set(account: &str, value: &str) -> Result<(), String> {
+        ENTRIES
+            .lock()
+            .unwrap()
+            .insert(account.to_string(), value.to_string());
+        Ok(())
+    }
+
+    pub fn delete(account: &str) -> Result<(), String> {
+        ENTRIES.lock().unwrap().remove(account);
+        Ok(())
+    }
+}
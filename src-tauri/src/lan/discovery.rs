@@ -0,0 +1,101 @@
+//! mDNS advertisement and browsing of other instances on the network. The
+//! TXT record carries the device's public key, which is what pairing trusts;
+//! the advertised name and addresses are only hints.
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+
+use mdns_sd::{ServiceDaemon, ServiceEvent, ServiceInfo};
+
+const SERVICE_TYPE: &str = "_mexdeclip._tcp.local.";
+
+#[derive(Debug, Clone)]
+pub struct Discovered {
+    pub name: String,
+    pub addresses: Vec<SocketAddr>,
+    fullname: String,
+}
+
+/// Running advertisement and browse. Both stop when dropped.
+pub struct Discovery {
+    daemon: ServiceDaemon,
+    /// Discovered devices by public key
+    devices: Arc<Mutex<HashMap<String, Discovered>>>,
+}
+
+impl Discovery {
+    pub fn start(public_key: &str, name: &str, port: u16) -> Result<Self, String> {
+        let daemon = ServiceDaemon::new().map_err(|e| e.to_string())?;
+
+        // Instance and host names only need to be unique on the network
+        let instance = &public_key[..16.min(public_key.len())];
+        let properties = [("pk", public_key), ("name", name)];
+        let info = ServiceInfo::new(
+            SERVICE_TYPE,
+            instance,
+            &format!("{}.local.", instance),
+            "",
+            port,
+            &properties[..],
+        )
+        .map_err(|e| e.to_string())?
+        .enable_addr_auto();
+        daemon.register(info).map_err(|e| e.to_string())?;
+
+        let events = daemon.browse(SERVICE_TYPE).map_err(|e| e.to_string())?;
+        let devices = Arc::new(Mutex::new(HashMap::new()));
+        let own_key = public_key.to_string();
+        let found = Arc::clone(&devices);
+        // Ends when the daemon shuts down and closes the channel
+        std::thread::spawn(move || {
+            while let Ok(event) = events.recv() {
+                let Ok(mut devices) = found.lock() else {
+                    break;
+                };
+                match event {
+                    ServiceEvent::ServiceResolved(info) => {
+                        let Some(key) = info.get_property_val_str("pk") else {
+                            continue;
+                        };
+                        if key == own_key {
+                            continue;
+                        }
+                        let addresses = info
+                            .get_addresses()
+                            .iter()
+                            .map(|ip| SocketAddr::new(*ip, info.get_port()))
+                            .collect();
+                        devices.insert(
+                            key.to_string(),
+                            Discovered {
+                                name: info.get_property_val_str("name").unwrap_or(key).to_string(),
+                                addresses,
+                                fullname: info.get_fullname().to_string(),
+                            },
+                        );
+                    }
+                    ServiceEvent::ServiceRemoved(_, fullname) => {
+                        devices.retain(|_, device| device.fullname != fullname);
+                    }
+                    _ => {}
+                }
+            }
+        });
+
+        Ok(Self { daemon, devices })
+    }
+
+    pub fn devices(&self) -> HashMap<String, Discovered> {
+        self.devices
+            .lock()
+            .map(|devices| devices.clone())
+            .unwrap_or_default()
+    }
+}
+
+impl Drop for Discovery {
+    fn drop(&mut self) {
+        let _ = self.daemon.shutdown();
+    }
+}
@@ -0,0 +1,826 @@
+//! Clipboard sharing with trusted devices on the local network, KDE Connect
+//! style. Instances advertise themselves over mDNS and listen for Noise
+//! connections (see `noise`). Devices pair once with a short-lived code,
+//! confirmed on the device that offered it by comparing the fingerprint both
+//! show; after that, every capture is pushed to the paired devices that can be
+//! reached, which insert it with `source_device` set and can optionally put
+//! it on their clipboard.
+
+mod discovery;
+mod noise;
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::OsRng;
+use serde::{Deserialize, Serialize};
+use tauri::async_runtime::JoinHandle;
+use tauri::{AppHandle, Emitter, Manager};
+use tokio::net::TcpListener;
+use tokio::sync::oneshot;
+
+use crate::clipboard::ClipboardManager;
+use crate::commands::run_blocking;
+use crate::db::{
+    from_hex, now_millis, to_hex, ClipboardItemRow, Database, InsertClipboardItemParams,
+    LanPeerRow,
+};
+use discovery::Discovery;
+use noise::{Channel, Purpose};
+
+/// Settings key: "true" to start sharing on launch, kept by `start` and `stop`
+pub const ENABLED_SETTING: &str = "lan_sharing_enabled";
+/// Settings key: TCP port to listen on, default 47471
+pub const PORT_SETTING: &str = "lan_port";
+/// Settings key: name shown to other devices, defaults to the hostname
+pub const NAME_SETTING: &str = "lan_device_name";
+/// Settings key: "true" to also put received items on the clipboard
+pub const WRITE_CLIPBOARD_SETTING: &str = "lan_write_clipboard";
+/// Settings key: this device's Noise private key, hex, generated on first start
+/// and kept in the OS keychain
+pub const PRIVATE_KEY_SETTING: &str = "lan_private_key";
+
+/// Emitted with the item id when an item arrives from a peer
+const ITEM_RECEIVED_EVENT: &str = "lan-item-received";
+/// Emitted with a `PairingFingerprint` once a pairing handshake succeeds
+const PAIRING_FINGERPRINT_EVENT: &str = "lan-pairing-fingerprint";
+
+const DEFAULT_PORT: u16 = 47471;
+const PAIRING_CODE_TTL: Duration = Duration::from_secs(120);
+/// Crockford base32, 20 characters of it make a 100 bit pairing code
+const PAIRING_CODE_ALPHABET: &[u8; 32] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+const PAIRING_CODE_LEN: usize = 20;
+/// How long the device that offered the code waits for its user to confirm
+const CONFIRM_TIMEOUT: Duration = Duration::from_secs(60);
+/// How long a received item is kept from being shared back when its
+/// capture comes round through the clipboard
+const ECHO_TTL: Duration = Duration::from_secs(30);
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Message {
+    /// Sent first by whoever connects. `port` is where its listener is.
+    Hello {
+        name: String,
+        port: u16,
+    },
+    Item(SharedItem),
+}
+
+#[derive(Serialize, Deserialize)]
+struct SharedItem {
+    content_type: String,
+    text_content: Option<String>,
+    image_data: Option<String>,
+    image_width: Option<i64>,
+    image_height: Option<i64>,
+    char_count: Option<i64>,
+    line_count: Option<i64>,
+    source_app: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct LanPeer {
+    pub public_key: String,
+    pub name: String,
+    pub address: Option<String>,
+    pub paired: bool,
+    /// Currently advertised on the network
+    pub online: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PairingCode {
+    pub code: String,
+    /// Epoch milliseconds
+    pub expires_at: i64,
+}
+
+/// Shown on both devices while pairing. The pairing is only saved once the
+/// user of the device that offered the code confirms the fingerprints match.
+#[derive(Debug, Clone, Serialize)]
+pub struct PairingFingerprint {
+    /// The other device's name, or its address on the device that connected
+    pub device: String,
+    pub fingerprint: String,
+    /// Whether this device is waiting for `confirm_pairing`
+    pub needs_confirmation: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct LanStatus {
+    pub running: bool,
+    pub name: Option<String>,
+    pub public_key: Option<String>,
+    pub port: Option<u16>,
+    /// Why mDNS isn't running, if it isn't. Paired devices with a known
+    /// address can still be reached.
+    pub discovery_error: Option<String>,
+}
+
+struct Identity {
+    private_key: Vec<u8>,
+    public_key: String,
+    name: String,
+    port: u16,
+}
+
+struct Running {
+    identity: Arc<Identity>,
+    discovery: Option<Discovery>,
+    discovery_error: Option<String>,
+    listener: JoinHandle<()>,
+}
+
+/// Managed state: the running listener, if any, the pairing code on offer,
+/// a pairing waiting for confirmation and content hashes recently received
+/// from peers.
+#[derive(Default)]
+pub struct LanState {
+    running: Mutex<Option<Running>>,
+    pairing_code: Mutex<Option<(String, Instant)>>,
+    confirmation: Mutex<Option<oneshot::Sender<bool>>>,
+    received: Mutex<HashMap<String, Instant>>,
+}
+
+impl LanState {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn identity(&self) -> Option<Arc<Identity>> {
+        let running = self.running.lock().ok()?;
+        running.as_ref().map(|r| Arc::clone(&r.identity))
+    }
+
+    fn status(&self) -> LanStatus {
+        let running = self.running.lock().ok();
+        let running = running.as_ref().and_then(|r| r.as_ref());
+        LanStatus {
+            running: running.is_some(),
+            name: running.map(|r| r.identity.name.clone()),
+            public_key: running.map(|r| r.identity.public_key.clone()),
+            port: running.map(|r| r.identity.port),
+            discovery_error: running.and_then(|r| r.discovery_error.clone()),
+        }
+    }
+
+    /// The pairing code on offer, if it hasn't expired. It stays on offer
+    /// until a device pairs with it, so a failed attempt doesn't use it up.
+    fn pairing_code(&self) -> Option<String> {
+        let code = self.pairing_code.lock().ok()?;
+        code.as_ref()
+            .filter(|(_, created)| created.elapsed() < PAIRING_CODE_TTL)
+            .map(|(code, _)| code.clone())
+    }
+
+    /// Withdraws `code` once a device has paired with it.
+    fn finish_pairing(&self, code: &str) {
+        if let Ok(mut current) = self.pairing_code.lock() {
+            if current.as_ref().is_some_and(|(current, _)| current == code) {
+                *current = None;
+            }
+        }
+    }
+
+    fn mark_received(&self, content_hash: &str) {
+        if let Ok(mut received) = self.received.lock() {
+            received.retain(|_, at| at.elapsed() < ECHO_TTL);
+            received.insert(content_hash.to_string(), Instant::now());
+        }
+    }
+
+    fn was_received(&self, content_hash: &str) -> bool {
+        self.received
+            .lock()
+            .map(|received| {
+                received
+                    .get(content_hash)
+                    .is_some_and(|at| at.elapsed() < ECHO_TTL)
+            })
+            .unwrap_or(false)
+    }
+}
+
+/// Starts listening and advertising. Does nothing if already running.
+pub async fn start(app: &AppHandle) -> Result<LanStatus, String> {
+    let state = app.state::<LanState>();
+    if state.identity().is_some() {
+        return Ok(state.status());
+    }
+    let database = app.state::<Database>();
+
+    let private_key = match database.get_setting(PRIVATE_KEY_SETTING)? {
+        Some(key) => from_hex(&key).ok_or("Invalid LAN private key setting")?,
+        None => {
+            let keypair = noise::generate_keypair()?;
+            database.set_setting(PRIVATE_KEY_SETTING, &to_hex(&keypair.private))?;
+            keypair.private
+        }
+    };
+    let public_key = to_hex(&public_key_of(&private_key)?);
+    let name = database
+        .get_setting(NAME_SETTING)?
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(hostname);
+    let port = database
+        .get_setting(PORT_SETTING)?
+        .and_then(|v| v.trim().parse().ok())
+        .unwrap_or(DEFAULT_PORT);
+
+    let listener = TcpListener::bind(("0.0.0.0", port))
+        .await
+        .map_err(|e| format!("Failed to listen on port {}: {}", port, e))?;
+    let port = listener.local_addr().map_err(|e| e.to_string())?.port();
+
+    let (discovery, discovery_error) = match Discovery::start(&public_key, &name, port) {
+        Ok(discovery) => (Some(discovery), None),
+        Err(e) => (None, Some(e)),
+    };
+    let identity = Arc::new(Identity {
+        private_key,
+        public_key,
+        name,
+        port,
+    });
+
+    let handle = app.clone();
+    let accept_identity = Arc::clone(&identity);
+    let listener = tauri::async_runtime::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            let app = handle.clone();
+            let identity = Arc::clone(&accept_identity);
+            tauri::async_runtime::spawn(async move {
+                let _ = handle_incoming(&app, stream, &identity).await;
+            });
+        }
+    });
+
+    let mut running = state.running.lock().map_err(|e| e.to_string())?;
+    if running.is_some() {
+        // Lost a race with another start
+        listener.abort();
+    } else {
+        *running = Some(Running {
+            identity,
+            discovery,
+            discovery_error,
+            listener,
+        });
+    }
+    drop(running);
+
+    database.set_setting(ENABLED_SETTING, "true")?;
+    Ok(state.status())
+}
+
+pub fn stop(app: &AppHandle) -> Result<(), String> {
+    let state = app.state::<LanState>();
+    let running = state.running.lock().map_err(|e| e.to_string())?.take();
+    if let Some(running) = running {
+        running.listener.abort();
+    }
+    app.state::<Database>()
+        .set_setting(ENABLED_SETTING, "false")
+}
+
+pub fn status(app: &AppHandle) -> LanStatus {
+    app.state::<LanState>().status()
+}
+
+/// Offers a code that a device can pair with for the next two minutes. It is
+/// long enough that a recorded handshake can't be brute-forced offline.
+pub fn start_pairing(app: &AppHandle) -> Result<PairingCode, String> {
+    let state = app.state::<LanState>();
+    if state.identity().is_none() {
+        return Err("LAN sharing is not running".to_string());
+    }
+    let mut bytes = [0u8; PAIRING_CODE_LEN];
+    OsRng.fill_bytes(&mut bytes);
+    let code: String = bytes
+        .iter()
+        .map(|b| PAIRING_CODE_ALPHABET[*b as usize % PAIRING_CODE_ALPHABET.len()] as char)
+        .collect();
+    *state.pairing_code.lock().map_err(|e| e.to_string())? = Some((code.clone(), Instant::now()));
+
+    let groups: Vec<&str> = (0..code.len())
+        .step_by(4)
+        .map(|i| &code[i..i + 4])
+        .collect();
+    Ok(PairingCode {
+        code: groups.join("-"),
+        expires_at: now_millis() + PAIRING_CODE_TTL.as_millis() as i64,
+    })
+}
+
+/// Answers the pairing waiting for confirmation on this device.
+pub fn confirm_pairing(app: &AppHandle, accept: bool) -> Result<(), String> {
+    let sender = app
+        .state::<LanState>()
+        .confirmation
+        .lock()
+        .map_err(|e| e.to_string())?
+        .take()
+        .ok_or("No pairing is waiting for confirmation")?;
+    sender
+        .send(accept)
+        .map_err(|_| "The pairing has already timed out".to_string())
+}
+
+/// Pairs with the device at `address` (`host:port`) showing `code`. Returns
+/// once the other device's user has confirmed the fingerprint.
+pub async fn pair(app: &AppHandle, address: &str, code: &str) -> Result<LanPeer, String> {
+    let identity = running_identity(app)?;
+    let code = normalize_code(code);
+    let mut channel =
+        Channel::connect(address, Purpose::Pair, &identity.private_key, Some(&code)).await?;
+    send_hello(&mut channel, &identity).await?;
+    let _ = app.emit(
+        PAIRING_FINGERPRINT_EVENT,
+        PairingFingerprint {
+            device: address.to_string(),
+            fingerprint: channel.fingerprint().to_string(),
+            needs_confirmation: false,
+        },
+    );
+    // A wrong code or a declined fingerprint only shows on the other side,
+    // which hangs up
+    let reply = channel
+        .recv_within(CONFIRM_TIMEOUT + Duration::from_secs(5))
+        .await;
+    let Ok(Some(Message::Hello { name, .. })) = reply.and_then(decode_message) else {
+        return Err("Pairing was refused, check the code and fingerprint".to_string());
+    };
+
+    let peer = LanPeerRow {
+        public_key: to_hex(&channel.remote_key()),
+        name,
+        address: Some(address.to_string()),
+        paired_at: now_millis().to_string(),
+    };
+    app.state::<Database>().save_lan_peer(&peer)?;
+    Ok(LanPeer {
+        public_key: peer.public_key,
+        name: peer.name,
+        address: peer.address,
+        paired: true,
+        online: true,
+    })
+}
+
+pub fn unpair(app: &AppHandle, public_key: &str) -> Result<(), String> {
+    app.state::<Database>().delete_lan_peer(public_key)
+}
+
+/// Paired devices, then unpaired ones seen on the network.
+pub fn peers(app: &AppHandle) -> Result<Vec<LanPeer>, String> {
+    let devices = discovered(app);
+    let mut peers: Vec<LanPeer> = app
+        .state::<Database>()
+        .get_lan_peers()?
+        .into_iter()
+        .map(|row| LanPeer {
+            online: devices.contains_key(&row.public_key),
+            public_key: row.public_key,
+            name: row.name,
+            address: row.address,
+            paired: true,
+        })
+        .collect();
+
+    let mut unpaired: Vec<LanPeer> = devices
+        .into_iter()
+        .filter(|(key, _)| !peers.iter().any(|p| &p.public_key == key))
+        .map(|(public_key, device)| LanPeer {
+            public_key,
+            name: device.name,
+            address: device.addresses.first().map(SocketAddr::to_string),
+            paired: false,
+            online: true,
+        })
+        .collect();
+    unpaired.sort_by(|a, b| a.name.cmp(&b.name));
+    peers.extend(unpaired);
+    Ok(peers)
+}
+
+/// Sends an item to every paired device that can be reached. Returns how
+/// many received it.
+pub async fn share(app: &AppHandle, item: &ClipboardItemRow) -> Result<usize, String> {
+    let identity = running_identity(app)?;
+    let devices = discovered(app);
+    let message = serde_json::to_vec(&Message::Item(SharedItem {
+        content_type: item.content_type.clone(),
+        text_content: item.text_content.clone(),
+        image_data: item.image_data.clone(),
+        image_width: item.image_width,
+        image_height: item.image_height,
+        char_count: item.char_count,
+        line_count: item.line_count,
+        source_app: item.source_app.clone(),
+    }))
+    .map_err(|e| e.to_string())?;
+
+    let mut delivered = 0;
+    for peer in app.state::<Database>().get_lan_peers()? {
+        // Advertised addresses first, they follow DHCP changes
+        let mut addresses: Vec<String> = devices
+            .get(&peer.public_key)
+            .map(|d| d.addresses.iter().map(SocketAddr::to_string).collect())
+            .unwrap_or_default();
+        addresses.extend(peer.address.clone());
+
+        // Devices that are off or unreachable are skipped
+        for address in addresses {
+            if send_item(&address, &peer, &identity, &message)
+                .await
+                .is_ok()
+            {
+                delivered += 1;
+                break;
+            }
+        }
+    }
+    Ok(delivered)
+}
+
+/// Shares a fresh capture in the background when sharing is on. Items that
+/// just arrived from a peer aren't sent back out.
+pub fn share_capture(app: &AppHandle, item: &ClipboardItemRow) {
+    let state = app.state::<LanState>();
+    if state.identity().is_none() {
+        return;
+    }
+    if item
+        .content_hash
+        .as_deref()
+        .is_some_and(|hash| state.was_received(hash))
+    {
+        return;
+    }
+
+    let app = app.clone();
+    let item = item.clone();
+    tauri::async_runtime::spawn(async move {
+        let _ = share(&app, &item).await;
+    });
+}
+
+async fn send_item(
+    address: &str,
+    peer: &LanPeerRow,
+    identity: &Identity,
+    message: &[u8],
+) -> Result<(), String> {
+    let mut channel =
+        Channel::connect(address, Purpose::Share, &identity.private_key, None).await?;
+    // Whoever answers must hold the key we paired with
+    if to_hex(&channel.remote_key()) != peer.public_key {
+        return Err("Device key doesn't match the paired device".to_string());
+    }
+    send_hello(&mut channel, identity).await?;
+    channel.send(message).await
+}
+
+async fn handle_incoming(
+    app: &AppHandle,
+    mut stream: tokio::net::TcpStream,
+    identity: &Identity,
+) -> Result<(), String> {
+    match Channel::read_purpose(&mut stream).await? {
+        Purpose::Pair => accept_pairing(app, stream, identity).await,
+        Purpose::Share => accept_share(app, stream, identity).await,
+    }
+}
+
+async fn accept_pairing(
+    app: &AppHandle,
+    stream: tokio::net::TcpStream,
+    identity: &Identity,
+) -> Result<(), String> {
+    let state = app.state::<LanState>();
+    let code = state.pairing_code().ok_or("No pairing code on offer")?;
+    let mut channel =
+        Channel::accept(stream, Purpose::Pair, &identity.private_key, Some(&code)).await?;
+
+    let Some(Message::Hello { name, port }) = recv_message(&mut channel).await? else {
+        return Err("Device didn't introduce itself".to_string());
+    };
+    if !confirm(app, &name, channel.fingerprint()).await? {
+        return Err(format!("Pairing with {} was declined", name));
+    }
+    state.finish_pairing(&code);
+
+    send_hello(&mut channel, identity).await?;
+    app.state::<Database>().save_lan_peer(&LanPeerRow {
+        public_key: to_hex(&channel.remote_key()),
+        name,
+        address: channel
+            .peer_ip()
+            .map(|ip| SocketAddr::new(ip, port).to_string()),
+        paired_at: now_millis().to_string(),
+    })
+}
+
+/// Asks this device's user to compare the fingerprint, see `confirm_pairing`.
+/// One pairing waits at a time; false if declined or not answered in time.
+async fn confirm(app: &AppHandle, device: &str, fingerprint: &str) -> Result<bool, String> {
+    let state = app.state::<LanState>();
+    let (sender, receiver) = oneshot::channel();
+    {
+        let mut pending = state.confirmation.lock().map_err(|e| e.to_string())?;
+        if pending.as_ref().is_some_and(|sender| !sender.is_closed()) {
+            return Err("Another pairing is waiting for confirmation".to_string());
+        }
+        *pending = Some(sender);
+    }
+    let _ = app.emit(
+        PAIRING_FINGERPRINT_EVENT,
+        PairingFingerprint {
+            device: device.to_string(),
+            fingerprint: fingerprint.to_string(),
+            needs_confirmation: true,
+        },
+    );
+
+    let accepted = tokio::time::timeout(CONFIRM_TIMEOUT, receiver).await;
+    if let Ok(mut pending) = state.confirmation.lock() {
+        // Still ours if it wasn't answered
+        if pending.as_ref().is_some_and(oneshot::Sender::is_closed) {
+            *pending = None;
+        }
+    }
+    Ok(matches!(accepted, Ok(Ok(true))))
+}
+
+async fn accept_share(
+    app: &AppHandle,
+    stream: tokio::net::TcpStream,
+    identity: &Identity,
+) -> Result<(), String> {
+    let database = app.state::<Database>();
+    let (mut channel, peer) = accept_paired(&database, stream, identity).await?;
+
+    let Some(Message::Hello { port, .. }) = recv_message(&mut channel).await? else {
+        return Err("Device didn't introduce itself".to_string());
+    };
+    if let Some(ip) = channel.peer_ip() {
+        let address = SocketAddr::new(ip, port).to_string();
+        database.set_lan_peer_address(&peer.public_key, &address)?;
+    }
+    while let Some(message) = recv_message(&mut channel).await? {
+        if let Message::Item(item) = message {
+            receive_item(app, &peer.name, item).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Runs the share handshake and looks up the paired device on the other end.
+/// Nothing from an unknown key is read.
+async fn accept_paired(
+    database: &Database,
+    stream: tokio::net::TcpStream,
+    identity: &Identity,
+) -> Result<(Channel, LanPeerRow), String> {
+    let channel = Channel::accept(stream, Purpose::Share, &identity.private_key, None).await?;
+    match database.get_lan_peer(&to_hex(&channel.remote_key()))? {
+        Some(peer) => Ok((channel, peer)),
+        None => Err("Rejected unpaired device".to_string()),
+    }
+}
+
+async fn receive_item(app: &AppHandle, peer_name: &str, item: SharedItem) -> Result<(), String> {
+    let database = app.state::<Database>();
+    let now = now_millis().to_string();
+    let params = InsertClipboardItemParams {
+        content_type: item.content_type,
+        text_content: item.text_content,
+        image_data: item.image_data,
+        image_width: item.image_width,
+        image_height: item.image_height,
+        char_count: item.char_count,
+        line_count: item.line_count,
+        source_app: item.source_app,
+        source_device: Some(peer_name.to_string()),
+        sort_order: database.top_sort_order()?,
+        kv_key: None,
+        selection: None,
+        detected_date: None,
+        detected_color: None,
+        created_at: now.clone(),
+        updated_at: now,
+    };
+    let handle = app.clone();
+    let row = run_blocking(move || handle.state::<Database>().record_capture(params)).await?;
+    if let Some(hash) = &row.content_hash {
+        app.state::<LanState>().mark_received(hash);
+    }
+    let _ = app.emit(ITEM_RECEIVED_EVENT, row.id);
+
+    if database.get_setting(WRITE_CLIPBOARD_SETTING)?.as_deref() == Some("true") {
+        let manager = app.state::<ClipboardManager>();
+        match (&row.text_content, &row.image_data) {
+            (Some(text), _) => manager.write(text.clone()).await?,
+            (None, Some(image)) => manager.write_image(image.clone()).await?,
+            (None, None) => {}
+        }
+    }
+    Ok(())
+}
+
+async fn send_hello(channel: &mut Channel, identity: &Identity) -> Result<(), String> {
+    let hello = Message::Hello {
+        name: identity.name.clone(),
+        port: identity.port,
+    };
+    channel
+        .send(&serde_json::to_vec(&hello).map_err(|e| e.to_string())?)
+        .await
+}
+
+async fn recv_message(channel: &mut Channel) -> Result<Option<Message>, String> {
+    decode_message(channel.recv().await?)
+}
+
+fn decode_message(data: Option<Vec<u8>>) -> Result<Option<Message>, String> {
+    match data {
+        Some(data) => serde_json::from_slice(&data)
+            .map(Some)
+            .map_err(|e| format!("Malformed message: {}", e)),
+        None => Ok(None),
+    }
+}
+
+/// Upper case without separators, reading the look-alikes Crockford base32
+/// leaves out as the digits they resemble.
+fn normalize_code(code: &str) -> String {
+    code.chars()
+        .filter(char::is_ascii_alphanumeric)
+        .map(|c| match c.to_ascii_uppercase() {
+            'O' => '0',
+            'I' | 'L' => '1',
+            c => c,
+        })
+        .collect()
+}
+
+fn running_identity(app: &AppHandle) -> Result<Arc<Identity>, String> {
+    app.state::<LanState>()
+        .identity()
+        .ok_or_else(|| "LAN sharing is not running".to_string())
+}
+
+fn discovered(app: &AppHandle) -> HashMap<String, discovery::Discovered> {
+    let state = app.state::<LanState>();
+    let running = state.running.lock();
+    running
+        .ok()
+        .and_then(|r| {
+            r.as_ref()
+                .and_then(|r| r.discovery.as_ref())
+                .map(Discovery::devices)
+        })
+        .unwrap_or_default()
+}
+
+/// X25519 public key for a private key.
+fn public_key_of(private_key: &[u8]) -> Result<Vec<u8>, String> {
+    let private: [u8; 32] = private_key
+        .try_into()
+        .map_err(|_| "Invalid LAN private key".to_string())?;
+    Ok(x25519_dalek::x25519(private, x25519_dalek::X25519_BASEPOINT_BYTES).to_vec())
+}
+
+fn hostname() -> String {
+    std::fs::read_to_string("/etc/hostname")
+        .ok()
+        .or_else(|| std::env::var("HOSTNAME").ok())
+        .or_else(|| std::env::var("COMPUTERNAME").ok())
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| "Mexdeclip".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn identity(name: &str) -> Identity {
+        let keypair = noise::generate_keypair().unwrap();
+        Identity {
+            private_key: keypair.private,
+            public_key: to_hex(&keypair.public),
+            name: name.to_string(),
+            port: DEFAULT_PORT,
+        }
+    }
+
+    /// How `identity` is saved on devices paired with it.
+    fn peer_row(identity: &Identity) -> LanPeerRow {
+        LanPeerRow {
+            public_key: identity.public_key.clone(),
+            name: identity.name.clone(),
+            address: None,
+            paired_at: now_millis().to_string(),
+        }
+    }
+
+    fn test_database(name: &str) -> Database {
+        let dir = std::env::temp_dir().join(format!("lan-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        Database::new(dir.join("test.db").to_str().unwrap()).unwrap()
+    }
+
+    fn item_message(text: &str) -> Vec<u8> {
+        serde_json::to_vec(&Message::Item(SharedItem {
+            content_type: "text".to_string(),
+            text_content: Some(text.to_string()),
+            image_data: None,
+            image_width: None,
+            image_height: None,
+            char_count: Some(text.chars().count() as i64),
+            line_count: Some(1),
+            source_app: None,
+        }))
+        .unwrap()
+    }
+
+    /// Shares an item from `sender` to the device it paired with, over
+    /// loopback, while `receiver` accepts the connection the way
+    /// `handle_incoming` does.
+    async fn share_once(
+        sender: &Identity,
+        paired_with: &Identity,
+        receiver: &Identity,
+        database: &Database,
+    ) -> (Result<(), String>, Result<(Channel, LanPeerRow), String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let accept = async {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            assert_eq!(Channel::read_purpose(&mut stream).await, Ok(Purpose::Share));
+            accept_paired(database, stream, receiver).await
+        };
+        let (peer, message) = (peer_row(paired_with), item_message("hello"));
+        let send = send_item(&address, &peer, sender, &message);
+        tokio::join!(send, accept)
+    }
+
+    #[tokio::test]
+    async fn share_from_a_paired_device_is_accepted() {
+        let (sender, receiver) = (identity("sender"), identity("receiver"));
+        let database = test_database("paired");
+        database.save_lan_peer(&peer_row(&sender)).unwrap();
+
+        let (sent, accepted) = share_once(&sender, &receiver, &receiver, &database).await;
+        sent.unwrap();
+        let (mut channel, peer) = accepted.unwrap();
+
+        assert_eq!(peer.name, "sender");
+        let Some(Message::Hello { port, .. }) = recv_message(&mut channel).await.unwrap() else {
+            panic!("expected the hello");
+        };
+        assert_eq!(port, DEFAULT_PORT);
+        let Some(Message::Item(item)) = recv_message(&mut channel).await.unwrap() else {
+            panic!("expected the shared item");
+        };
+        assert_eq!(item.text_content.as_deref(), Some("hello"));
+    }
+
+    #[tokio::test]
+    async fn share_from_an_unpaired_device_is_rejected() {
+        let (sender, receiver) = (identity("sender"), identity("receiver"));
+        let database = test_database("unpaired");
+
+        let (_, accepted) = share_once(&sender, &receiver, &receiver, &database).await;
+
+        // Rejected straight after the handshake, without a channel to read from
+        assert_eq!(accepted.err().as_deref(), Some("Rejected unpaired device"));
+        assert!(database.get_lan_peers().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn send_item_refuses_a_device_whose_key_changed() {
+        let (sender, receiver) = (identity("sender"), identity("receiver"));
+        // Answers at the paired device's address with a key of its own
+        let impostor = identity("receiver");
+        let database = test_database("changed-key");
+        database.save_lan_peer(&peer_row(&sender)).unwrap();
+
+        let (sent, accepted) = share_once(&sender, &receiver, &impostor, &database).await;
+
+        assert_eq!(
+            sent.err().as_deref(),
+            Some("Device key doesn't match the paired device")
+        );
+        // Nothing follows the handshake, not even the hello
+        let (mut channel, _) = accepted.unwrap();
+        assert!(recv_message(&mut channel).await.unwrap().is_none());
+    }
+}
@@ -0,0 +1,341 @@
+//! Noise-encrypted connections between devices. Pairing runs
+//! `Noise_XXpsk3` with a key derived from the pairing code, so only a device
+//! that was shown the code can finish the handshake, and both learn the
+//! other's static key. Both ends then show a fingerprint of the handshake to
+//! compare. Later connections run plain `Noise_XX` and are only accepted
+//! when the remote static key belongs to a paired peer.
+//!
+//! On the wire each Noise message is a big-endian `u16` length and its
+//! bytes. An application message is one Noise message holding its length,
+//! followed by as many as needed for its bytes.
+
+use std::time::Duration;
+
+use sha2::{Digest, Sha256};
+use snow::{Builder, HandshakeState, TransportState};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::time::timeout;
+
+const PATTERN: &str = "Noise_XX_25519_ChaChaPoly_SHA256";
+const PAIRING_PATTERN: &str = "Noise_XXpsk3_25519_ChaChaPoly_SHA256";
+
+const MAX_NOISE_MESSAGE: usize = 65535;
+const TAG_LEN: usize = 16;
+const MAX_CHUNK: usize = MAX_NOISE_MESSAGE - TAG_LEN;
+/// Largest application message accepted, enough for big screenshots
+const MAX_MESSAGE: usize = 64 * 1024 * 1024;
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(3);
+const IO_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// First byte of a connection, sent in the clear
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Purpose {
+    Pair,
+    Share,
+}
+
+impl Purpose {
+    fn byte(self) -> u8 {
+        match self {
+            Self::Pair => b'P',
+            Self::Share => b'S',
+        }
+    }
+}
+
+pub struct Keypair {
+    pub private: Vec<u8>,
+    pub public: Vec<u8>,
+}
+
+pub fn generate_keypair() -> Result<Keypair, String> {
+    let keypair = Builder::new(params(PATTERN)?)
+        .generate_keypair()
+        .map_err(|e| e.to_string())?;
+    Ok(Keypair {
+        private: keypair.private,
+        public: keypair.public,
+    })
+}
+
+pub struct Channel {
+    stream: TcpStream,
+    transport: TransportState,
+    fingerprint: String,
+}
+
+impl Channel {
+    /// Connects and runs the handshake. `pairing_code` is required for
+    /// `Purpose::Pair` and ignored otherwise.
+    pub async fn connect(
+        address: &str,
+        purpose: Purpose,
+        private_key: &[u8],
+        pairing_code: Option<&str>,
+    ) -> Result<Self, String> {
+        let mut stream = timeout(CONNECT_TIMEOUT, TcpStream::connect(address))
+            .await
+            .map_err(|_| format!("Timed out connecting to {}", address))?
+            .map_err(|e| format!("Failed to connect to {}: {}", address, e))?;
+        stream
+            .write_u8(purpose.byte())
+            .await
+            .map_err(|e| e.to_string())?;
+
+        let handshake = handshake_state(purpose, private_key, pairing_code, true)?;
+        Self::handshake(stream, handshake).await
+    }
+
+    /// Reads which kind of connection the remote wants, before `accept`.
+    pub async fn read_purpose(stream: &mut TcpStream) -> Result<Purpose, String> {
+        let byte = timeout(IO_TIMEOUT, stream.read_u8())
+            .await
+            .map_err(|_| "Timed out waiting for peer".to_string())?
+            .map_err(|e| e.to_string())?;
+        match byte {
+            b'P' => Ok(Purpose::Pair),
+            b'S' => Ok(Purpose::Share),
+            _ => Err("Unknown connection type".to_string()),
+        }
+    }
+
+    pub async fn accept(
+        stream: TcpStream,
+        purpose: Purpose,
+        private_key: &[u8],
+        pairing_code: Option<&str>,
+    ) -> Result<Self, String> {
+        let handshake = handshake_state(purpose, private_key, pairing_code, false)?;
+        Self::handshake(stream, handshake).await
+    }
+
+    async fn handshake(
+        mut stream: TcpStream,
+        mut handshake: HandshakeState,
+    ) -> Result<Self, String> {
+        let mut buf = vec![0u8; MAX_NOISE_MESSAGE];
+        while !handshake.is_handshake_finished() {
+            if handshake.is_my_turn() {
+                let len = handshake
+                    .write_message(&[], &mut buf)
+                    .map_err(|e| e.to_string())?;
+                write_frame(&mut stream, &buf[..len]).await?;
+            } else {
+                let frame = read_frame(&mut stream).await?;
+                handshake
+                    .read_message(&frame, &mut buf)
+                    .map_err(|_| "Handshake failed, check the pairing code".to_string())?;
+            }
+        }
+        let fingerprint = fingerprint(handshake.get_handshake_hash());
+        let transport = handshake.into_transport_mode().map_err(|e| e.to_string())?;
+        Ok(Self {
+            stream,
+            transport,
+            fingerprint,
+        })
+    }
+
+    /// The remote device's static public key.
+    pub fn remote_key(&self) -> Vec<u8> {
+        self.transport
+            .get_remote_static()
+            .map(<[u8]>::to_vec)
+            .unwrap_or_default()
+    }
+
+    /// Six digits that match on both ends unless someone sat in between.
+    pub fn fingerprint(&self) -> &str {
+        &self.fingerprint
+    }
+
+    pub fn peer_ip(&self) -> Option<std::net::IpAddr> {
+        self.stream.peer_addr().ok().map(|addr| addr.ip())
+    }
+
+    pub async fn send(&mut self, data: &[u8]) -> Result<(), String> {
+        let mut buf = vec![0u8; MAX_NOISE_MESSAGE];
+        let header = (data.len() as u32).to_be_bytes();
+        for chunk in std::iter::once(&header[..]).chain(data.chunks(MAX_CHUNK)) {
+            let len = self
+                .transport
+                .write_message(chunk, &mut buf)
+                .map_err(|e| e.to_string())?;
+            write_frame(&mut self.stream, &buf[..len]).await?;
+        }
+        Ok(())
+    }
+
+    /// The next message, or `None` once the peer has closed the connection.
+    pub async fn recv(&mut self) -> Result<Option<Vec<u8>>, String> {
+        self.recv_within(IO_TIMEOUT).await
+    }
+
+    /// Like `recv`, but waits up to `wait` for the message to start, e.g.
+    /// while the other side asks its user.
+    pub async fn recv_within(&mut self, wait: Duration) -> Result<Option<Vec<u8>>, String> {
+        let mut buf = vec![0u8; MAX_NOISE_MESSAGE];
+        let Some(header) = read_frame_or_eof(&mut self.stream, wait).await? else {
+            return Ok(None);
+        };
+        let len = self.decrypt(&header, &mut buf)?;
+        let header: [u8; 4] = buf[..len]
+            .try_into()
+            .map_err(|_| "Malformed message header".to_string())?;
+        let total = u32::from_be_bytes(header) as usize;
+        if total > MAX_MESSAGE {
+            return Err("Message too large".to_string());
+        }
+
+        // Grows with what arrives rather than what the header claims
+        let mut data = Vec::new();
+        while data.len() < total {
+            let frame = read_frame(&mut self.stream).await?;
+            let len = self.decrypt(&frame, &mut buf)?;
+            data.extend_from_slice(&buf[..len]);
+        }
+        if data.len() != total {
+            return Err("Malformed message".to_string());
+        }
+        Ok(Some(data))
+    }
+
+    fn decrypt(&mut self, frame: &[u8], buf: &mut [u8]) -> Result<usize, String> {
+        self.transport
+            .read_message(frame, buf)
+            .map_err(|_| "Failed to decrypt message".to_string())
+    }
+}
+
+fn params(pattern: &str) -> Result<snow::params::NoiseParams, String> {
+    pattern.parse().map_err(|e: snow::Error| e.to_string())
+}
+
+fn handshake_state(
+    purpose: Purpose,
+    private_key: &[u8],
+    pairing_code: Option<&str>,
+    initiator: bool,
+) -> Result<HandshakeState, String> {
+    let psk;
+    let builder = match purpose {
+        Purpose::Pair => {
+            let code = pairing_code.ok_or("No pairing code")?;
+            psk = Sha256::digest(format!("mexdeclip-pairing:{}", code).as_bytes());
+            Builder::new(params(PAIRING_PATTERN)?).psk(3, &psk)
+        }
+        Purpose::Share => Builder::new(params(PATTERN)?),
+    }
+    .local_private_key(private_key);
+
+    if initiator {
+        builder.build_initiator()
+    } else {
+        builder.build_responder()
+    }
+    .map_err(|e| e.to_string())
+}
+
+async fn write_frame(stream: &mut TcpStream, frame: &[u8]) -> Result<(), String> {
+    let write = async {
+        stream.write_u16(frame.len() as u16).await?;
+        stream.write_all(frame).await
+    };
+    timeout(IO_TIMEOUT, write)
+        .await
+        .map_err(|_| "Timed out sending to peer".to_string())?
+        .map_err(|e| e.to_string())
+}
+
+/// Handshake hash as `123 456`.
+fn fingerprint(handshake_hash: &[u8]) -> String {
+    let value = handshake_hash
+        .iter()
+        .take(4)
+        .fold(0u32, |value, &byte| value << 8 | byte as u32)
+        % 1_000_000;
+    format!("{:03} {:03}", value / 1000, value % 1000)
+}
+
+async fn read_frame(stream: &mut TcpStream) -> Result<Vec<u8>, String> {
+    read_frame_or_eof(stream, IO_TIMEOUT)
+        .await?
+        .ok_or_else(|| "Connection closed by peer".to_string())
+}
+
+async fn read_frame_or_eof(
+    stream: &mut TcpStream,
+    wait: Duration,
+) -> Result<Option<Vec<u8>>, String> {
+    let read = async {
+        let len = match stream.read_u16().await {
+            Ok(len) => len as usize,
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        let mut frame = vec![0u8; len];
+        stream.read_exact(&mut frame).await?;
+        Ok(Some(frame))
+    };
+    timeout(wait, read)
+        .await
+        .map_err(|_| "Timed out waiting for peer".to_string())?
+        .map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+
+    /// Pairs two devices over loopback: `offering` shows `offered` and
+    /// `joining` connects with `entered`. Returns the joining side's channel,
+    /// then the offering side's.
+    async fn pair(
+        offering: &Keypair,
+        offered: &str,
+        joining: &Keypair,
+        entered: &str,
+    ) -> (Result<Channel, String>, Result<Channel, String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let accept = async {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            assert_eq!(Channel::read_purpose(&mut stream).await, Ok(Purpose::Pair));
+            Channel::accept(stream, Purpose::Pair, &offering.private, Some(offered)).await
+        };
+        let connect = Channel::connect(&address, Purpose::Pair, &joining.private, Some(entered));
+        tokio::join!(connect, accept)
+    }
+
+    #[tokio::test]
+    async fn pairing_with_the_right_code_succeeds() {
+        let (offering, joining) = (generate_keypair().unwrap(), generate_keypair().unwrap());
+        let (connected, accepted) = pair(&offering, "CODE", &joining, "CODE").await;
+        let (mut connected, mut accepted) = (connected.unwrap(), accepted.unwrap());
+
+        assert_eq!(connected.remote_key(), offering.public);
+        assert_eq!(accepted.remote_key(), joining.public);
+        assert_eq!(connected.fingerprint(), accepted.fingerprint());
+
+        let large = vec![7u8; 3 * MAX_CHUNK + 1];
+        connected.send(&large).await.unwrap();
+        assert_eq!(accepted.recv().await.unwrap(), Some(large));
+    }
+
+    #[tokio::test]
+    async fn pairing_with_a_wrong_code_fails_the_handshake() {
+        let (offering, joining) = (generate_keypair().unwrap(), generate_keypair().unwrap());
+        let (connected, accepted) = pair(&offering, "CODE", &joining, "C0DE").await;
+
+        assert_eq!(
+            accepted.err().as_deref(),
+            Some("Handshake failed, check the pairing code")
+        );
+        // The joining side finishes its half, but never hears back
+        let reply = connected.unwrap().recv().await;
+        assert!(!matches!(reply, Ok(Some(_))));
+    }
+}
@@ -0,0 +1,269 @@
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::Arc;
+
+use encoding_rs::{Encoding, UTF_8};
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use reqwest::{header, redirect, Client, Proxy, Response};
+use url::{Host, Url};
+
+use super::policy::{Decision, PreviewSettings};
+
+const USER_AGENT: &str = "Mozilla/5.0 (compatible; LinkPreview/1.0)";
+const TIMEOUT_SECS: u64 = 5;
+const MAX_REDIRECTS: usize = 5;
+
+/// HTTP client for previews. Redirects are followed by hand so every hop, like
+/// every image and oEmbed URL, goes through the same privacy settings and
+/// address checks as the original URL.
+pub struct Fetcher<'a> {
+    client: Client,
+    settings: &'a PreviewSettings,
+    user_requested: bool,
+    /// Names are resolved by the proxy, so they can only be checked up front
+    proxied: bool,
+}
+
+/// A successful response after redirects, with the URL it was served from.
+pub struct Fetched {
+    pub url: Url,
+    pub response: Response,
+}
+
+impl<'a> Fetcher<'a> {
+    pub fn new(settings: &'a PreviewSettings, user_requested: bool) -> Result<Self, String> {
+        let mut builder = Client::builder()
+            .timeout(std::time::Duration::from_secs(TIMEOUT_SECS))
+            .redirect(redirect::Policy::none())
+            .user_agent(USER_AGENT);
+        if let Some(proxy) = &settings.proxy {
+            let proxy = Proxy::all(proxy).map_err(|e| format!("Invalid proxy: {}", e))?;
+            builder = builder.proxy(proxy);
+        } else if settings.block_private {
+            builder = builder.dns_resolver(Arc::new(PublicResolver));
+        }
+        let client = builder.build().map_err(|e| e.to_string())?;
+        Ok(Self {
+            client,
+            settings,
+            user_requested,
+            proxied: settings.proxy.is_some(),
+        })
+    }
+
+    pub async fn get(&self, url: &str) -> Result<Fetched, String> {
+        let mut current = Url::parse(url).map_err(|e| format!("Invalid URL: {}", e))?;
+
+        for _ in 0..=MAX_REDIRECTS {
+            self.check_target(&current).await?;
+
+            let response = self
+                .client
+                .get(current.clone())
+                .send()
+                .await
+                .map_err(|e| error_chain(&e))?;
+
+            if response.status().is_redirection() {
+                let location = response
+                    .headers()
+                    .get(header::LOCATION)
+                    .and_then(|v| v.to_str().ok())
+                    .ok_or("Redirect without a Location header")?;
+                current = current
+                    .join(location)
+                    .map_err(|e| format!("Invalid redirect target: {}", e))?;
+                continue;
+            }
+
+            let response = response.error_for_status().map_err(|e| e.to_string())?;
+            return Ok(Fetched {
+                url: current,
+                response,
+            });
+        }
+
+        Err(format!("Too many redirects (more than {})", MAX_REDIRECTS))
+    }
+
+    async fn check_target(&self, url: &Url) -> Result<(), String> {
+        match url.scheme() {
+            "http" | "https" => {}
+            scheme => return Err(format!("Unsupported URL scheme: {}", scheme)),
+        }
+        match self.settings.decide(url, self.user_requested) {
+            Decision::Fetch => {}
+            Decision::CacheOnly => {
+                return Err(format!(
+                    "Not fetching {} without a request",
+                    url.host_str().unwrap_or_default()
+                ))
+            }
+            Decision::Blocked(reason) => return Err(reason),
+        }
+        if !self.settings.block_private {
+            return Ok(());
+        }
+
+        let addresses: Vec<IpAddr> = match url.host().ok_or("URL has no host")? {
+            Host::Ipv4(ip) => vec![IpAddr::V4(ip)],
+            Host::Ipv6(ip) => vec![IpAddr::V6(ip)],
+            Host::Domain(domain) => {
+                let domain = domain.trim_end_matches('.').to_ascii_lowercase();
+                if domain == "localhost" || domain.ends_with(".localhost") {
+                    return Err(format!("Refusing to fetch local address {}", domain));
+                }
+                // Without a proxy `PublicResolver` filters the addresses that
+                // are actually connected to, so a second lookup here could only
+                // disagree with it
+                if !self.proxied {
+                    return Ok(());
+                }
+                let port = url.port_or_known_default().unwrap_or(80);
+                let resolved = tokio::net::lookup_host((domain.as_str(), port))
+                    .await
+                    .map_err(|e| format!("Failed to resolve {}: {}", domain, e))?;
+                resolved.map(|addr| addr.ip()).collect()
+            }
+        };
+
+        match addresses.iter().find(|ip| is_private(ip)) {
+            Some(ip) => Err(format!("Refusing to fetch private address {}", ip)),
+            None => Ok(()),
+        }
+    }
+}
+
+/// The error with its sources, which is where reqwest keeps the reason a
+/// request failed, e.g. a refused address from `PublicResolver`.
+fn error_chain(error: &dyn std::error::Error) -> String {
+    let mut message = error.to_string();
+    let mut source = error.source();
+    while let Some(cause) = source {
+        message.push_str(": ");
+        message.push_str(&cause.to_string());
+        source = cause.source();
+    }
+    message
+}
+
+/// Resolver that drops private addresses. Checking the same answer that is
+/// connected to closes the gap a separate lookup would leave for DNS
+/// rebinding.
+struct PublicResolver;
+
+impl Resolve for PublicResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        Box::pin(async move {
+            // The port is replaced with the URL's by the connector
+            let addresses: Vec<SocketAddr> = tokio::net::lookup_host((name.as_str(), 0))
+                .await?
+                .filter(|addr| !is_private(&addr.ip()))
+                .collect();
+            if addresses.is_empty() {
+                return Err(format!("Refusing to fetch private address {}", name.as_str()).into());
+            }
+            Ok(Box::new(addresses.into_iter()) as Addrs)
+        })
+    }
+}
+
+/// Loopback, RFC 1918, link-local, CGNAT, unique-local and other
+/// non-routable ranges.
+pub(super) fn is_private(ip: &IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_private_v4(ip),
+        IpAddr::V6(ip) => is_private_v6(ip),
+    }
+}
+
+fn is_private_v4(ip: &Ipv4Addr) -> bool {
+    let [a, b, ..] = ip.octets();
+    ip.is_private()
+        || ip.is_loopback()
+        || ip.is_link_local()
+        || ip.is_unspecified()
+        || ip.is_broadcast()
+        || ip.is_documentation()
+        || a == 0
+        // 100.64.0.0/10 carrier-grade NAT
+        || (a == 100 && (64..128).contains(&b))
+}
+
+fn is_private_v6(ip: &Ipv6Addr) -> bool {
+    if let Some(v4) = ip.to_ipv4_mapped() {
+        return is_private_v4(&v4);
+    }
+    let first = ip.segments()[0];
+    ip.is_loopback()
+        || ip.is_unspecified()
+        // fc00::/7 unique local
+        || (first & 0xfe00) == 0xfc00
+        // fe80::/10 link local
+        || (first & 0xffc0) == 0xfe80
+}
+
+/// The lowercased MIME type of a response, without parameters.
+pub fn mime_type(response: &Response) -> Option<String> {
+    content_type_param(response, None)
+}
+
+/// The `charset` parameter of the Content-Type header, if any.
+pub fn charset(response: &Response) -> Option<String> {
+    content_type_param(response, Some("charset"))
+}
+
+fn content_type_param(response: &Response, param: Option<&str>) -> Option<String> {
+    let value = response
+        .headers()
+        .get(header::CONTENT_TYPE)?
+        .to_str()
+        .ok()?;
+    let mut parts = value.split(';').map(str::trim);
+    let mime = parts.next()?.to_ascii_lowercase();
+    match param {
+        None => Some(mime),
+        Some(name) => parts
+            .filter_map(|p| p.split_once('='))
+            .find(|(key, _)| key.trim().eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.trim().trim_matches('"').to_string()),
+    }
+}
+
+/// Reads at most `limit` bytes of the body. Returns the bytes and whether the
+/// body was cut short, without buffering anything past the limit.
+pub async fn read_limited(mut response: Response, limit: usize) -> Result<(Vec<u8>, bool), String> {
+    let mut body = Vec::new();
+    while let Some(chunk) = response.chunk().await.map_err(|e| e.to_string())? {
+        let remaining = limit - body.len();
+        if chunk.len() > remaining {
+            body.extend_from_slice(&chunk[..remaining]);
+            return Ok((body, true));
+        }
+        body.extend_from_slice(&chunk);
+    }
+    Ok((body, false))
+}
+
+/// Decodes an HTML body using, in order: a byte order mark, the HTTP charset,
+/// a `<meta charset>` in the first KiB, then UTF-8.
+pub fn decode_html(bytes: &[u8], header_charset: Option<&str>) -> String {
+    let encoding = header_charset
+        .and_then(|label| Encoding::for_label(label.as_bytes()))
+        .or_else(|| sniff_meta_charset(bytes))
+        .unwrap_or(UTF_8);
+    // `decode` lets a BOM override the chosen encoding
+    let (text, _, _) = encoding.decode(bytes);
+    text.into_owned()
+}
+
+fn sniff_meta_charset(bytes: &[u8]) -> Option<&'static Encoding> {
+    let head = &bytes[..bytes.len().min(1024)];
+    let head = String::from_utf8_lossy(head).to_ascii_lowercase();
+    let start = head.find("charset=")? + "charset=".len();
+    let label: String = head[start..]
+        .trim_start_matches(['"', '\''])
+        .chars()
+        .take_while(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | ':' | '.'))
+        .collect();
+    Encoding::for_label(label.as_bytes())
+}
@@ -0,0 +1,196 @@
+use scraper::{Html, Selector};
+use serde_json::Value;
+use url::Url;
+
+/// Metadata scraped from a page, before assets are downloaded.
+#[derive(Debug, Default)]
+pub struct PageMetadata {
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub image: Option<String>,
+    pub favicon: Option<String>,
+    pub site_name: Option<String>,
+    /// JSON oEmbed endpoint advertised by the page
+    pub oembed_url: Option<String>,
+}
+
+impl PageMetadata {
+    /// True when the page itself lacked a title or image worth asking oEmbed for.
+    pub fn is_incomplete(&self) -> bool {
+        self.title.is_none() || self.image.is_none()
+    }
+
+    /// Fills gaps from an oEmbed JSON response.
+    pub fn merge_oembed(&mut self, json: &str, base: &Url) {
+        let Ok(Value::Object(data)) = serde_json::from_str::<Value>(json) else {
+            return;
+        };
+        let field = |key: &str| data.get(key).and_then(string_value);
+
+        fill(&mut self.title, field("title"));
+        fill(&mut self.site_name, field("provider_name"));
+        fill(&mut self.description, field("author_name"));
+        fill(
+            &mut self.image,
+            field("thumbnail_url").map(|img| resolve_url(base, &img)),
+        );
+    }
+}
+
+/// Extracts preview metadata, preferring Open Graph, then Twitter cards,
+/// then JSON-LD, then plain HTML.
+pub fn extract(html: &str, base: &Url) -> PageMetadata {
+    let document = Html::parse_document(html);
+    let json_ld = json_ld(&document);
+
+    let title = select_meta_content(&document, "og:title")
+        .or_else(|| twitter_card(&document, "twitter:title"))
+        .or_else(|| json_ld.as_ref().and_then(|d| d.title.clone()))
+        .or_else(|| {
+            Selector::parse("title")
+                .ok()
+                .and_then(|sel| document.select(&sel).next())
+                .map(|el| el.text().collect::<String>())
+        });
+
+    let description = select_meta_content(&document, "og:description")
+        .or_else(|| twitter_card(&document, "twitter:description"))
+        .or_else(|| json_ld.as_ref().and_then(|d| d.description.clone()))
+        .or_else(|| select_meta_content_by_name(&document, "description"));
+
+    let image = select_meta_content(&document, "og:image")
+        .or_else(|| twitter_card(&document, "twitter:image"))
+        .or_else(|| twitter_card(&document, "twitter:image:src"))
+        .or_else(|| json_ld.as_ref().and_then(|d| d.image.clone()))
+        .map(|img| resolve_url(base, &img));
+
+    let site_name = select_meta_content(&document, "og:site_name")
+        .or_else(|| json_ld.as_ref().and_then(|d| d.site_name.clone()));
+
+    // Resolve favicon
+    let favicon = select_link_href(&document, "icon")
+        .or_else(|| select_link_href(&document, "shortcut icon"))
+        .map(|href| resolve_url(base, &href))
+        .or_else(|| base.join("/favicon.ico").ok().map(|u| u.to_string()));
+
+    let oembed_url = Selector::parse("link[rel=\"alternate\"][type=\"application/json+oembed\"]")
+        .ok()
+        .and_then(|sel| document.select(&sel).next())
+        .and_then(|el| el.value().attr("href"))
+        .map(|href| resolve_url(base, href));
+
+    PageMetadata {
+        title: clean(title),
+        description: clean(description),
+        image,
+        favicon,
+        site_name: clean(site_name),
+        oembed_url,
+    }
+}
+
+#[derive(Debug, Default)]
+struct JsonLd {
+    title: Option<String>,
+    description: Option<String>,
+    image: Option<String>,
+    site_name: Option<String>,
+}
+
+/// Reads the first JSON-LD entity that has a name or headline.
+fn json_ld(document: &Html) -> Option<JsonLd> {
+    let selector = Selector::parse("script[type=\"application/ld+json\"]").ok()?;
+    document
+        .select(&selector)
+        .filter_map(|el| serde_json::from_str::<Value>(&el.text().collect::<String>()).ok())
+        .flat_map(json_ld_entities)
+        .find_map(|entity| {
+            let title = entity
+                .get("headline")
+                .or_else(|| entity.get("name"))
+                .and_then(string_value)?;
+            Some(JsonLd {
+                title: Some(title),
+                description: entity.get("description").and_then(string_value),
+                image: entity.get("image").and_then(image_value),
+                site_name: entity
+                    .get("publisher")
+                    .and_then(|p| p.get("name"))
+                    .and_then(string_value),
+            })
+        })
+}
+
+/// Flattens top-level arrays and `@graph` containers into a list of entities.
+fn json_ld_entities(value: Value) -> Vec<Value> {
+    match value {
+        Value::Array(items) => items.into_iter().flat_map(json_ld_entities).collect(),
+        Value::Object(mut map) => match map.remove("@graph") {
+            Some(graph) => json_ld_entities(graph),
+            None => vec![Value::Object(map)],
+        },
+        _ => Vec::new(),
+    }
+}
+
+/// JSON-LD images may be a URL, an ImageObject, or a list of either.
+fn image_value(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) => Some(s.clone()),
+        Value::Array(items) => items.iter().find_map(image_value),
+        Value::Object(map) => map.get("url").and_then(string_value),
+        _ => None,
+    }
+}
+
+fn string_value(value: &Value) -> Option<String> {
+    value.as_str().map(str::to_string)
+}
+
+fn fill(slot: &mut Option<String>, value: Option<String>) {
+    if slot.is_none() {
+        *slot = value.filter(|v| !v.trim().is_empty());
+    }
+}
+
+/// Collapses whitespace and drops empty values.
+fn clean(value: Option<String>) -> Option<String> {
+    value
+        .map(|v| v.split_whitespace().collect::<Vec<_>>().join(" "))
+        .filter(|v| !v.is_empty())
+}
+
+/// Twitter cards officially use `name`, but many sites emit `property`.
+fn twitter_card(document: &Html, key: &str) -> Option<String> {
+    select_meta_content_by_name(document, key).or_else(|| select_meta_content(document, key))
+}
+
+fn select_meta_content(document: &Html, property: &str) -> Option<String> {
+    let selector = Selector::parse(&format!("meta[property=\"{}\"]", property)).ok()?;
+    document
+        .select(&selector)
+        .next()
+        .and_then(|el| el.value().attr("content").map(|s| s.to_string()))
+}
+
+fn select_meta_content_by_name(document: &Html, name: &str) -> Option<String> {
+    let selector = Selector::parse(&format!("meta[name=\"{}\"]", name)).ok()?;
+    document
+        .select(&selector)
+        .next()
+        .and_then(|el| el.value().attr("content").map(|s| s.to_string()))
+}
+
+fn select_link_href(document: &Html, rel: &str) -> Option<String> {
+    let selector = Selector::parse(&format!("link[rel=\"{}\"]", rel)).ok()?;
+    document
+        .select(&selector)
+        .next()
+        .and_then(|el| el.value().attr("href").map(|s| s.to_string()))
+}
+
+fn resolve_url(base: &Url, href: &str) -> String {
+    base.join(href)
+        .map(|u| u.to_string())
+        .unwrap_or_else(|_| href.to_string())
+}
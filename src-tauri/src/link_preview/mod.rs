@@ -0,0 +1,524 @@
+mod fetch;
+mod metadata;
+mod policy;
+
+use std::path::{Path, PathBuf};
+
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use sha2::{Digest, Sha256};
+
+use crate::db::{now_millis, Database, LinkPreviewRow};
+use fetch::Fetcher;
+use metadata::PageMetadata;
+use policy::{Decision, PreviewSettings};
+
+/// Only the start of a page is needed for its metadata
+const MAX_HTML_BYTES: usize = 1024 * 1024;
+const MAX_OEMBED_BYTES: usize = 256 * 1024;
+/// Favicons and og:images larger than this are not stored locally
+const MAX_ASSET_BYTES: usize = 2 * 1024 * 1024;
+
+#[derive(serde::Serialize)]
+pub struct LinkPreviewData {
+    pub title: Option<String>,
+    pub description: Option<String>,
+    /// Data URI when the image is stored locally, otherwise the remote URL
+    pub image: Option<String>,
+    /// Data URI when the favicon is stored locally, otherwise the remote URL
+    pub favicon: Option<String>,
+    pub site_name: Option<String>,
+    pub fetched_at: String,
+}
+
+/// Normalizes a URL for use as a cache key: drops the fragment and tracking
+/// parameters. Scheme/host case and default ports are normalized by `url`.
+pub fn normalize_url(raw: &str) -> Result<String, String> {
+    let mut url = url::Url::parse(raw.trim()).map_err(|e| format!("Invalid URL: {}", e))?;
+    url.set_fragment(None);
+
+    let kept: Vec<(String, String)> = url
+        .query_pairs()
+        .filter(|(key, _)| !key.starts_with("utm_") && key != "fbclid" && key != "gclid")
+        .map(|(k, v)| (k.into_owned(), v.into_owned()))
+        .collect();
+    if kept.is_empty() {
+        url.set_query(None);
+    } else {
+        url.query_pairs_mut().clear().extend_pairs(kept);
+    }
+
+    Ok(url.to_string())
+}
+
+/// Returns a preview for `url`, serving the database cache while it is fresh,
+/// and falling back to stale cache entries when offline or when the fetch fails.
+/// `None` means the privacy settings defer fetching until `user_requested`.
+pub async fn get_preview(
+    url: &str,
+    user_requested: bool,
+    database: &Database,
+    assets_dir: &Path,
+) -> Result<Option<LinkPreviewData>, String> {
+    let key = normalize_url(url)?;
+    let settings = PreviewSettings::load(database)?;
+
+    let decision = settings.decide(
+        &url::Url::parse(&key).map_err(|e| e.to_string())?,
+        user_requested,
+    );
+    if let Decision::Blocked(reason) = decision {
+        return Err(reason);
+    }
+
+    let cached = database.get_link_preview(&key)?;
+    if let Some(row) = &cached {
+        let age_ms = now_millis() - row.fetched_at.parse::<i64>().unwrap_or(0);
+        let fresh = age_ms < settings.ttl_hours.saturating_mul(60 * 60 * 1000);
+        if settings.offline || fresh || decision == Decision::CacheOnly {
+            return Ok(Some(to_preview_data(row)));
+        }
+    } else if settings.offline {
+        return Err("No cached preview available offline".to_string());
+    } else if decision == Decision::CacheOnly {
+        return Ok(None);
+    }
+
+    match fetch_and_store(url, &key, &settings, user_requested, database, assets_dir).await {
+        Ok(row) => Ok(Some(to_preview_data(&row))),
+        Err(e) => cached
+            .as_ref()
+            .map(|row| Some(to_preview_data(row)))
+            .ok_or(e),
+    }
+}
+
+async fn fetch_and_store(
+    url: &str,
+    key: &str,
+    settings: &PreviewSettings,
+    user_requested: bool,
+    database: &Database,
+    assets_dir: &Path,
+) -> Result<LinkPreviewRow, String> {
+    let fetcher = Fetcher::new(settings, user_requested)?;
+
+    let page = fetch_page(&fetcher, url).await?;
+
+    let image_path = match &page.image {
+        Some(image) => download_asset(&fetcher, image, assets_dir).await,
+        None => None,
+    };
+    let favicon_path = match &page.favicon {
+        Some(favicon) => download_asset(&fetcher, favicon, assets_dir).await,
+        None => None,
+    };
+
+    let row = LinkPreviewRow {
+        url: key.to_string(),
+        title: page.title,
+        description: page.description,
+        image: page.image,
+        favicon: page.favicon,
+        site_name: page.site_name,
+        image_path: image_path.map(|p| p.to_string_lossy().into_owned()),
+        favicon_path: favicon_path.map(|p| p.to_string_lossy().into_owned()),
+        fetched_at: now_millis().to_string(),
+    };
+    database.upsert_link_preview(&row)?;
+    Ok(row)
+}
+
+async fn fetch_page(fetcher: &Fetcher<'_>, url: &str) -> Result<PageMetadata, String> {
+    let fetched = fetcher.get(url).await?;
+
+    let mime = fetch::mime_type(&fetched.response).unwrap_or_default();
+    if mime != "text/html" && mime != "application/xhtml+xml" {
+        return Err(format!("Not an HTML page ({})", mime));
+    }
+    let charset = fetch::charset(&fetched.response);
+
+    // A truncated page still has its <head>, which is all we read
+    let (body, _) = fetch::read_limited(fetched.response, MAX_HTML_BYTES).await?;
+    let html = fetch::decode_html(&body, charset.as_deref());
+    let mut page = metadata::extract(&html, &fetched.url);
+
+    if page.is_incomplete() {
+        if let Some(oembed_url) = page.oembed_url.clone() {
+            if let Ok(json) = fetch_oembed(fetcher, &oembed_url).await {
+                page.merge_oembed(&json, &fetched.url);
+            }
+        }
+    }
+
+    Ok(page)
+}
+
+async fn fetch_oembed(fetcher: &Fetcher<'_>, url: &str) -> Result<String, String> {
+    let fetched = fetcher.get(url).await?;
+    let (body, truncated) = fetch::read_limited(fetched.response, MAX_OEMBED_BYTES).await?;
+    if truncated {
+        return Err("oEmbed response too large".to_string());
+    }
+    String::from_utf8(body).map_err(|e| e.to_string())
+}
+
+/// Downloads an image into `assets_dir`, named by the hash of its URL.
+/// Failures are not fatal: the preview just keeps the remote URL.
+async fn download_asset(fetcher: &Fetcher<'_>, url: &str, assets_dir: &Path) -> Option<PathBuf> {
+    let fetched = fetcher.get(url).await.ok()?;
+    let mime = fetch::mime_type(&fetched.response).filter(|m| m.starts_with("image/"))?;
+    // Too large to keep anyway, don't download the first part
+    if fetched
+        .response
+        .content_length()
+        .is_some_and(|len| len > MAX_ASSET_BYTES as u64)
+    {
+        return None;
+    }
+
+    let (bytes, truncated) = fetch::read_limited(fetched.response, MAX_ASSET_BYTES)
+        .await
+        .ok()?;
+    if truncated || bytes.is_empty() {
+        return None;
+    }
+
+    std::fs::create_dir_all(assets_dir).ok()?;
+    let name = format!(
+        "{:x}.{}",
+        Sha256::digest(url.as_bytes()),
+        extension_for_mime(&mime)
+    );
+    let path = assets_dir.join(name);
+    std::fs::write(&path, &bytes).ok()?;
+    Some(path)
+}
+
+fn extension_for_mime(mime: &str) -> &'static str {
+    match mime {
+        "image/png" => "png",
+        "image/jpeg" => "jpg",
+        "image/gif" => "gif",
+        "image/webp" => "webp",
+        "image/svg+xml" => "svg",
+        "image/x-icon" | "image/vnd.microsoft.icon" => "ico",
+        _ => "img",
+    }
+}
+
+fn mime_for_extension(ext: &str) -> &'static str {
+    match ext {
+        "png" => "image/png",
+        "jpg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "svg" => "image/svg+xml",
+        "ico" => "image/x-icon",
+        _ => "application/octet-stream",
+    }
+}
+
+/// Reads a stored asset back as a data URI so the UI can render it offline.
+fn data_uri(path: &str) -> Option<String> {
+    let path = Path::new(path);
+    let bytes = std::fs::read(path).ok()?;
+    let ext = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+    Some(format!(
+        "data:{};base64,{}",
+        mime_for_extension(ext),
+        BASE64.encode(bytes)
+    ))
+}
+
+fn to_preview_data(row: &LinkPreviewRow) -> LinkPreviewData {
+    LinkPreviewData {
+        title: row.title.clone(),
+        description: row.description.clone(),
+        image: row
+            .image_path
+            .as_deref()
+            .and_then(data_uri)
+            .or_else(|| row.image.clone()),
+        favicon: row
+            .favicon_path
+            .as_deref()
+            .and_then(data_uri)
+            .or_else(|| row.favicon.clone()),
+        site_name: row.site_name.clone(),
+        fetched_at: row.fetched_at.clone(),
+    }
+}
+
+/// Removes every cached preview and its downloaded assets.
+pub fn clear_cache(database: &Database, assets_dir: &Path) -> Result<(), String> {
+    database.clear_link_previews()?;
+    if assets_dir.exists() {
+        std::fs::remove_dir_all(assets_dir).map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    use super::*;
+    use policy::{
+        ALLOWLIST_SETTING, BLOCK_PRIVATE_SETTING, DENYLIST_SETTING, MODE_SETTING, OFFLINE_SETTING,
+        TTL_SETTING,
+    };
+
+    const PAGE: &str = "<html><head><title>Stub page</title></head><body></body></html>";
+
+    /// Local HTTP server. `/page` serves `page`, or a 500 while `failing` is
+    /// set, `/redirect` redirects to `location` and anything else is a 404.
+    struct Stub {
+        base: String,
+        page_hits: Arc<AtomicUsize>,
+        failing: Arc<AtomicBool>,
+    }
+
+    impl Stub {
+        async fn start(page: String, location: Option<String>) -> Self {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let base = format!("http://127.0.0.1:{}", listener.local_addr().unwrap().port());
+            let page_hits = Arc::new(AtomicUsize::new(0));
+            let failing = Arc::new(AtomicBool::new(false));
+
+            let (hits, fail) = (Arc::clone(&page_hits), Arc::clone(&failing));
+            tokio::spawn(async move {
+                while let Ok((mut stream, _)) = listener.accept().await {
+                    let mut request = vec![0u8; 4096];
+                    let len = stream.read(&mut request).await.unwrap_or(0);
+                    let request = String::from_utf8_lossy(&request[..len]).into_owned();
+                    let path = request.split_whitespace().nth(1).unwrap_or("/").to_string();
+
+                    let (status, headers, body) = match path.as_str() {
+                        "/page" => {
+                            hits.fetch_add(1, Ordering::SeqCst);
+                            if fail.load(Ordering::SeqCst) {
+                                ("500 Internal Server Error", String::new(), String::new())
+                            } else {
+                                (
+                                    "200 OK",
+                                    "Content-Type: text/html\r\n".to_string(),
+                                    page.clone(),
+                                )
+                            }
+                        }
+                        "/redirect" => (
+                            "302 Found",
+                            format!("Location: {}\r\n", location.clone().unwrap_or_default()),
+                            String::new(),
+                        ),
+                        _ => ("404 Not Found", String::new(), String::new()),
+                    };
+                    let response = format!(
+                        "HTTP/1.1 {}\r\n{}Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+                        status,
+                        headers,
+                        body.len(),
+                        body
+                    );
+                    let _ = stream.write_all(response.as_bytes()).await;
+                }
+            });
+
+            Self {
+                base,
+                page_hits,
+                failing,
+            }
+        }
+
+        fn url(&self, path: &str) -> String {
+            format!("{}{}", self.base, path)
+        }
+
+        fn hits(&self) -> usize {
+            self.page_hits.load(Ordering::SeqCst)
+        }
+    }
+
+    /// A fresh database that may fetch from the stub, plus an assets dir.
+    fn test_database(name: &str) -> (Database, PathBuf) {
+        let dir =
+            std::env::temp_dir().join(format!("link-preview-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let database = Database::new(dir.join("test.db").to_str().unwrap()).unwrap();
+        database
+            .set_setting(BLOCK_PRIVATE_SETTING, "false")
+            .unwrap();
+        database
+            .set_setting(ALLOWLIST_SETTING, "127.0.0.1")
+            .unwrap();
+        (database, dir.join("assets"))
+    }
+
+    async fn title(
+        url: &str,
+        user_requested: bool,
+        database: &Database,
+        assets: &Path,
+    ) -> Option<String> {
+        get_preview(url, user_requested, database, assets)
+            .await
+            .unwrap()
+            .and_then(|preview| preview.title)
+    }
+
+    #[tokio::test]
+    async fn fresh_cache_is_served_without_fetching() {
+        let stub = Stub::start(PAGE.to_string(), None).await;
+        let (database, assets) = test_database("fresh");
+
+        let url = stub.url("/page");
+        assert_eq!(
+            title(&url, false, &database, &assets).await.as_deref(),
+            Some("Stub page")
+        );
+        assert_eq!(
+            title(&url, false, &database, &assets).await.as_deref(),
+            Some("Stub page")
+        );
+        assert_eq!(stub.hits(), 1);
+    }
+
+    #[tokio::test]
+    async fn stale_cache_is_fetched_again() {
+        let stub = Stub::start(PAGE.to_string(), None).await;
+        let (database, assets) = test_database("stale");
+        database.set_setting(TTL_SETTING, "0").unwrap();
+
+        let url = stub.url("/page");
+        title(&url, false, &database, &assets).await;
+        title(&url, false, &database, &assets).await;
+        assert_eq!(stub.hits(), 2);
+    }
+
+    #[tokio::test]
+    async fn huge_ttl_does_not_overflow() {
+        let stub = Stub::start(PAGE.to_string(), None).await;
+        let (database, assets) = test_database("huge-ttl");
+        database
+            .set_setting(TTL_SETTING, &i64::MAX.to_string())
+            .unwrap();
+
+        let url = stub.url("/page");
+        title(&url, false, &database, &assets).await;
+        assert_eq!(
+            title(&url, false, &database, &assets).await.as_deref(),
+            Some("Stub page")
+        );
+        assert_eq!(stub.hits(), 1);
+    }
+
+    #[tokio::test]
+    async fn offline_serves_stale_cache_without_fetching() {
+        let stub = Stub::start(PAGE.to_string(), None).await;
+        let (database, assets) = test_database("offline");
+
+        let url = stub.url("/page");
+        title(&url, false, &database, &assets).await;
+        database.set_setting(TTL_SETTING, "0").unwrap();
+        database.set_setting(OFFLINE_SETTING, "true").unwrap();
+
+        assert_eq!(
+            title(&url, false, &database, &assets).await.as_deref(),
+            Some("Stub page")
+        );
+        assert!(
+            get_preview(&stub.url("/page?other"), false, &database, &assets)
+                .await
+                .is_err()
+        );
+        assert_eq!(stub.hits(), 1);
+    }
+
+    #[tokio::test]
+    async fn on_demand_mode_only_fetches_when_requested() {
+        let stub = Stub::start(PAGE.to_string(), None).await;
+        let (database, assets) = test_database("on-demand");
+        database.set_setting(MODE_SETTING, "on_demand").unwrap();
+        database.set_setting(ALLOWLIST_SETTING, "").unwrap();
+
+        let url = stub.url("/page");
+        assert!(get_preview(&url, false, &database, &assets)
+            .await
+            .unwrap()
+            .is_none());
+        assert_eq!(stub.hits(), 0);
+
+        assert_eq!(
+            title(&url, true, &database, &assets).await.as_deref(),
+            Some("Stub page")
+        );
+        database.set_setting(TTL_SETTING, "0").unwrap();
+        assert_eq!(
+            title(&url, false, &database, &assets).await.as_deref(),
+            Some("Stub page")
+        );
+        assert_eq!(stub.hits(), 1);
+    }
+
+    #[tokio::test]
+    async fn failed_fetch_falls_back_to_cache() {
+        let stub = Stub::start(PAGE.to_string(), None).await;
+        let (database, assets) = test_database("fallback");
+
+        let url = stub.url("/page");
+        title(&url, false, &database, &assets).await;
+        database.set_setting(TTL_SETTING, "0").unwrap();
+        stub.failing.store(true, Ordering::SeqCst);
+
+        assert_eq!(
+            title(&url, false, &database, &assets).await.as_deref(),
+            Some("Stub page")
+        );
+        assert_eq!(stub.hits(), 2);
+        assert!(
+            get_preview(&stub.url("/page?uncached"), false, &database, &assets)
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn oversized_page_keeps_its_head() {
+        let page = format!("{}{}", PAGE, " ".repeat(2 * MAX_HTML_BYTES));
+        let stub = Stub::start(page, None).await;
+        let (database, assets) = test_database("oversized");
+
+        let url = stub.url("/page");
+        assert_eq!(
+            title(&url, false, &database, &assets).await.as_deref(),
+            Some("Stub page")
+        );
+    }
+
+    #[tokio::test]
+    async fn redirect_to_denied_host_is_not_followed() {
+        let target = Stub::start(PAGE.to_string(), None).await;
+        let port = target.base.rsplit(':').next().unwrap().to_string();
+        let stub = Stub::start(
+            PAGE.to_string(),
+            Some(format!("http://localhost:{}/page", port)),
+        )
+        .await;
+        let (database, assets) = test_database("redirect");
+        database.set_setting(DENYLIST_SETTING, "localhost").unwrap();
+
+        assert!(
+            get_preview(&stub.url("/redirect"), false, &database, &assets)
+                .await
+                .is_err()
+        );
+        assert_eq!(target.hits(), 0);
+    }
+}
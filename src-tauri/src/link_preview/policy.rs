@@ -0,0 +1,152 @@
+use url::{Host, Url};
+
+use super::fetch;
+use crate::db::Database;
+
+/// Settings key: "always", "on_demand" or "never"
+pub const MODE_SETTING: &str = "link_preview_mode";
+/// Settings key: domains that are always fetched, comma or newline separated
+pub const ALLOWLIST_SETTING: &str = "link_preview_allowlist";
+/// Settings key: domains that are never fetched, comma or newline separated
+pub const DENYLIST_SETTING: &str = "link_preview_denylist";
+/// Settings key: proxy URL for preview requests, e.g. `http://proxy:3128`
+pub const PROXY_SETTING: &str = "link_preview_proxy";
+/// Settings key: serve cached previews only, never touch the network
+pub const OFFLINE_SETTING: &str = "link_preview_offline";
+/// Settings key: hours before a cached preview is re-fetched
+pub const TTL_SETTING: &str = "link_preview_ttl_hours";
+/// Settings key: "false" allows fetching loopback/private network addresses
+pub const BLOCK_PRIVATE_SETTING: &str = "link_preview_block_private";
+
+const DEFAULT_TTL_HOURS: i64 = 24 * 7;
+
+/// Suffixes that only resolve inside a private network.
+const INTRANET_SUFFIXES: &[&str] = &[
+    ".local",
+    ".localhost",
+    ".lan",
+    ".home",
+    ".home.arpa",
+    ".internal",
+    ".intranet",
+    ".corp",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PreviewMode {
+    /// Fetch previews as items render
+    Always,
+    /// Only fetch when the user asks for a preview
+    OnDemand,
+    /// Never fetch
+    Never,
+}
+
+impl PreviewMode {
+    fn from_setting(value: Option<&str>) -> Self {
+        match value {
+            Some("on_demand") => Self::OnDemand,
+            Some("never") => Self::Never,
+            _ => Self::Always,
+        }
+    }
+}
+
+/// What may be done for a given URL.
+#[derive(Debug, PartialEq)]
+pub enum Decision {
+    Fetch,
+    /// Serve the cache, but don't make a request without the user asking
+    CacheOnly,
+    Blocked(String),
+}
+
+pub struct PreviewSettings {
+    pub mode: PreviewMode,
+    pub allowlist: Vec<String>,
+    pub denylist: Vec<String>,
+    pub proxy: Option<String>,
+    pub block_private: bool,
+    pub offline: bool,
+    pub ttl_hours: i64,
+}
+
+impl PreviewSettings {
+    pub fn load(database: &Database) -> Result<Self, String> {
+        let setting = |key: &str| database.get_setting(key);
+
+        Ok(Self {
+            mode: PreviewMode::from_setting(setting(MODE_SETTING)?.as_deref()),
+            allowlist: parse_domain_list(setting(ALLOWLIST_SETTING)?.as_deref()),
+            denylist: parse_domain_list(setting(DENYLIST_SETTING)?.as_deref()),
+            proxy: setting(PROXY_SETTING)?
+                .map(|p| p.trim().to_string())
+                .filter(|p| !p.is_empty()),
+            block_private: setting(BLOCK_PRIVATE_SETTING)?.as_deref() != Some("false"),
+            offline: setting(OFFLINE_SETTING)?.as_deref() == Some("true"),
+            ttl_hours: setting(TTL_SETTING)?
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(DEFAULT_TTL_HOURS),
+        })
+    }
+
+    /// The denylist always wins and the allowlist always fetches. Otherwise
+    /// intranet hosts and on-demand mode only fetch when `user_requested`.
+    pub fn decide(&self, url: &Url, user_requested: bool) -> Decision {
+        let Some(host) = url.host() else {
+            return Decision::Blocked("URL has no host".to_string());
+        };
+        let name = host.to_string().to_ascii_lowercase();
+
+        if matches_any(&name, &self.denylist) {
+            return Decision::Blocked(format!("Link previews are disabled for {}", name));
+        }
+        if self.mode == PreviewMode::Never {
+            return Decision::Blocked("Link previews are disabled".to_string());
+        }
+        if matches_any(&name, &self.allowlist) || user_requested {
+            return Decision::Fetch;
+        }
+        if self.mode == PreviewMode::OnDemand || is_intranet_host(&host) {
+            return Decision::CacheOnly;
+        }
+        Decision::Fetch
+    }
+}
+
+/// Splits a list of domains. A leading `*.` is accepted and ignored, since
+/// every entry already matches its subdomains.
+fn parse_domain_list(value: Option<&str>) -> Vec<String> {
+    value
+        .unwrap_or_default()
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .map(|d| {
+            d.trim()
+                .trim_start_matches("*.")
+                .trim_matches('.')
+                .to_ascii_lowercase()
+        })
+        .filter(|d| !d.is_empty())
+        .collect()
+}
+
+fn matches_any(host: &str, domains: &[String]) -> bool {
+    domains.iter().any(|d| {
+        host == d
+            || host
+                .strip_suffix(d.as_str())
+                .is_some_and(|p| p.ends_with('.'))
+    })
+}
+
+/// Single-label names, private IPs and well-known internal suffixes.
+fn is_intranet_host(host: &Host<&str>) -> bool {
+    match host {
+        Host::Ipv4(ip) => fetch::is_private(&(*ip).into()),
+        Host::Ipv6(ip) => fetch::is_private(&(*ip).into()),
+        Host::Domain(domain) => {
+            let domain = domain.trim_end_matches('.').to_ascii_lowercase();
+            !domain.contains('.') || INTRANET_SUFFIXES.iter().any(|s| domain.ends_with(s))
+        }
+    }
+}
@@ -1,39 +1,76 @@
 // Prevents additional console window on Windows in release, DO NOT REMOVE!!
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
+mod classify;
 mod clipboard;
+mod color;
 mod commands;
+mod copy_events;
+mod date;
 mod db;
+mod fractional_index;
+mod fuzzy;
+mod image_ops;
+mod keychain;
+mod lan;
+mod link_preview;
+mod ocr;
+mod perceptual_hash;
+mod registers;
 mod schema;
+mod search_query;
+mod secrets;
+mod stats;
+mod structured;
+mod sync;
+mod transform;
 mod tray;
+mod undo;
 mod window_state;
 
 use clipboard::ClipboardManager;
 use commands::{
-    db_bump_item, db_clear_all, db_dedup_item, db_delete_item, db_get_all_items, db_get_item_count,
-    db_insert_item, db_toggle_favorite, db_update_sort_orders,
+    db_add_palette_items, db_batch_add_tag, db_batch_copy, db_batch_delete, db_batch_export,
+    db_batch_merge, db_batch_remove_tag, db_batch_set_favorite, db_bump_item, db_clear_all,
+    db_clear_history, db_clear_register, db_create_palette, db_dedup_item, db_delete_item,
+    db_delete_palette, db_empty_trash, db_find_similar_images, db_get_all_items,
+    db_get_copy_timeline, db_get_item, db_get_item_count, db_get_item_summaries, db_get_item_tags,
+    db_get_items_after, db_get_palettes, db_get_registers, db_get_tags, db_get_trash,
+    db_get_undo_state, db_get_usage_stats, db_record_capture, db_record_copy_event, db_redo,
+    db_restore_items, db_set_register, db_split_item, db_toggle_favorite, db_top_sort_order,
+    db_undo, db_update_sort_orders,
 };
 use commands::{
-    detect_color_content, detect_date_content, detect_env_content, fetch_link_preview, get_setting, get_system_theme, handle_command,
-    hide_window, is_cosmic_data_control_enabled, is_wayland_session, parse_command_from_args,
-    parse_env_content, read_clipboard, read_clipboard_image, reinitialize_clipboard, set_setting,
-    show_window, show_window_at_cursor, toggle_window, write_clipboard, write_clipboard_image,
+    clear_link_preview_cache, convert_color_item, convert_date_item, copy_image_as_data_uri,
+    copy_image_text, detect_color_content, detect_date_content, detect_env_content,
+    detect_structured_content, edit_image_item, export_image_item, export_palette,
+    extract_image_palette, fetch_link_preview, get_setting, get_system_theme, handle_command,
+    hide_window, is_cosmic_data_control_enabled, is_wayland_session, lan_confirm_pairing,
+    lan_get_peers, lan_pair, lan_share_item, lan_start, lan_start_pairing, lan_status, lan_stop,
+    lan_unpair, parse_command_arg_from_args, parse_command_from_args, parse_env_content,
+    paste_register, read_clipboard, read_clipboard_image, reinitialize_clipboard, search_items,
+    search_items_fuzzy, set_setting, show_window, show_window_at_cursor, store_register, sync_now,
+    toggle_window, transform_item, transform_structured_item, write_clipboard,
+    write_clipboard_image,
 };
 use db::Database;
+use lan::LanState;
 use tauri::Manager;
 use window_state::set_visible as window_set_visible;
 
 fn main() {
     let args: Vec<String> = std::env::args().collect();
     let initial_command = parse_command_from_args(&args).to_string();
+    let initial_arg = parse_command_arg_from_args(&args).map(str::to_string);
 
     tauri::Builder::default()
         .plugin(tauri_plugin_opener::init())
         .plugin(tauri_plugin_single_instance::init(|app, args, _cwd| {
             let command = parse_command_from_args(&args);
-            handle_command(app, command);
+            handle_command(app, command, parse_command_arg_from_args(&args));
         }))
         .manage(ClipboardManager::new())
+        .manage(LanState::new())
         .setup(move |app| {
             // Initialize database in app data directory
             let app_data_dir = app
@@ -44,10 +81,21 @@ fn main() {
             let db_path = app_data_dir.join("clipboard.db");
             let database =
                 Database::new(db_path.to_str().unwrap()).expect("failed to initialize database");
+            let lan_enabled = database.get_setting(lan::ENABLED_SETTING).ok().flatten();
             app.manage(database);
+            let handle = app.handle().clone();
+            tauri::async_runtime::spawn_blocking(move || {
+                let _ = handle.state::<Database>().backfill_perceptual_hashes();
+            });
+            if lan_enabled.as_deref() == Some("true") {
+                let handle = app.handle().clone();
+                tauri::async_runtime::spawn(async move {
+                    let _ = lan::start(&handle).await;
+                });
+            }
 
             tray::setup(app)?;
-            setup_main_window(app, &initial_command);
+            setup_main_window(app, &initial_command, initial_arg.as_deref());
             Ok(())
         })
         .invoke_handler(tauri::generate_handler![
@@ -64,29 +112,87 @@ fn main() {
             is_cosmic_data_control_enabled,
             get_system_theme,
             db_get_all_items,
-            db_insert_item,
+            db_get_items_after,
+            db_get_item_summaries,
+            db_get_item,
+            db_top_sort_order,
+            db_record_capture,
             db_bump_item,
             db_delete_item,
             db_clear_all,
+            db_clear_history,
+            db_get_trash,
+            db_restore_items,
+            db_empty_trash,
+            db_split_item,
+            db_undo,
+            db_redo,
+            db_get_undo_state,
             db_toggle_favorite,
             db_update_sort_orders,
+            db_batch_delete,
+            db_batch_set_favorite,
+            db_batch_add_tag,
+            db_batch_remove_tag,
+            db_batch_merge,
+            db_batch_copy,
+            db_batch_export,
+            db_get_item_tags,
+            db_get_tags,
             db_get_item_count,
+            db_record_copy_event,
+            db_get_copy_timeline,
+            db_get_usage_stats,
             db_dedup_item,
+            search_items,
+            search_items_fuzzy,
+            db_find_similar_images,
+            db_create_palette,
+            db_add_palette_items,
+            db_get_palettes,
+            db_delete_palette,
             detect_env_content,
             parse_env_content,
             detect_date_content,
             detect_color_content,
+            detect_structured_content,
+            transform_structured_item,
+            transform_item,
+            convert_color_item,
+            convert_date_item,
+            extract_image_palette,
+            copy_image_text,
+            edit_image_item,
+            export_image_item,
+            copy_image_as_data_uri,
+            export_palette,
             fetch_link_preview,
+            clear_link_preview_cache,
             get_setting,
             set_setting,
+            sync_now,
+            lan_start,
+            lan_stop,
+            lan_status,
+            lan_get_peers,
+            lan_start_pairing,
+            lan_pair,
+            lan_confirm_pairing,
+            lan_unpair,
+            lan_share_item,
+            store_register,
+            paste_register,
+            db_get_registers,
+            db_set_register,
+            db_clear_register,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
 }
 
-fn setup_main_window(app: &tauri::App, initial_command: &str) {
+fn setup_main_window(app: &tauri::App, initial_command: &str, initial_arg: Option<&str>) {
     if let Some(window) = app.get_webview_window("main") {
-        handle_command(app.handle(), initial_command);
+        handle_command(app.handle(), initial_command, initial_arg);
 
         let window_clone = window.clone();
         window.on_window_event(move |event| match event {
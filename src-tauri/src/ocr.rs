@@ -0,0 +1,67 @@
+//! OCR for image items via the `tesseract` command-line tool, so builds don't
+//! need the Tesseract/Leptonica development libraries.
+
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+/// Settings key: "false" disables OCR of new image items
+pub const OCR_ENABLED_SETTING: &str = "ocr_enabled";
+/// Settings key: Tesseract language codes, e.g. "eng" or "eng+deu"
+pub const OCR_LANGUAGES_SETTING: &str = "ocr_languages";
+
+const DEFAULT_LANGUAGES: &str = "eng";
+
+/// Recognizes text in a PNG. Returns an empty string when no text was found.
+pub fn recognize(png_bytes: &[u8], languages: Option<&str>) -> Result<String, String> {
+    let languages = languages
+        .map(str::trim)
+        .filter(|l| !l.is_empty())
+        .unwrap_or(DEFAULT_LANGUAGES);
+
+    let mut child = Command::new("tesseract")
+        .args(["stdin", "stdout", "-l", languages])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| {
+            format!(
+                "Failed to execute tesseract (is tesseract-ocr installed?): {}",
+                e
+            )
+        })?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin
+            .write_all(png_bytes)
+            .map_err(|e| format!("Failed to write to tesseract stdin: {}", e))?;
+    }
+
+    let output = child
+        .wait_with_output()
+        .map_err(|e| format!("Failed to wait for tesseract: {}", e))?;
+
+    if output.status.success() {
+        Ok(clean_output(&String::from_utf8_lossy(&output.stdout)))
+    } else {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        Err(format!("tesseract failed: {}", stderr.trim()))
+    }
+}
+
+/// Drops the trailing form feed Tesseract emits per page, trailing spaces,
+/// and runs of blank lines.
+fn clean_output(text: &str) -> String {
+    let mut lines: Vec<&str> = Vec::new();
+    for line in text.split('\n') {
+        let line = line.trim_end_matches(['\u{c}', ' ', '\t', '\r']);
+        if line.is_empty() && lines.last().is_none_or(|l| l.is_empty()) {
+            continue;
+        }
+        lines.push(line);
+    }
+    while lines.last().is_some_and(|l| l.is_empty()) {
+        lines.pop();
+    }
+    lines.join("\n")
+}
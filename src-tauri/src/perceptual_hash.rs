@@ -0,0 +1,57 @@
+//! Perceptual hashes for image items. Re-capturing the same screen region
+//! rarely produces byte-identical PNGs, so exact `content_hash` dedup misses
+//! them; a difference hash (dHash) of the pixels stays the same or close.
+
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use image::{imageops, GrayImage, Luma, RgbaImage};
+
+use crate::image_ops::decode_to_rgba;
+
+/// Settings key: maximum Hamming distance at which a new image is merged into
+/// an existing one. Unset or empty disables merging.
+pub const MERGE_DISTANCE_SETTING: &str = "image_merge_distance";
+
+/// Default cut-off for "similar images", out of 64 bits
+pub const DEFAULT_SIMILAR_DISTANCE: u32 = 10;
+
+/// Hashes a base64-encoded image as 16 hex digits, or `None` if it can't be decoded.
+pub fn hash_base64_image(data: &str) -> Option<String> {
+    let bytes = BASE64.decode(data).ok()?;
+    let (rgba, width, height) = decode_to_rgba(&bytes).ok()?;
+    let image = RgbaImage::from_raw(width, height, rgba)?;
+    Some(format!("{:016x}", dhash(&image)))
+}
+
+/// 64-bit dHash: shrink to 9x8 grayscale and set a bit wherever a pixel is
+/// brighter than its right-hand neighbour.
+pub fn dhash(image: &RgbaImage) -> u64 {
+    let small = imageops::resize(&grayscale(image), 9, 8, imageops::FilterType::Triangle);
+
+    let mut hash = 0u64;
+    for y in 0..8 {
+        for x in 0..8 {
+            let left = small.get_pixel(x, y).0[0];
+            let right = small.get_pixel(x + 1, y).0[0];
+            hash = (hash << 1) | u64::from(left > right);
+        }
+    }
+    hash
+}
+
+/// Luma with transparency composited over white, so an RGBA screenshot and
+/// the same image flattened to RGB hash alike.
+fn grayscale(image: &RgbaImage) -> GrayImage {
+    GrayImage::from_fn(image.width(), image.height(), |x, y| {
+        let [r, g, b, a] = image.get_pixel(x, y).0;
+        let alpha = f32::from(a) / 255.0;
+        let luma = 0.299 * f32::from(r) + 0.587 * f32::from(g) + 0.114 * f32::from(b);
+        Luma([(luma * alpha + 255.0 * (1.0 - alpha)).round() as u8])
+    })
+}
+
+/// Number of differing bits between two hex hashes.
+pub fn distance(a: &str, b: &str) -> Option<u32> {
+    let a = u64::from_str_radix(a, 16).ok()?;
+    let b = u64::from_str_radix(b, 16).ok()?;
+    Some((a ^ b).count_ones())
+}
@@ -0,0 +1,73 @@
+//! Named registers, like vim's: slots `a`–`z` and `1`–`9` that each hold
+//! their own copy of some clipboard content, apart from the history. Besides
+//! the commands, storing and pasting are command line verbs so they can be
+//! bound to hotkeys, e.g. `mexdeclip paste-register a`.
+
+use tauri::{AppHandle, Manager};
+
+use crate::clipboard::ClipboardManager;
+use crate::db::{now_millis, Database, RegisterRow};
+
+pub const STORE_COMMAND: &str = "store-register";
+pub const PASTE_COMMAND: &str = "paste-register";
+
+/// Checks a register name, folding upper case to lower case.
+pub fn parse_name(name: &str) -> Result<String, String> {
+    let mut chars = name.trim().chars();
+    match (chars.next(), chars.next()) {
+        (Some(c @ ('a'..='z' | 'A'..='Z' | '1'..='9')), None) => {
+            Ok(c.to_ascii_lowercase().to_string())
+        }
+        _ => Err(format!("Invalid register \"{}\", use a-z or 1-9", name)),
+    }
+}
+
+/// Stores what is on the clipboard now in register `name`. Images win over
+/// text, as when capturing.
+pub async fn store(app: &AppHandle, name: &str) -> Result<RegisterRow, String> {
+    let name = parse_name(name)?;
+    let manager = app.state::<ClipboardManager>();
+
+    let mut row = RegisterRow {
+        name,
+        content_type: "text".to_string(),
+        text_content: None,
+        image_data: None,
+        image_width: None,
+        image_height: None,
+        updated_at: now_millis().to_string(),
+    };
+    match manager.read_image().await.ok().flatten() {
+        Some((image_data, width, height)) => {
+            row.content_type = "image".to_string();
+            row.image_data = Some(image_data);
+            row.image_width = Some(width as i64);
+            row.image_height = Some(height as i64);
+        }
+        None => {
+            let text = manager.read().await?;
+            if text.is_empty() {
+                return Err("Nothing is copied".to_string());
+            }
+            row.text_content = Some(text);
+        }
+    }
+
+    app.state::<Database>().set_register(&row)?;
+    Ok(row)
+}
+
+/// Puts the content of register `name` on the clipboard.
+pub async fn paste(app: &AppHandle, name: &str) -> Result<(), String> {
+    let name = parse_name(name)?;
+    let register = app
+        .state::<Database>()
+        .get_register(&name)?
+        .ok_or_else(|| format!("Register {} is empty", name))?;
+
+    let manager = app.state::<ClipboardManager>();
+    match (register.content_type.as_str(), register.image_data) {
+        ("image", Some(image_data)) => manager.write_image(image_data).await,
+        _ => manager.write(register.text_content.unwrap_or_default()).await,
+    }
+}
@@ -13,6 +13,8 @@ pub struct ClipboardItems {
     pub char_count: Option<i64>,
     pub line_count: Option<i64>,
     pub source_app: Option<String>,
+    /// Name of the paired LAN device the item was received from
+    pub source_device: Option<String>,
     pub is_favorite: i64,
     /// Fractional index string for drag-and-drop ordering
     pub sort_order: String,
@@ -25,6 +27,16 @@ pub struct ClipboardItems {
     pub detected_color: Option<String>,
     /// SHA-256 hash of content for fast deduplication
     pub content_hash: Option<String>,
+    /// Structured kind from `classify` ("url", "json", "code", ...)
+    pub content_kind: Option<String>,
+    /// Detected language when `content_kind` is "code"
+    pub code_language: Option<String>,
+    /// Text recognized in image items by OCR
+    pub ocr_text: Option<String>,
+    /// dHash of image items as 16 hex digits, for near-duplicate detection
+    pub perceptual_hash: Option<String>,
+    /// Epoch milliseconds when moved to the trash, `None` while in history
+    pub deleted_at: Option<String>,
     pub created_at: String,
     pub updated_at: String,
 }
@@ -36,8 +48,119 @@ pub struct Settings {
     pub value: String,
 }
 
+#[SQLiteTable]
+pub struct Palettes {
+    #[column(primary, autoincrement)]
+    pub id: i64,
+    pub name: String,
+    pub created_at: String,
+}
+
+/// Membership of color items in a palette
+#[SQLiteTable]
+pub struct PaletteItems {
+    #[column(primary, autoincrement)]
+    pub id: i64,
+    pub palette_id: i64,
+    pub item_id: i64,
+    pub position: i64,
+}
+
+/// Cached link preview metadata, keyed by normalized URL
+#[SQLiteTable]
+pub struct LinkPreviews {
+    #[column(primary)]
+    pub url: String,
+    pub title: Option<String>,
+    pub description: Option<String>,
+    /// Remote og:image URL
+    pub image: Option<String>,
+    /// Remote favicon URL
+    pub favicon: Option<String>,
+    pub site_name: Option<String>,
+    /// Local copy of the og:image under the app data dir
+    pub image_path: Option<String>,
+    /// Local copy of the favicon under the app data dir
+    pub favicon_path: Option<String>,
+    pub fetched_at: String,
+}
+
+/// One capture, restore or paste of an item
+#[SQLiteTable]
+pub struct CopyEvents {
+    #[column(primary, autoincrement)]
+    pub id: i64,
+    pub item_id: i64,
+    /// "captured", "restored" or "pasted"
+    pub kind: String,
+    pub source_app: Option<String>,
+    /// Selection the content went through, e.g. "clipboard" or "primary"
+    pub selection: Option<String>,
+    pub created_at: String,
+}
+
+/// Free-form label on an item, unique per item
+#[SQLiteTable]
+pub struct ItemTags {
+    #[column(primary, autoincrement)]
+    pub id: i64,
+    pub item_id: i64,
+    pub tag: String,
+}
+
+/// Last synced state of an item, keyed by content. Rows outlive the item so
+/// deletions can be passed on as tombstones.
+#[SQLiteTable]
+pub struct SyncState {
+    #[column(primary)]
+    pub content_hash: String,
+    pub is_favorite: i64,
+    pub sort_order: String,
+    /// 1 once the item was trashed or deleted
+    pub deleted: i64,
+    /// Epoch milliseconds of the last change, for last-writer-wins
+    pub modified_at: i64,
+    /// Device that made the last change, breaks `modified_at` ties
+    pub device_id: String,
+}
+
+/// Device paired for LAN sharing, identified by its Noise static key
+#[SQLiteTable]
+pub struct LanPeers {
+    /// Hex-encoded X25519 public key
+    #[column(primary)]
+    pub public_key: String,
+    pub name: String,
+    /// Last known `ip:port` of its listener
+    pub address: Option<String>,
+    pub paired_at: String,
+}
+
+/// Named register holding its own copy of some clipboard content
+#[SQLiteTable]
+pub struct Registers {
+    /// `a`–`z` or `1`–`9`
+    #[column(primary)]
+    pub name: String,
+    /// "text" or "image"
+    pub content_type: String,
+    pub text_content: Option<String>,
+    pub image_data: Option<String>,
+    pub image_width: Option<i64>,
+    pub image_height: Option<i64>,
+    pub updated_at: String,
+}
+
 #[derive(SQLiteSchema)]
 pub struct Schema {
     pub clipboard_items: ClipboardItems,
     pub settings: Settings,
+    pub palettes: Palettes,
+    pub palette_items: PaletteItems,
+    pub link_previews: LinkPreviews,
+    pub copy_events: CopyEvents,
+    pub item_tags: ItemTags,
+    pub sync_state: SyncState,
+    pub lan_peers: LanPeers,
+    pub registers: Registers,
 }
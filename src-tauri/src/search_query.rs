@@ -0,0 +1,416 @@
+//! Filter query language for history search, e.g.
+//! `type:image app:firefox fav:yes after:2026-09-01 color:* len>200 "exact phrase"`.
+//! Queries are parsed into terms and compiled to a WHERE clause whose values
+//! are all bound as parameters.
+
+use rusqlite::types::Value;
+
+use crate::classify;
+use crate::date::{self, DateOptions};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Comparison {
+    Eq,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl Comparison {
+    fn sql(self) -> &'static str {
+        match self {
+            Self::Eq => "=",
+            Self::Lt => "<",
+            Self::Le => "<=",
+            Self::Gt => ">",
+            Self::Ge => ">=",
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Filter {
+    /// Bare word or quoted phrase, matched against text and OCR text
+    Text(String),
+    /// `type:text|image`
+    Type(String),
+    /// `kind:url|json|code|...`
+    Kind(String),
+    /// `app:` substring of the source app
+    App(String),
+    /// `tag:` exact tag, ignoring case
+    Tag(String),
+    /// `fav:yes|no`
+    Favorite(bool),
+    /// `after:DATE`, from the start of that day
+    After(String),
+    /// `before:DATE`, up to the start of that day
+    Before(String),
+    /// `color:*` for any detected color, otherwise a substring of its hex form.
+    /// Values that aren't hex digits, like `red` or `rgb(0,128,255)`, are
+    /// converted to hex first.
+    Color(Option<String>),
+    /// `date:*` for any detected date, otherwise a prefix of the ISO date
+    Date(Option<String>),
+    /// `len>200`, `len<=10`, `len:42`
+    Length(Comparison, i64),
+}
+
+/// A filter, negated by a leading `-`.
+#[derive(Debug, PartialEq)]
+pub struct Term {
+    pub negated: bool,
+    pub filter: Filter,
+}
+
+pub fn parse(input: &str) -> Result<Vec<Term>, String> {
+    tokenize(input)?
+        .into_iter()
+        .map(|(token, quoted)| parse_term(&token, quoted))
+        .collect()
+}
+
+/// Splits on whitespace, keeping double-quoted runs together. Returns each
+/// token and whether it was entirely a quoted phrase.
+fn tokenize(input: &str) -> Result<Vec<(String, bool)>, String> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    let mut in_quotes = false;
+    let mut quoted = false;
+
+    for c in input.chars() {
+        match c {
+            '"' => {
+                if !in_quotes && current.is_empty() {
+                    quoted = true;
+                } else if !in_quotes {
+                    // `key:"value with spaces"` is a filter, not a phrase
+                    quoted = false;
+                }
+                in_quotes = !in_quotes;
+            }
+            c if c.is_whitespace() && !in_quotes => {
+                if !current.is_empty() || quoted {
+                    tokens.push((std::mem::take(&mut current), quoted));
+                }
+                quoted = false;
+            }
+            c => current.push(c),
+        }
+    }
+    if in_quotes {
+        return Err("Unterminated quote in search query".to_string());
+    }
+    if !current.is_empty() {
+        tokens.push((current, quoted));
+    }
+
+    Ok(tokens.into_iter().filter(|(t, _)| !t.is_empty()).collect())
+}
+
+fn parse_term(token: &str, quoted: bool) -> Result<Term, String> {
+    if quoted {
+        return Ok(Term {
+            negated: false,
+            filter: Filter::Text(token.to_string()),
+        });
+    }
+
+    let (negated, body) = match token.strip_prefix('-') {
+        Some(rest) if !rest.is_empty() => (true, rest),
+        _ => (false, token),
+    };
+    let filter = parse_filter(body)?.unwrap_or_else(|| Filter::Text(body.to_string()));
+    Ok(Term { negated, filter })
+}
+
+/// `None` when the token isn't a known filter, e.g. a URL like `https://...`.
+fn parse_filter(token: &str) -> Result<Option<Filter>, String> {
+    if let Some(rest) = token.strip_prefix("len") {
+        if let Some((comparison, value)) = parse_comparison(rest) {
+            let length = value
+                .parse()
+                .map_err(|_| format!("Invalid length '{}' in '{}'", value, token))?;
+            return Ok(Some(Filter::Length(comparison, length)));
+        }
+    }
+
+    let Some((key, value)) = token.split_once(':') else {
+        return Ok(None);
+    };
+    if value.is_empty() {
+        return Ok(None);
+    }
+
+    let filter = match key.to_ascii_lowercase().as_str() {
+        "type" => match value.to_ascii_lowercase().as_str() {
+            t @ ("text" | "image") => Filter::Type(t.to_string()),
+            _ => return Err(format!("Unknown type '{}', expected text or image", value)),
+        },
+        "kind" => Filter::Kind(value.to_ascii_lowercase()),
+        "app" => Filter::App(value.to_string()),
+        "tag" => Filter::Tag(value.to_string()),
+        "fav" | "favorite" => Filter::Favorite(
+            parse_bool(value)
+                .ok_or_else(|| format!("Invalid value '{}' for fav:, expected yes or no", value))?,
+        ),
+        "after" => Filter::After(value.to_string()),
+        "before" => Filter::Before(value.to_string()),
+        "color" => Filter::Color(wildcard(value).map(hex_color).transpose()?),
+        "date" => Filter::Date(wildcard(value)),
+        _ => return Ok(None),
+    };
+    Ok(Some(filter))
+}
+
+/// Normalizes a `color:` value to the lowercase hex that `detected_color` holds.
+/// Hex fragments like `#ff` or `00ff` are kept as-is for substring matching.
+fn hex_color(value: String) -> Result<String, String> {
+    let digits = value.strip_prefix('#').unwrap_or(&value);
+    if !digits.is_empty() && digits.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Ok(value.to_ascii_lowercase());
+    }
+    classify::detect_color(&value).ok_or_else(|| format!("Invalid color '{}'", value))
+}
+
+fn parse_comparison(rest: &str) -> Option<(Comparison, &str)> {
+    [
+        (">=", Comparison::Ge),
+        ("<=", Comparison::Le),
+        (">", Comparison::Gt),
+        ("<", Comparison::Lt),
+        ("=", Comparison::Eq),
+        (":", Comparison::Eq),
+    ]
+    .into_iter()
+    .find_map(|(op, comparison)| rest.strip_prefix(op).map(|v| (comparison, v)))
+}
+
+fn parse_bool(value: &str) -> Option<bool> {
+    match value.to_ascii_lowercase().as_str() {
+        "yes" | "y" | "true" | "1" => Some(true),
+        "no" | "n" | "false" | "0" => Some(false),
+        _ => None,
+    }
+}
+
+fn wildcard(value: &str) -> Option<String> {
+    (value != "*").then(|| value.to_string())
+}
+
+/// Compiles terms to a WHERE clause (without the keyword) and its parameters,
+/// in order for anonymous `?` placeholders. No terms compile to `1`.
+pub fn compile(terms: &[Term], options: &DateOptions) -> Result<(String, Vec<Value>), String> {
+    let mut clauses = Vec::new();
+    let mut params = Vec::new();
+
+    for term in terms {
+        let clause = compile_filter(&term.filter, options, &mut params)?;
+        // A NULL column makes the clause NULL, which NOT would leave NULL
+        clauses.push(if term.negated {
+            format!("NOT COALESCE(({}), 0)", clause)
+        } else {
+            clause
+        });
+    }
+
+    if clauses.is_empty() {
+        return Ok(("1".to_string(), params));
+    }
+    Ok((clauses.join(" AND "), params))
+}
+
+fn compile_filter(
+    filter: &Filter,
+    options: &DateOptions,
+    params: &mut Vec<Value>,
+) -> Result<String, String> {
+    let clause = match filter {
+        // The trigram index only knows substrings of three or more characters
+        Filter::Text(text) if text.chars().count() >= 3 => {
+            params.push(Value::Text(fts_phrase(text)));
+            "id IN (SELECT rowid FROM clipboard_items_fts WHERE clipboard_items_fts MATCH ?)"
+                .to_string()
+        }
+        Filter::Text(text) => {
+            let pattern = like_contains(text);
+            params.push(Value::Text(pattern.clone()));
+            params.push(Value::Text(pattern));
+            "(text_content LIKE ? ESCAPE '\\' OR ocr_text LIKE ? ESCAPE '\\')".to_string()
+        }
+        Filter::Type(content_type) => {
+            params.push(Value::Text(content_type.clone()));
+            "content_type = ?".to_string()
+        }
+        Filter::Kind(kind) => {
+            params.push(Value::Text(kind.clone()));
+            "content_kind = ?".to_string()
+        }
+        Filter::App(app) => {
+            params.push(Value::Text(like_contains(app)));
+            "source_app LIKE ? ESCAPE '\\'".to_string()
+        }
+        Filter::Tag(tag) => {
+            params.push(Value::Text(tag.clone()));
+            "id IN (SELECT item_id FROM item_tags WHERE tag = ? COLLATE NOCASE)".to_string()
+        }
+        Filter::Favorite(favorite) => {
+            params.push(Value::Integer(*favorite as i64));
+            "is_favorite = ?".to_string()
+        }
+        Filter::After(value) => {
+            params.push(Value::Integer(date_millis(value, options)?));
+            "CAST(created_at AS INTEGER) >= ?".to_string()
+        }
+        Filter::Before(value) => {
+            params.push(Value::Integer(date_millis(value, options)?));
+            "CAST(created_at AS INTEGER) < ?".to_string()
+        }
+        Filter::Color(None) => "detected_color IS NOT NULL".to_string(),
+        Filter::Color(Some(color)) => {
+            params.push(Value::Text(like_contains(color)));
+            "detected_color LIKE ? ESCAPE '\\'".to_string()
+        }
+        Filter::Date(None) => "detected_date IS NOT NULL".to_string(),
+        Filter::Date(Some(prefix)) => {
+            params.push(Value::Text(format!("{}%", escape_like(prefix))));
+            "detected_date LIKE ? ESCAPE '\\'".to_string()
+        }
+        Filter::Length(comparison, length) => {
+            params.push(Value::Integer(*length));
+            format!("char_count {} ?", comparison.sql())
+        }
+    };
+    Ok(clause)
+}
+
+/// Dates without a time are the start of that day in the local timezone.
+fn date_millis(value: &str, options: &DateOptions) -> Result<i64, String> {
+    date::parse(value, options)
+        .map(|dt| dt.timestamp_millis())
+        .ok_or_else(|| format!("Invalid date '{}'", value))
+}
+
+fn escape_like(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
+
+/// Quotes text as an FTS5 phrase, so it matches as one substring rather than
+/// being read as query syntax.
+fn fts_phrase(value: &str) -> String {
+    format!("\"{}\"", value.replace('"', "\"\""))
+}
+
+fn like_contains(value: &str) -> String {
+    format!("%{}%", escape_like(value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OPTIONS: DateOptions = DateOptions { day_first: false };
+
+    fn term(filter: Filter) -> Term {
+        Term {
+            negated: false,
+            filter,
+        }
+    }
+
+    #[test]
+    fn tokenizes_filters_phrases_and_negation() {
+        let terms =
+            parse(r#"type:IMAGE -app:"Google Chrome" "exact phrase" len>=200 https://a.b/c"#)
+                .unwrap();
+        assert_eq!(
+            terms,
+            vec![
+                term(Filter::Type("image".to_string())),
+                Term {
+                    negated: true,
+                    filter: Filter::App("Google Chrome".to_string()),
+                },
+                term(Filter::Text("exact phrase".to_string())),
+                term(Filter::Length(Comparison::Ge, 200)),
+                term(Filter::Text("https://a.b/c".to_string())),
+            ]
+        );
+        // A quoted phrase is never read as a filter
+        assert_eq!(
+            parse(r#""fav:yes""#).unwrap(),
+            vec![term(Filter::Text("fav:yes".to_string()))]
+        );
+        assert_eq!(
+            parse("color:* date:2026-03").unwrap(),
+            vec![
+                term(Filter::Color(None)),
+                term(Filter::Date(Some("2026-03".to_string()))),
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_queries() {
+        assert_eq!(
+            parse(r#"say "hello"#).unwrap_err(),
+            "Unterminated quote in search query"
+        );
+        assert!(parse("type:video").unwrap_err().starts_with("Unknown type"));
+        assert!(parse("fav:maybe").unwrap_err().starts_with("Invalid value"));
+        assert!(parse("len>lots").unwrap_err().starts_with("Invalid length"));
+        assert_eq!(parse("color:nope").unwrap_err(), "Invalid color 'nope'");
+        let terms = parse("after:someday").unwrap();
+        assert_eq!(
+            compile(&terms, &OPTIONS).unwrap_err(),
+            "Invalid date 'someday'"
+        );
+    }
+
+    #[test]
+    fn compiles_to_bound_parameters() {
+        let (clause, params) =
+            compile(&parse(r#"fav:yes -tag:work "it's" 5%"#).unwrap(), &OPTIONS).unwrap();
+        assert_eq!(
+            clause,
+            "is_favorite = ? \
+             AND NOT COALESCE((id IN (SELECT item_id FROM item_tags WHERE tag = ? COLLATE NOCASE)), 0) \
+             AND id IN (SELECT rowid FROM clipboard_items_fts WHERE clipboard_items_fts MATCH ?) \
+             AND (text_content LIKE ? ESCAPE '\\' OR ocr_text LIKE ? ESCAPE '\\')"
+        );
+        assert_eq!(
+            params,
+            vec![
+                Value::Integer(1),
+                Value::Text("work".to_string()),
+                Value::Text("\"it's\"".to_string()),
+                Value::Text("%5\\%%".to_string()),
+                Value::Text("%5\\%%".to_string()),
+            ]
+        );
+        assert_eq!(
+            compile(&[], &OPTIONS).unwrap(),
+            ("1".to_string(), Vec::new())
+        );
+    }
+
+    #[test]
+    fn colors_are_matched_by_hex() {
+        assert_eq!(
+            parse("color:#FF00 color:red").unwrap(),
+            vec![
+                term(Filter::Color(Some("#ff00".to_string()))),
+                term(Filter::Color(Some("#ff0000".to_string()))),
+            ]
+        );
+    }
+
+    #[test]
+    fn quotes_inside_fts_phrases_are_escaped() {
+        assert_eq!(fts_phrase(r#"say "hi" OR x"#), r#""say ""hi"" OR x""#);
+    }
+}
@@ -0,0 +1,100 @@
+//! Heuristics for text that looks like a credential: well-known token
+//! formats, private keys, JWTs and `API_KEY=...` style assignments.
+
+/// Token prefixes and the minimum number of token chars that follow
+const TOKEN_PREFIXES: &[(&str, usize)] = &[
+    // AWS access key ids, long-lived and temporary
+    ("AKIA", 16),
+    ("ASIA", 16),
+    // GitHub personal, OAuth, user, server and refresh tokens
+    ("ghp_", 36),
+    ("gho_", 36),
+    ("ghu_", 36),
+    ("ghs_", 36),
+    ("ghr_", 36),
+    ("github_pat_", 60),
+    ("glpat-", 20),
+    // Slack bot, user, app and legacy tokens
+    ("xoxb-", 20),
+    ("xoxp-", 20),
+    ("xoxa-", 20),
+    ("xoxs-", 20),
+    ("sk_live_", 20),
+    ("rk_live_", 20),
+    ("AIza", 35),
+    ("npm_", 36),
+    ("sk-", 32),
+];
+
+/// Words in a key name that mark its value as a credential
+const SECRET_KEY_WORDS: &[&str] = &[
+    "secret",
+    "token",
+    "password",
+    "passwd",
+    "api_key",
+    "apikey",
+    "private_key",
+    "access_key",
+];
+
+/// Shortest value of a `SECRET=value` assignment treated as a credential
+const MIN_ASSIGNED_LEN: usize = 8;
+
+pub fn is_secret(text: &str) -> bool {
+    let text = text.trim();
+    if text.contains("-----BEGIN") && text.contains("PRIVATE KEY-----") {
+        return true;
+    }
+    text.split_whitespace().any(is_secret_token) || text.lines().any(is_secret_assignment)
+}
+
+fn is_token_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_' || c == '-'
+}
+
+fn is_secret_token(word: &str) -> bool {
+    // Drop surrounding quotes and punctuation, e.g. `"ghp_...",`
+    let word = word.trim_matches(|c: char| !is_token_char(c) && c != '.');
+    let word = word.trim_end_matches('.');
+
+    let prefixed = TOKEN_PREFIXES.iter().any(|(prefix, min_len)| {
+        word.strip_prefix(prefix)
+            .is_some_and(|rest| rest.len() >= *min_len && rest.chars().all(is_token_char))
+    });
+    prefixed || is_jwt(word)
+}
+
+/// Three base64url segments, the first two being JSON objects (`eyJ` = `{"`).
+fn is_jwt(word: &str) -> bool {
+    let parts: Vec<&str> = word.split('.').collect();
+    parts.len() == 3
+        && parts[0].starts_with("eyJ")
+        && parts[1].starts_with("eyJ")
+        && parts
+            .iter()
+            .all(|part| !part.is_empty() && part.chars().all(is_token_char))
+}
+
+/// `KEY=value` or `key: value` where the key names a credential, as in env
+/// files, YAML and JSON.
+fn is_secret_assignment(line: &str) -> bool {
+    let Some((key, value)) = line.split_once(['=', ':']) else {
+        return false;
+    };
+    let key = key.trim();
+    let key = key
+        .strip_prefix("export ")
+        .unwrap_or(key)
+        .trim_matches(|c| c == '"' || c == '\'')
+        .to_ascii_lowercase();
+    let value = value
+        .trim()
+        .trim_end_matches(',')
+        .trim_matches(|c| c == '"' || c == '\'');
+
+    !key.contains(char::is_whitespace)
+        && SECRET_KEY_WORDS.iter().any(|word| key.contains(word))
+        && value.len() >= MIN_ASSIGNED_LEN
+        && !value.contains(char::is_whitespace)
+}
@@ -0,0 +1,204 @@
+//! Aggregates over clipboard history for the usage dashboard. Timestamps are
+//! stored as epoch milliseconds in text columns, so every query casts them.
+//! Items in the trash are left out.
+
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+
+/// Characters of text kept in `TopItem::preview`
+const PREVIEW_CHARS: usize = 120;
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Period {
+    Day,
+    Week,
+}
+
+impl Period {
+    /// `strftime` format for the bucket label, e.g. "2026-03-14" or the ISO week
+    /// "2026-W11", which keeps the days around New Year in one bucket
+    fn strftime_format(self) -> &'static str {
+        match self {
+            Self::Day => "%Y-%m-%d",
+            Self::Week => "%G-W%V",
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct UsageStats {
+    pub total_items: i64,
+    pub favorite_items: i64,
+    pub items_per_period: Vec<PeriodCount>,
+    pub top_reused: Vec<TopItem>,
+    pub type_breakdown: Vec<TypeCount>,
+    pub storage: StorageUsage,
+    pub source_apps: Vec<SourceAppCount>,
+    /// Mean milliseconds between re-copies of reused items, if any were reused
+    pub avg_time_to_reuse_ms: Option<f64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PeriodCount {
+    pub period: String,
+    pub count: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TopItem {
+    pub id: i64,
+    pub content_type: String,
+    pub content_kind: Option<String>,
+    pub preview: Option<String>,
+    pub copy_count: i64,
+    pub is_favorite: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TypeCount {
+    pub content_type: String,
+    pub content_kind: Option<String>,
+    pub count: i64,
+}
+
+/// Approximate payload sizes in bytes, excluding SQLite overhead
+#[derive(Debug, Serialize)]
+pub struct StorageUsage {
+    pub image_bytes: i64,
+    pub text_bytes: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SourceAppCount {
+    pub source_app: String,
+    pub items: i64,
+    pub copies: i64,
+}
+
+/// Collects the whole dashboard. `periods` limits the number of most recent
+/// buckets returned and `limit` the length of the ranked lists.
+pub fn collect(
+    conn: &Connection,
+    period: Period,
+    periods: i64,
+    limit: i64,
+) -> rusqlite::Result<UsageStats> {
+    let (total_items, favorite_items) = conn.query_row(
+        "SELECT COUNT(*), COALESCE(SUM(is_favorite != 0), 0) FROM clipboard_items WHERE deleted_at IS NULL",
+        [],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )?;
+
+    Ok(UsageStats {
+        total_items,
+        favorite_items,
+        items_per_period: items_per_period(conn, period, periods)?,
+        top_reused: top_reused(conn, limit)?,
+        type_breakdown: type_breakdown(conn)?,
+        storage: storage(conn)?,
+        source_apps: source_apps(conn, limit)?,
+        avg_time_to_reuse_ms: avg_time_to_reuse(conn)?,
+    })
+}
+
+/// Items created per local day or week, oldest first.
+fn items_per_period(
+    conn: &Connection,
+    period: Period,
+    periods: i64,
+) -> rusqlite::Result<Vec<PeriodCount>> {
+    let mut rows: Vec<PeriodCount> = conn
+        .prepare(
+            "SELECT strftime(?1, CAST(created_at AS INTEGER) / 1000, 'unixepoch', 'localtime') AS period, COUNT(*)
+             FROM clipboard_items WHERE deleted_at IS NULL
+             GROUP BY period ORDER BY period DESC LIMIT ?2",
+        )?
+        .query_map(rusqlite::params![period.strftime_format(), periods], |row| {
+            Ok(PeriodCount {
+                period: row.get(0)?,
+                count: row.get(1)?,
+            })
+        })?
+        .collect::<Result<_, _>>()?;
+    rows.reverse();
+    Ok(rows)
+}
+
+/// Items copied more than once, most reused first.
+fn top_reused(conn: &Connection, limit: i64) -> rusqlite::Result<Vec<TopItem>> {
+    conn.prepare(
+        "SELECT id, content_type, content_kind, substr(text_content, 1, ?2), copy_count, is_favorite
+         FROM clipboard_items WHERE copy_count > 1 AND deleted_at IS NULL
+         ORDER BY copy_count DESC, updated_at DESC LIMIT ?1",
+    )?
+    .query_map(rusqlite::params![limit, PREVIEW_CHARS as i64], |row| {
+        Ok(TopItem {
+            id: row.get(0)?,
+            content_type: row.get(1)?,
+            content_kind: row.get(2)?,
+            preview: row.get(3)?,
+            copy_count: row.get(4)?,
+            is_favorite: row.get::<_, i64>(5)? != 0,
+        })
+    })?
+    .collect()
+}
+
+fn type_breakdown(conn: &Connection) -> rusqlite::Result<Vec<TypeCount>> {
+    conn.prepare(
+        "SELECT content_type, content_kind, COUNT(*) AS count FROM clipboard_items
+         WHERE deleted_at IS NULL GROUP BY content_type, content_kind ORDER BY count DESC",
+    )?
+    .query_map([], |row| {
+        Ok(TypeCount {
+            content_type: row.get(0)?,
+            content_kind: row.get(1)?,
+            count: row.get(2)?,
+        })
+    })?
+    .collect()
+}
+
+/// Images are stored as base64, so their decoded size is 3/4 of the text.
+fn storage(conn: &Connection) -> rusqlite::Result<StorageUsage> {
+    conn.query_row(
+        "SELECT COALESCE(SUM(LENGTH(image_data)), 0) * 3 / 4,
+                COALESCE(SUM(LENGTH(CAST(text_content AS BLOB))), 0)
+         FROM clipboard_items WHERE deleted_at IS NULL",
+        [],
+        |row| {
+            Ok(StorageUsage {
+                image_bytes: row.get(0)?,
+                text_bytes: row.get(1)?,
+            })
+        },
+    )
+}
+
+fn source_apps(conn: &Connection, limit: i64) -> rusqlite::Result<Vec<SourceAppCount>> {
+    conn.prepare(
+        "SELECT source_app, COUNT(*) AS items, SUM(copy_count) AS copies FROM clipboard_items
+         WHERE source_app IS NOT NULL AND source_app != '' AND deleted_at IS NULL
+         GROUP BY source_app ORDER BY copies DESC, items DESC LIMIT ?1",
+    )?
+    .query_map(rusqlite::params![limit], |row| {
+        Ok(SourceAppCount {
+            source_app: row.get(0)?,
+            items: row.get(1)?,
+            copies: row.get(2)?,
+        })
+    })?
+    .collect()
+}
+
+/// For each reused item, the span from first capture to last re-copy divided
+/// by the number of re-copies, averaged over items.
+fn avg_time_to_reuse(conn: &Connection) -> rusqlite::Result<Option<f64>> {
+    conn.query_row(
+        "SELECT AVG((CAST(updated_at AS INTEGER) - CAST(created_at AS INTEGER)) * 1.0 / (copy_count - 1))
+         FROM clipboard_items WHERE copy_count > 1 AND deleted_at IS NULL",
+        [],
+        |row| row.get(0),
+    )
+}
@@ -0,0 +1,312 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DataFormat {
+    Json,
+    Yaml,
+    Toml,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum StructuredOp {
+    Pretty,
+    Minify,
+    Convert {
+        to: DataFormat,
+    },
+    /// Extract a value with a JSONPath subset: `$`, `.key`, `['key']`, `[0]`, `[-1]`, `[*]`, `.*`
+    ExtractPath {
+        path: String,
+    },
+}
+
+/// Detects JSON, YAML or TOML documents. Only objects/arrays/tables count, since
+/// almost any line of prose is a valid YAML scalar.
+pub fn detect_format(text: &str) -> Option<DataFormat> {
+    let trimmed = text.trim();
+    if trimmed.is_empty() {
+        return None;
+    }
+
+    if (trimmed.starts_with('{') || trimmed.starts_with('['))
+        && serde_json::from_str::<Value>(trimmed).is_ok_and(|v| v.is_object() || v.is_array())
+    {
+        return Some(DataFormat::Json);
+    }
+
+    if looks_like_toml(trimmed) && toml::from_str::<toml::Table>(trimmed).is_ok() {
+        return Some(DataFormat::Toml);
+    }
+
+    // Require a document marker or several lines so "Note: hi" stays plain text
+    let multi_line = trimmed.lines().filter(|l| !l.trim().is_empty()).count() > 1;
+    if (multi_line || trimmed.starts_with("---"))
+        && serde_norway::from_str::<Value>(trimmed)
+            .is_ok_and(|v| v.as_object().is_some_and(|o| !o.is_empty()) || v.is_array())
+    {
+        return Some(DataFormat::Yaml);
+    }
+
+    None
+}
+
+fn looks_like_toml(text: &str) -> bool {
+    // A table header or a `key = value` line with spaces around `=`, which
+    // keeps single-line env content (`KEY=value`) out of this bucket
+    text.lines().map(str::trim).any(|line| {
+        (line.starts_with('[') && line.ends_with(']') && !line.contains(','))
+            || line.contains(" = ")
+    })
+}
+
+pub fn parse(text: &str, format: DataFormat) -> Result<Value, String> {
+    match format {
+        DataFormat::Json => serde_json::from_str(text).map_err(|e| format!("Invalid JSON: {}", e)),
+        DataFormat::Yaml => serde_norway::from_str(text).map_err(|e| format!("Invalid YAML: {}", e)),
+        DataFormat::Toml => {
+            let table: toml::Table =
+                toml::from_str(text).map_err(|e| format!("Invalid TOML: {}", e))?;
+            Ok(toml_to_json(toml::Value::Table(table)))
+        }
+    }
+}
+
+/// Serializing a TOML datetime with serde wraps it in a private marker
+/// object, so datetimes are mapped to their TOML string form here instead.
+fn toml_to_json(value: toml::Value) -> Value {
+    match value {
+        toml::Value::String(s) => Value::String(s),
+        toml::Value::Integer(i) => Value::from(i),
+        toml::Value::Float(f) => Value::from(f),
+        toml::Value::Boolean(b) => Value::Bool(b),
+        toml::Value::Datetime(d) => Value::String(d.to_string()),
+        toml::Value::Array(items) => Value::Array(items.into_iter().map(toml_to_json).collect()),
+        toml::Value::Table(table) => Value::Object(
+            table
+                .into_iter()
+                .map(|(key, value)| (key, toml_to_json(value)))
+                .collect(),
+        ),
+    }
+}
+
+pub fn serialize(value: &Value, format: DataFormat) -> Result<String, String> {
+    match format {
+        DataFormat::Json => serde_json::to_string_pretty(value).map_err(|e| e.to_string()),
+        DataFormat::Yaml => serde_norway::to_string(value).map_err(|e| e.to_string()),
+        DataFormat::Toml => {
+            if !value.is_object() {
+                return Err("TOML documents must be a table at the top level".to_string());
+            }
+            toml::to_string_pretty(value).map_err(|e| format!("Cannot convert to TOML: {}", e))
+        }
+    }
+}
+
+/// Applies a structured-data operation. `from` overrides format detection.
+pub fn transform(
+    text: &str,
+    op: &StructuredOp,
+    from: Option<DataFormat>,
+) -> Result<String, String> {
+    let format = from
+        .or_else(|| detect_format(text))
+        .ok_or("Content is not JSON, YAML or TOML")?;
+    let value = parse(text.trim(), format)?;
+
+    match op {
+        StructuredOp::Pretty => serialize(&value, format),
+        StructuredOp::Minify => match format {
+            DataFormat::Json => serde_json::to_string(&value).map_err(|e| e.to_string()),
+            _ => Err("Minify is only supported for JSON".to_string()),
+        },
+        StructuredOp::Convert { to } => serialize(&value, *to),
+        StructuredOp::ExtractPath { path } => {
+            let matches = select_path(&value, path)?;
+            let result = match matches.as_slice() {
+                [] => return Err(format!("No value at {}", path)),
+                [single] => (*single).clone(),
+                many => Value::Array(many.iter().map(|v| (*v).clone()).collect()),
+            };
+            match result {
+                // Copy bare strings without the surrounding quotes
+                Value::String(s) => Ok(s),
+                other => serde_json::to_string_pretty(&other).map_err(|e| e.to_string()),
+            }
+        }
+    }
+}
+
+enum PathSegment {
+    Key(String),
+    Index(i64),
+    Wildcard,
+}
+
+fn parse_path(path: &str) -> Result<Vec<PathSegment>, String> {
+    let invalid = || format!("Invalid JSONPath: {}", path);
+    let path = path.trim();
+    // Allow a bare leading key: `data.items[0]`
+    let normalized = match path.strip_prefix('$') {
+        Some(rest) => rest.to_string(),
+        None if path.starts_with(['.', '[']) => path.to_string(),
+        None => format!(".{}", path),
+    };
+    let mut rest = normalized.as_str();
+    let mut segments = Vec::new();
+
+    while !rest.is_empty() {
+        if let Some(after) = rest.strip_prefix('.') {
+            let end = after.find(['.', '[']).unwrap_or(after.len());
+            let key = &after[..end];
+            if key.is_empty() {
+                return Err(invalid());
+            }
+            segments.push(if key == "*" {
+                PathSegment::Wildcard
+            } else {
+                PathSegment::Key(key.to_string())
+            });
+            rest = &after[end..];
+        } else if let Some(after) = rest.strip_prefix('[') {
+            let end = after.find(']').ok_or_else(invalid)?;
+            let inner = after[..end].trim();
+            let quoted = inner
+                .strip_prefix('\'')
+                .and_then(|s| s.strip_suffix('\''))
+                .or_else(|| inner.strip_prefix('"').and_then(|s| s.strip_suffix('"')));
+            segments.push(match quoted {
+                Some(key) => PathSegment::Key(key.to_string()),
+                None if inner == "*" => PathSegment::Wildcard,
+                None => PathSegment::Index(inner.parse().map_err(|_| invalid())?),
+            });
+            rest = &after[end + 1..];
+        } else {
+            return Err(invalid());
+        }
+    }
+
+    Ok(segments)
+}
+
+fn select_path<'a>(root: &'a Value, path: &str) -> Result<Vec<&'a Value>, String> {
+    let mut current = vec![root];
+    for segment in parse_path(path)? {
+        current = current
+            .into_iter()
+            .flat_map(|value| -> Vec<&Value> {
+                match (&segment, value) {
+                    (PathSegment::Key(key), Value::Object(map)) => {
+                        map.get(key).into_iter().collect()
+                    }
+                    (PathSegment::Index(i), Value::Array(items)) => {
+                        let idx = if *i < 0 { items.len() as i64 + i } else { *i };
+                        usize::try_from(idx)
+                            .ok()
+                            .and_then(|i| items.get(i))
+                            .into_iter()
+                            .collect()
+                    }
+                    (PathSegment::Wildcard, Value::Object(map)) => map.values().collect(),
+                    (PathSegment::Wildcard, Value::Array(items)) => items.iter().collect(),
+                    _ => Vec::new(),
+                }
+            })
+            .collect();
+    }
+    Ok(current)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_documents_but_not_prose() {
+        assert_eq!(detect_format("{\"a\": 1}"), Some(DataFormat::Json));
+        assert_eq!(
+            detect_format("[package]\nname = \"x\""),
+            Some(DataFormat::Toml)
+        );
+        assert_eq!(detect_format("a: 1\nb: [2, 3]"), Some(DataFormat::Yaml));
+        assert_eq!(detect_format("Note: hi"), None);
+        assert_eq!(detect_format("KEY=value"), None);
+        assert_eq!(detect_format("\"just a string\""), None);
+    }
+
+    #[test]
+    fn converts_between_formats() {
+        let yaml = transform(
+            "{\"name\": \"app\", \"ports\": [80, 443]}",
+            &StructuredOp::Convert {
+                to: DataFormat::Yaml,
+            },
+            None,
+        )
+        .unwrap();
+        assert_eq!(yaml, "name: app\nports:\n- 80\n- 443\n");
+
+        // TOML datetimes come out as their TOML text
+        let json = transform(
+            "released = 2026-03-28T14:30:00Z",
+            &StructuredOp::Minify,
+            Some(DataFormat::Toml),
+        );
+        assert!(json.is_err(), "minify is JSON only");
+        let json = transform(
+            "released = 2026-03-28T14:30:00Z",
+            &StructuredOp::Convert {
+                to: DataFormat::Json,
+            },
+            Some(DataFormat::Toml),
+        )
+        .unwrap();
+        assert_eq!(json, "{\n  \"released\": \"2026-03-28T14:30:00Z\"\n}");
+    }
+
+    #[test]
+    fn extracts_paths() {
+        let doc = "{\"items\": [{\"id\": 1}, {\"id\": 2}], \"name\": \"app\"}";
+        let extract = |path: &str| {
+            transform(
+                doc,
+                &StructuredOp::ExtractPath {
+                    path: path.to_string(),
+                },
+                None,
+            )
+        };
+        assert_eq!(extract("$.name").unwrap(), "app");
+        assert_eq!(extract("items[-1].id").unwrap(), "2");
+        assert_eq!(extract("$.items[*].id").unwrap(), "[\n  1,\n  2\n]");
+        assert_eq!(extract("$['name']").unwrap(), "app");
+    }
+
+    #[test]
+    fn rejects_malformed_input() {
+        assert!(parse("{\"a\": ", DataFormat::Json)
+            .unwrap_err()
+            .starts_with("Invalid JSON"));
+        assert!(parse("a = ", DataFormat::Toml)
+            .unwrap_err()
+            .starts_with("Invalid TOML"));
+        assert!(transform("plain words", &StructuredOp::Pretty, None).is_err());
+        assert!(serialize(&serde_json::json!([1, 2]), DataFormat::Toml).is_err());
+
+        let doc = "{\"a\": 1}";
+        let extract = |path: &str| {
+            transform(
+                doc,
+                &StructuredOp::ExtractPath {
+                    path: path.to_string(),
+                },
+                None,
+            )
+        };
+        assert_eq!(extract("$.a[").unwrap_err(), "Invalid JSONPath: $.a[");
+        assert_eq!(extract("$.missing").unwrap_err(), "No value at $.missing");
+    }
+}
@@ -0,0 +1,94 @@
+//! Encryption of sync files with a key derived from the sync passphrase, so
+//! the shared folder or WebDAV server only ever sees ciphertext.
+//!
+//! Layout: `MAGIC | salt (16) | nonce (24) | XChaCha20-Poly1305 ciphertext`.
+//! The key is Argon2id over the passphrase with the file's salt.
+
+use argon2::Argon2;
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+
+const MAGIC: &[u8; 8] = b"CLPSYNC1";
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 24;
+const HEADER_LEN: usize = MAGIC.len() + SALT_LEN + NONCE_LEN;
+
+pub fn seal(plaintext: &[u8], passphrase: &str) -> Result<Vec<u8>, String> {
+    let mut salt = [0u8; SALT_LEN];
+    OsRng.fill_bytes(&mut salt);
+    let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+
+    let ciphertext = cipher(passphrase, &salt)?
+        .encrypt(
+            &nonce,
+            Payload {
+                msg: plaintext,
+                aad: MAGIC,
+            },
+        )
+        .map_err(|_| "Failed to encrypt sync data".to_string())?;
+
+    let mut sealed = Vec::with_capacity(HEADER_LEN + ciphertext.len());
+    sealed.extend_from_slice(MAGIC);
+    sealed.extend_from_slice(&salt);
+    sealed.extend_from_slice(&nonce);
+    sealed.extend_from_slice(&ciphertext);
+    Ok(sealed)
+}
+
+pub fn open(sealed: &[u8], passphrase: &str) -> Result<Vec<u8>, String> {
+    if sealed.len() < HEADER_LEN || !sealed.starts_with(MAGIC) {
+        return Err("Not a sync file".to_string());
+    }
+    let (salt, rest) = sealed[MAGIC.len()..].split_at(SALT_LEN);
+    let (nonce, ciphertext) = rest.split_at(NONCE_LEN);
+
+    cipher(passphrase, salt)?
+        .decrypt(
+            XNonce::from_slice(nonce),
+            Payload {
+                msg: ciphertext,
+                aad: MAGIC,
+            },
+        )
+        .map_err(|_| "Wrong sync passphrase or corrupted sync file".to_string())
+}
+
+fn cipher(passphrase: &str, salt: &[u8]) -> Result<XChaCha20Poly1305, String> {
+    let mut key = [0u8; 32];
+    Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|e| e.to_string())?;
+    Ok(XChaCha20Poly1305::new(&key.into()))
+}
+
+/// Random hex id for this device.
+pub fn random_id() -> String {
+    let mut bytes = [0u8; 16];
+    OsRng.fill_bytes(&mut bytes);
+    crate::db::to_hex(&bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sealed_data_opens_with_the_same_passphrase() {
+        let sealed = seal(b"clipboard", "passphrase").unwrap();
+        assert!(!sealed.windows(9).any(|w| w == b"clipboard"));
+        assert_eq!(open(&sealed, "passphrase").unwrap(), b"clipboard");
+    }
+
+    #[test]
+    fn wrong_passphrase_or_tampering_fails_to_open() {
+        let mut sealed = seal(b"clipboard", "passphrase").unwrap();
+        assert!(open(&sealed, "other").is_err());
+
+        let last = sealed.len() - 1;
+        sealed[last] ^= 1;
+        assert!(open(&sealed, "passphrase").is_err());
+        assert!(open(b"not sealed", "passphrase").is_err());
+    }
+}
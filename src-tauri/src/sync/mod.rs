@@ -0,0 +1,275 @@
+//! Replicates favorites (or all of history) between machines through a
+//! shared folder or a WebDAV server. Each device writes only its own file,
+//! `<device_id>.clipsync`, holding every item it knows of, and reads the
+//! others'. Files are encrypted with the sync passphrase before they leave
+//! the machine, so the folder or server never sees clipboard content.
+
+mod crypto;
+mod state;
+mod transport;
+
+use serde::{Deserialize, Serialize};
+
+use crate::db::{now_millis, Database};
+use transport::Transport;
+
+pub(crate) use state::merge;
+pub use state::{SyncRecord, SyncScope};
+
+/// Settings key: absolute folder path or `http(s)://` WebDAV collection URL
+pub const TARGET_SETTING: &str = "sync_target";
+/// Settings key: passphrase the sync files are encrypted with, the same on every device
+pub const PASSPHRASE_SETTING: &str = "sync_passphrase";
+/// Settings key: WebDAV user name, when the server needs one
+pub const USERNAME_SETTING: &str = "sync_webdav_username";
+/// Settings key: WebDAV password
+pub const PASSWORD_SETTING: &str = "sync_webdav_password";
+/// Settings key: "favorites" (default) or "all"
+pub const SCOPE_SETTING: &str = "sync_scope";
+/// Settings key: this device's id, generated on the first sync
+pub const DEVICE_ID_SETTING: &str = "sync_device_id";
+/// Settings key: epoch milliseconds of the last successful sync
+pub const LAST_SYNC_SETTING: &str = "sync_last_synced_at";
+
+const FILE_EXTENSION: &str = ".clipsync";
+const FORMAT_VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize)]
+struct SyncFile {
+    version: u32,
+    device_id: String,
+    records: Vec<SyncRecord>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SyncReport {
+    /// Other devices whose files were read
+    pub devices: usize,
+    /// Changes from other devices applied here
+    pub received: usize,
+    /// Items in this device's file
+    pub published: usize,
+}
+
+struct SyncSettings {
+    target: String,
+    passphrase: String,
+    username: Option<String>,
+    password: Option<String>,
+    scope: SyncScope,
+    device_id: String,
+}
+
+impl SyncSettings {
+    fn load(database: &Database) -> Result<Self, String> {
+        let setting = |key: &str| -> Result<Option<String>, String> {
+            Ok(database
+                .get_setting(key)?
+                .map(|v| v.trim().to_string())
+                .filter(|v| !v.is_empty()))
+        };
+
+        let target = setting(TARGET_SETTING)?.ok_or("Sync is not set up: no sync target")?;
+        let passphrase = database
+            .get_setting(PASSPHRASE_SETTING)?
+            .filter(|p| !p.is_empty())
+            .ok_or("Sync is not set up: no sync passphrase")?;
+        let device_id = match setting(DEVICE_ID_SETTING)? {
+            Some(id) => id,
+            None => {
+                let id = crypto::random_id();
+                database.set_setting(DEVICE_ID_SETTING, &id)?;
+                id
+            }
+        };
+
+        Ok(Self {
+            target,
+            passphrase,
+            username: setting(USERNAME_SETTING)?,
+            password: database.get_setting(PASSWORD_SETTING)?,
+            scope: SyncScope::from_setting(setting(SCOPE_SETTING)?.as_deref()),
+            device_id,
+        })
+    }
+}
+
+/// Pulls the other devices' files, merges them into the database and
+/// publishes this device's file. Any unreadable file fails the sync, so a
+/// mistyped passphrase doesn't go unnoticed.
+pub async fn sync_now(database: &Database) -> Result<SyncReport, String> {
+    let settings = SyncSettings::load(database)?;
+    let transport = Transport::new(
+        &settings.target,
+        settings.username.clone(),
+        settings.password.clone(),
+    )?;
+    let own_file = format!("{}{}", settings.device_id, FILE_EXTENSION);
+
+    let mut remote = Vec::new();
+    let mut devices = 0;
+    for name in transport.list().await? {
+        if name == own_file {
+            continue;
+        }
+        let sealed = transport.get(&name).await?;
+        let file = decode(&sealed, &settings.passphrase).map_err(|e| format!("{}: {}", name, e))?;
+        remote.extend(file.records);
+        devices += 1;
+    }
+
+    let (received, records) =
+        database.sync_merge(&settings.device_id, settings.scope, &state::latest(remote))?;
+    let published = records.len();
+
+    let file = SyncFile {
+        version: FORMAT_VERSION,
+        device_id: settings.device_id.clone(),
+        records,
+    };
+    let json = serde_json::to_vec(&file).map_err(|e| e.to_string())?;
+    transport
+        .put(&own_file, crypto::seal(&json, &settings.passphrase)?)
+        .await?;
+    database.set_setting(LAST_SYNC_SETTING, &now_millis().to_string())?;
+
+    Ok(SyncReport {
+        devices,
+        received,
+        published,
+    })
+}
+
+fn decode(sealed: &[u8], passphrase: &str) -> Result<SyncFile, String> {
+    let json = crypto::open(sealed, passphrase)?;
+    let file: SyncFile = serde_json::from_slice(&json).map_err(|e| e.to_string())?;
+    if file.version > FORMAT_VERSION {
+        return Err("Written by a newer version, update to sync with it".to_string());
+    }
+    Ok(file)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use super::*;
+    use crate::db::{InsertClipboardItemParams, ItemOrder};
+
+    const PASSPHRASE: &str = "correct horse battery staple";
+
+    /// A fresh directory for one test, emptied first.
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("sync-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// A device syncing through `folder` with the test passphrase.
+    fn device(dir: &Path, name: &str, folder: &Path) -> Database {
+        let database = Database::new(dir.join(format!("{}.db", name)).to_str().unwrap()).unwrap();
+        database
+            .set_setting(TARGET_SETTING, folder.to_str().unwrap())
+            .unwrap();
+        database
+            .set_setting(PASSPHRASE_SETTING, PASSPHRASE)
+            .unwrap();
+        database
+    }
+
+    fn capture(database: &Database, text: &str) -> i64 {
+        let sort_order = database.top_sort_order().unwrap();
+        database
+            .record_capture(InsertClipboardItemParams::text(
+                text.to_string(),
+                sort_order,
+            ))
+            .unwrap()
+            .id
+    }
+
+    fn find(database: &Database, text: &str) -> Option<crate::db::ClipboardItemRow> {
+        database
+            .get_all_items(100, 0, ItemOrder::Manual)
+            .unwrap()
+            .into_iter()
+            .find(|item| item.text_content.as_deref() == Some(text))
+    }
+
+    #[tokio::test]
+    async fn favorites_and_deletes_round_trip_through_a_folder() {
+        let dir = test_dir("round-trip");
+        let folder = dir.join("shared");
+        let (a, b) = (device(&dir, "a", &folder), device(&dir, "b", &folder));
+
+        let id = capture(&a, "shared snippet");
+        a.toggle_favorite(id).unwrap();
+        capture(&a, "not a favorite");
+
+        let report = sync_now(&a).await.unwrap();
+        assert_eq!((report.devices, report.published), (0, 1));
+        let report = sync_now(&b).await.unwrap();
+        assert_eq!((report.devices, report.received), (1, 1));
+
+        let item = find(&b, "shared snippet").expect("favorite synced to b");
+        assert!(item.is_favorite);
+        assert!(find(&b, "not a favorite").is_none());
+
+        b.delete_item(item.id).unwrap();
+        sync_now(&b).await.unwrap();
+        let report = sync_now(&a).await.unwrap();
+        assert_eq!(report.received, 1);
+        assert!(find(&a, "shared snippet").is_none());
+        assert!(a
+            .get_trash(100, 0)
+            .unwrap()
+            .iter()
+            .any(|item| item.text_content.as_deref() == Some("shared snippet")));
+    }
+
+    #[tokio::test]
+    async fn wrong_passphrase_fails_the_sync() {
+        let dir = test_dir("passphrase");
+        let folder = dir.join("shared");
+        let (a, b) = (device(&dir, "a", &folder), device(&dir, "b", &folder));
+        b.set_setting(PASSPHRASE_SETTING, "something else").unwrap();
+
+        let id = capture(&a, "secret");
+        a.toggle_favorite(id).unwrap();
+        sync_now(&a).await.unwrap();
+
+        assert!(sync_now(&b).await.is_err());
+        assert!(find(&b, "secret").is_none());
+    }
+
+    #[test]
+    fn equal_timestamps_go_to_the_greater_device_id() {
+        let dir = test_dir("tie-break");
+        let database = device(&dir, "a", &dir.join("shared"));
+        let id = capture(&database, "contested");
+        database.toggle_favorite(id).unwrap();
+
+        let (_, records) = database.sync_merge("m", SyncScope::Favorites, &[]).unwrap();
+        let local = records.into_iter().next().unwrap();
+        let unfavorited = |device_id: &str| SyncRecord {
+            is_favorite: false,
+            device_id: device_id.to_string(),
+            ..local.clone()
+        };
+
+        // Same time from a smaller device id: ours stands
+        let (received, _) = database
+            .sync_merge("m", SyncScope::Favorites, &[unfavorited("a")])
+            .unwrap();
+        assert_eq!(received, 0);
+        assert!(database.get_item(id).unwrap().is_favorite);
+
+        // Same time from a greater device id: theirs wins
+        let (received, _) = database
+            .sync_merge("m", SyncScope::Favorites, &[unfavorited("z")])
+            .unwrap();
+        assert_eq!(received, 1);
+        assert!(!database.get_item(id).unwrap().is_favorite);
+    }
+}
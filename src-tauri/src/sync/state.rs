@@ -0,0 +1,340 @@
+//! Change tracking and last-writer-wins merging of synced items. Each item is
+//! identified by its content hash; what syncs is whether it is a favorite,
+//! its fractional sort order and whether it was deleted. `sync_state` holds
+//! the last known value of those, so comparing it with the live rows picks up
+//! every local change without hooking each mutation.
+
+use std::collections::HashMap;
+
+use rusqlite::{Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+
+use crate::db::{self, InsertClipboardItemParams};
+
+/// Tombstones are dropped after this long. A device offline for longer may
+/// bring the item back.
+const TOMBSTONE_TTL_DAYS: i64 = 180;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SyncScope {
+    Favorites,
+    All,
+}
+
+impl SyncScope {
+    pub fn from_setting(value: Option<&str>) -> Self {
+        match value {
+            Some("all") => Self::All,
+            _ => Self::Favorites,
+        }
+    }
+}
+
+/// An item as published to other devices.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SyncRecord {
+    pub content_hash: String,
+    pub is_favorite: bool,
+    pub sort_order: String,
+    pub deleted: bool,
+    /// Epoch milliseconds of the last change
+    pub modified_at: i64,
+    /// Device that made the last change
+    pub device_id: String,
+    /// `None` for tombstones
+    pub content: Option<SyncContent>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SyncContent {
+    pub content_type: String,
+    pub text_content: Option<String>,
+    pub image_data: Option<String>,
+    pub image_width: Option<i64>,
+    pub image_height: Option<i64>,
+    pub char_count: Option<i64>,
+    pub line_count: Option<i64>,
+    pub source_app: Option<String>,
+    pub kv_key: Option<String>,
+    pub created_at: String,
+}
+
+impl SyncRecord {
+    fn wins_over(&self, modified_at: i64, device_id: &str) -> bool {
+        (self.modified_at, self.device_id.as_str()) > (modified_at, device_id)
+    }
+}
+
+/// The newest record per item.
+pub fn latest(records: impl IntoIterator<Item = SyncRecord>) -> Vec<SyncRecord> {
+    let mut newest: HashMap<String, SyncRecord> = HashMap::new();
+    for record in records {
+        match newest.get(&record.content_hash) {
+            Some(current) if !record.wins_over(current.modified_at, &current.device_id) => {}
+            _ => {
+                newest.insert(record.content_hash.clone(), record);
+            }
+        }
+    }
+    newest.into_values().collect()
+}
+
+/// Picks up local changes, applies the `remote` records newer than what we
+/// have and returns how many were applied along with the records to publish.
+pub(crate) fn merge(
+    conn: &Connection,
+    device_id: &str,
+    scope: SyncScope,
+    remote: &[SyncRecord],
+    now_ms: i64,
+) -> rusqlite::Result<(usize, Vec<SyncRecord>)> {
+    refresh(conn, device_id, scope, now_ms)?;
+
+    let mut applied = 0;
+    for record in remote {
+        let current: Option<(i64, String)> = conn
+            .query_row(
+                "SELECT modified_at, device_id FROM sync_state WHERE content_hash = ?1",
+                rusqlite::params![record.content_hash],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()?;
+        if current.is_some_and(|(modified_at, device)| !record.wins_over(modified_at, &device)) {
+            continue;
+        }
+        if apply(conn, record, now_ms)? {
+            applied += 1;
+        }
+    }
+
+    Ok((applied, published(conn)?))
+}
+
+/// Stamps items whose synced fields differ from `sync_state` as changed now
+/// by this device. New items are only tracked when in `scope`; once tracked
+/// they stay tracked, so unfavoriting an item is passed on too.
+fn refresh(
+    conn: &Connection,
+    device_id: &str,
+    scope: SyncScope,
+    now_ms: i64,
+) -> rusqlite::Result<()> {
+    let items: Vec<(String, i64, String, bool)> = conn
+        .prepare(
+            "SELECT content_hash, is_favorite, sort_order, deleted_at IS NOT NULL
+             FROM clipboard_items WHERE content_hash IS NOT NULL",
+        )?
+        .query_map([], |row| {
+            Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
+        })?
+        .collect::<Result<_, _>>()?;
+
+    let mut tracked = conn.prepare(
+        "SELECT is_favorite, sort_order, deleted FROM sync_state WHERE content_hash = ?1",
+    )?;
+    for (content_hash, is_favorite, sort_order, deleted) in items {
+        let state: Option<(i64, String, bool)> = tracked
+            .query_row(rusqlite::params![content_hash], |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+            })
+            .optional()?;
+        match state {
+            None if deleted || (scope == SyncScope::Favorites && is_favorite == 0) => continue,
+            Some(state) if state == (is_favorite, sort_order.clone(), deleted) => continue,
+            _ => {}
+        }
+        write_state(
+            conn,
+            &content_hash,
+            (is_favorite, &sort_order, deleted),
+            now_ms,
+            device_id,
+        )?;
+    }
+
+    // Items deleted for good since the last sync
+    conn.execute(
+        "UPDATE sync_state SET deleted = 1, modified_at = ?1, device_id = ?2
+         WHERE deleted = 0 AND content_hash NOT IN
+           (SELECT content_hash FROM clipboard_items WHERE content_hash IS NOT NULL)",
+        rusqlite::params![now_ms, device_id],
+    )?;
+    conn.execute(
+        "DELETE FROM sync_state WHERE deleted = 1 AND modified_at < ?1",
+        rusqlite::params![now_ms - TOMBSTONE_TTL_DAYS * 24 * 60 * 60 * 1000],
+    )?;
+    Ok(())
+}
+
+/// Brings the local item in line with `record`, inserting it if needed.
+/// Returns false for records that can't be applied, e.g. a live record
+/// without content or whose content doesn't match its hash.
+fn apply(conn: &Connection, record: &SyncRecord, now_ms: i64) -> rusqlite::Result<bool> {
+    let id = db::find_by_hash(conn, &record.content_hash)?;
+    match (id, record.deleted) {
+        (Some(id), true) => {
+            conn.execute(
+                "UPDATE clipboard_items SET deleted_at = ?1 WHERE id = ?2 AND deleted_at IS NULL",
+                rusqlite::params![now_ms.to_string(), id],
+            )?;
+        }
+        (None, true) => {}
+        (Some(id), false) => {
+            conn.execute(
+                "UPDATE clipboard_items SET is_favorite = ?1, sort_order = ?2, deleted_at = NULL WHERE id = ?3",
+                rusqlite::params![record.is_favorite, record.sort_order, id],
+            )?;
+        }
+        (None, false) => {
+            let Some(content) = &record.content else {
+                return Ok(false);
+            };
+            let params = insert_params(content, &record.sort_order, now_ms);
+            if db::params_hash(&params) != record.content_hash {
+                return Ok(false);
+            }
+            let id = db::insert_item(conn, &params, &record.content_hash)?;
+            conn.execute(
+                "UPDATE clipboard_items SET is_favorite = ?1 WHERE id = ?2",
+                rusqlite::params![record.is_favorite, id],
+            )?;
+        }
+    }
+
+    // Track the item as it now is locally, so the next refresh doesn't see
+    // the change as our own
+    let local: Option<(i64, String, bool)> = conn
+        .query_row(
+            "SELECT is_favorite, sort_order, deleted_at IS NOT NULL FROM clipboard_items WHERE content_hash = ?1",
+            rusqlite::params![record.content_hash],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )
+        .optional()?;
+    let (is_favorite, sort_order, deleted) =
+        local.unwrap_or((record.is_favorite as i64, record.sort_order.clone(), true));
+    write_state(
+        conn,
+        &record.content_hash,
+        (is_favorite, &sort_order, deleted),
+        record.modified_at,
+        &record.device_id,
+    )?;
+    Ok(true)
+}
+
+fn insert_params(
+    content: &SyncContent,
+    sort_order: &str,
+    now_ms: i64,
+) -> InsertClipboardItemParams {
+    InsertClipboardItemParams {
+        content_type: content.content_type.clone(),
+        text_content: content.text_content.clone(),
+        image_data: content.image_data.clone(),
+        image_width: content.image_width,
+        image_height: content.image_height,
+        char_count: content.char_count,
+        line_count: content.line_count,
+        source_app: content.source_app.clone(),
+        source_device: None,
+        sort_order: sort_order.to_string(),
+        kv_key: content.kv_key.clone(),
+        selection: None,
+        detected_date: None,
+        detected_color: None,
+        created_at: content.created_at.clone(),
+        updated_at: now_ms.to_string(),
+    }
+}
+
+fn write_state(
+    conn: &Connection,
+    content_hash: &str,
+    (is_favorite, sort_order, deleted): (i64, &str, bool),
+    modified_at: i64,
+    device_id: &str,
+) -> rusqlite::Result<()> {
+    conn.execute(
+        "INSERT INTO sync_state (content_hash, is_favorite, sort_order, deleted, modified_at, device_id)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+         ON CONFLICT(content_hash) DO UPDATE SET is_favorite = ?2, sort_order = ?3, deleted = ?4, modified_at = ?5, device_id = ?6",
+        rusqlite::params![content_hash, is_favorite, sort_order, deleted, modified_at, device_id],
+    )?;
+    Ok(())
+}
+
+/// Every tracked item, with content for the live ones.
+fn published(conn: &Connection) -> rusqlite::Result<Vec<SyncRecord>> {
+    conn.prepare(
+        "SELECT s.content_hash, s.is_favorite, s.sort_order, s.deleted, s.modified_at, s.device_id,
+                c.content_type, c.text_content, c.image_data, c.image_width, c.image_height,
+                c.char_count, c.line_count, c.source_app, c.kv_key, c.created_at
+         FROM sync_state s
+         LEFT JOIN clipboard_items c ON c.content_hash = s.content_hash AND s.deleted = 0",
+    )?
+    .query_map([], |row| {
+        let content_type: Option<String> = row.get(6)?;
+        let content = match content_type {
+            Some(content_type) => Some(SyncContent {
+                content_type,
+                text_content: row.get(7)?,
+                image_data: row.get(8)?,
+                image_width: row.get(9)?,
+                image_height: row.get(10)?,
+                char_count: row.get(11)?,
+                line_count: row.get(12)?,
+                source_app: row.get(13)?,
+                kv_key: row.get(14)?,
+                created_at: row.get(15)?,
+            }),
+            None => None,
+        };
+        Ok(SyncRecord {
+            content_hash: row.get(0)?,
+            is_favorite: row.get::<_, i64>(1)? != 0,
+            sort_order: row.get(2)?,
+            deleted: row.get(3)?,
+            modified_at: row.get(4)?,
+            device_id: row.get(5)?,
+            content,
+        })
+    })?
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(modified_at: i64, device_id: &str) -> SyncRecord {
+        SyncRecord {
+            content_hash: "hash".to_string(),
+            is_favorite: true,
+            sort_order: "a0".to_string(),
+            deleted: false,
+            modified_at,
+            device_id: device_id.to_string(),
+            content: None,
+        }
+    }
+
+    fn winner(records: Vec<SyncRecord>) -> (i64, String) {
+        let latest = latest(records);
+        assert_eq!(latest.len(), 1);
+        (latest[0].modified_at, latest[0].device_id.clone())
+    }
+
+    #[test]
+    fn latest_prefers_the_newer_change() {
+        let expected = (2, "a".to_string());
+        assert_eq!(winner(vec![record(1, "b"), record(2, "a")]), expected);
+        assert_eq!(winner(vec![record(2, "a"), record(1, "b")]), expected);
+    }
+
+    #[test]
+    fn latest_breaks_ties_by_device_id_in_any_order() {
+        let expected = (5, "b".to_string());
+        assert_eq!(winner(vec![record(5, "a"), record(5, "b")]), expected);
+        assert_eq!(winner(vec![record(5, "b"), record(5, "a")]), expected);
+    }
+}
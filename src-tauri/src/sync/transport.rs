@@ -0,0 +1,273 @@
+use std::path::PathBuf;
+use std::time::Duration;
+
+use percent_encoding::percent_decode_str;
+use quick_xml::escape::resolve_predefined_entity;
+use quick_xml::events::Event;
+use quick_xml::name::{Namespace, ResolveResult};
+use quick_xml::NsReader;
+use reqwest::{header, Client, Method, RequestBuilder, StatusCode};
+use url::Url;
+
+use super::FILE_EXTENSION;
+
+const TIMEOUT_SECS: u64 = 30;
+
+const DAV_NAMESPACE: Namespace<'static> = Namespace(b"DAV:");
+
+const PROPFIND_BODY: &str = r#"<?xml version="1.0" encoding="utf-8"?><d:propfind xmlns:d="DAV:"><d:prop><d:resourcetype/></d:prop></d:propfind>"#;
+
+/// Where sync files live: a local folder shared by e.g. Syncthing, or a
+/// WebDAV collection.
+pub enum Transport {
+    Folder(PathBuf),
+    WebDav(WebDav),
+}
+
+pub struct WebDav {
+    /// Collection URL, always ending in `/`
+    url: Url,
+    username: Option<String>,
+    password: Option<String>,
+    client: Client,
+}
+
+impl Transport {
+    /// `target` is an `http(s)://` WebDAV URL or an absolute folder path.
+    pub fn new(
+        target: &str,
+        username: Option<String>,
+        password: Option<String>,
+    ) -> Result<Self, String> {
+        if target.starts_with("http://") || target.starts_with("https://") {
+            let mut url = Url::parse(target).map_err(|e| format!("Invalid sync URL: {}", e))?;
+            if !url.path().ends_with('/') {
+                url.set_path(&format!("{}/", url.path()));
+            }
+            let client = Client::builder()
+                .timeout(Duration::from_secs(TIMEOUT_SECS))
+                .build()
+                .map_err(|e| e.to_string())?;
+            return Ok(Self::WebDav(WebDav {
+                url,
+                username,
+                password,
+                client,
+            }));
+        }
+
+        let path = PathBuf::from(target);
+        if !path.is_absolute() {
+            return Err("Sync folder must be an absolute path".to_string());
+        }
+        Ok(Self::Folder(path))
+    }
+
+    /// Names of the sync files present, including our own.
+    pub async fn list(&self) -> Result<Vec<String>, String> {
+        match self {
+            Self::Folder(dir) => {
+                let mut entries = match tokio::fs::read_dir(dir).await {
+                    Ok(entries) => entries,
+                    Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+                    Err(e) => return Err(e.to_string()),
+                };
+                let mut names = Vec::new();
+                while let Some(entry) = entries.next_entry().await.map_err(|e| e.to_string())? {
+                    if let Ok(name) = entry.file_name().into_string() {
+                        if is_sync_file(&name) {
+                            names.push(name);
+                        }
+                    }
+                }
+                Ok(names)
+            }
+            Self::WebDav(dav) => {
+                let method = Method::from_bytes(b"PROPFIND").map_err(|e| e.to_string())?;
+                let response = dav
+                    .request(method, dav.url.clone())
+                    .header("Depth", "1")
+                    .header(header::CONTENT_TYPE, "application/xml")
+                    .body(PROPFIND_BODY)
+                    .send()
+                    .await
+                    .map_err(|e| e.to_string())?;
+
+                if response.status() == StatusCode::NOT_FOUND {
+                    // First device to sync: create the collection
+                    let method = Method::from_bytes(b"MKCOL").map_err(|e| e.to_string())?;
+                    check(dav.request(method, dav.url.clone()).send().await)?;
+                    return Ok(Vec::new());
+                }
+                let body = check(Ok(response))?
+                    .text()
+                    .await
+                    .map_err(|e| e.to_string())?;
+                sync_file_names(&body)
+            }
+        }
+    }
+
+    pub async fn get(&self, name: &str) -> Result<Vec<u8>, String> {
+        match self {
+            Self::Folder(dir) => tokio::fs::read(dir.join(name))
+                .await
+                .map_err(|e| e.to_string()),
+            Self::WebDav(dav) => {
+                let response = check(dav.request(Method::GET, dav.file_url(name)?).send().await)?;
+                let bytes = response.bytes().await.map_err(|e| e.to_string())?;
+                Ok(bytes.to_vec())
+            }
+        }
+    }
+
+    /// Replaces the file in one step, so readers never see it half written.
+    pub async fn put(&self, name: &str, data: Vec<u8>) -> Result<(), String> {
+        match self {
+            Self::Folder(dir) => {
+                tokio::fs::create_dir_all(dir)
+                    .await
+                    .map_err(|e| e.to_string())?;
+                // Dot-prefixed so Syncthing's default ignores skip it
+                let temp = dir.join(format!(".{}.tmp", name));
+                tokio::fs::write(&temp, data)
+                    .await
+                    .map_err(|e| e.to_string())?;
+                tokio::fs::rename(&temp, dir.join(name))
+                    .await
+                    .map_err(|e| e.to_string())
+            }
+            Self::WebDav(dav) => {
+                let request = dav
+                    .request(Method::PUT, dav.file_url(name)?)
+                    .header(header::CONTENT_TYPE, "application/octet-stream")
+                    .body(data);
+                check(request.send().await).map(|_| ())
+            }
+        }
+    }
+}
+
+impl WebDav {
+    fn request(&self, method: Method, url: Url) -> RequestBuilder {
+        let request = self.client.request(method, url);
+        match &self.username {
+            Some(username) => request.basic_auth(username, self.password.as_ref()),
+            None => request,
+        }
+    }
+
+    fn file_url(&self, name: &str) -> Result<Url, String> {
+        self.url.join(name).map_err(|e| e.to_string())
+    }
+}
+
+fn is_sync_file(name: &str) -> bool {
+    !name.starts_with('.') && name.ends_with(FILE_EXTENSION)
+}
+
+fn check(result: reqwest::Result<reqwest::Response>) -> Result<reqwest::Response, String> {
+    let response = result.map_err(|e| e.to_string())?;
+    if !response.status().is_success() {
+        return Err(format!("WebDAV server returned {}", response.status()));
+    }
+    Ok(response)
+}
+
+/// Sync files listed in a PROPFIND response.
+fn sync_file_names(body: &str) -> Result<Vec<String>, String> {
+    Ok(hrefs(body)?
+        .iter()
+        .filter_map(|href| {
+            let name = href.trim_end_matches('/').rsplit('/').next()?;
+            let name = percent_decode_str(name).decode_utf8().ok()?;
+            is_sync_file(&name).then(|| name.into_owned())
+        })
+        .collect())
+}
+
+/// Contents of the DAV `href` elements in a PROPFIND response, whatever
+/// prefix the server binds the namespace to, with entities decoded.
+fn hrefs(body: &str) -> Result<Vec<String>, String> {
+    let invalid = |e: quick_xml::Error| format!("Invalid WebDAV response: {}", e);
+    let mut reader = NsReader::from_str(body);
+    let mut hrefs = Vec::new();
+    // Text of the href being read
+    let mut current: Option<String> = None;
+
+    loop {
+        let (namespace, event) = reader.read_resolved_event().map_err(invalid)?;
+        match (event, current.as_mut()) {
+            (Event::Start(start), _)
+                if namespace == ResolveResult::Bound(DAV_NAMESPACE)
+                    && start.local_name().as_ref() == b"href" =>
+            {
+                current = Some(String::new());
+            }
+            (Event::End(_), Some(_)) => {
+                if let Some(href) = current.take() {
+                    hrefs.push(href.trim().to_string());
+                }
+            }
+            (Event::Text(text), Some(href)) => {
+                href.push_str(&text.decode().map_err(|e| invalid(e.into()))?);
+            }
+            (Event::CData(data), Some(href)) => {
+                href.push_str(&data.decode().map_err(|e| invalid(e.into()))?);
+            }
+            (Event::GeneralRef(entity), Some(href)) => {
+                match entity.resolve_char_ref().map_err(invalid)? {
+                    Some(c) => href.push(c),
+                    None => {
+                        let name = entity.decode().map_err(|e| invalid(e.into()))?;
+                        let value = resolve_predefined_entity(&name).ok_or_else(|| {
+                            format!("Invalid WebDAV response: unknown entity &{};", name)
+                        })?;
+                        href.push_str(value);
+                    }
+                }
+            }
+            (Event::Eof, _) => break,
+            _ => {}
+        }
+    }
+
+    hrefs.retain(|href| !href.is_empty());
+    Ok(hrefs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MULTISTATUS: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<D:multistatus xmlns:D="DAV:">
+  <D:response>
+    <D:href>/dav/clips/</D:href>
+    <D:propstat><D:prop><D:resourcetype><D:collection/></D:resourcetype></D:prop></D:propstat>
+  </D:response>
+  <D:response><D:href>/dav/clips/laptop.clipsync</D:href></D:response>
+  <response xmlns="DAV:"><href>https://example.com/dav/clips/R%26D%20desk.clipsync</href></response>
+  <lp1:response xmlns:lp1="DAV:"><lp1:href> /dav/clips/a&amp;b&#x2e;clipsync </lp1:href></lp1:response>
+  <D:response><D:href>/dav/clips/.laptop.clipsync.tmp</D:href></D:response>
+  <D:response><D:href>/dav/clips/notes.txt</D:href></D:response>
+  <x:href xmlns:x="urn:example">/dav/clips/other.clipsync</x:href>
+</D:multistatus>"#;
+
+    #[test]
+    fn lists_sync_files_in_a_multistatus() {
+        assert_eq!(
+            sync_file_names(MULTISTATUS).unwrap(),
+            vec!["laptop.clipsync", "R&D desk.clipsync", "a&b.clipsync"]
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_responses() {
+        assert!(sync_file_names(
+            "<D:multistatus xmlns:D=\"DAV:\"><D:href>a.clipsync</D:multistatus>"
+        )
+        .is_err());
+        assert!(sync_file_names("<D:href xmlns:D=\"DAV:\">&nope;</D:href>").is_err());
+    }
+}
@@ -0,0 +1,349 @@
+use base64::engine::general_purpose::{STANDARD, URL_SAFE, URL_SAFE_NO_PAD};
+use base64::Engine;
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+
+use crate::db::to_hex;
+
+/// RFC 3986 unreserved characters are left as-is, everything else is escaped.
+const URL_COMPONENT: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'_')
+    .remove(b'.')
+    .remove(b'~');
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TextOp {
+    Upper,
+    Lower,
+    Title,
+    Snake,
+    Camel,
+    Kebab,
+    Trim,
+    NormalizeWhitespace,
+    StripFormatting,
+    SortLines,
+    DedupeLines,
+    Base64Encode,
+    Base64Decode,
+    UrlEncode,
+    UrlDecode,
+    HexEncode,
+    HexDecode,
+    JsonEscape,
+    JsonUnescape,
+    Sha256,
+    Count,
+}
+
+pub fn apply(text: &str, op: TextOp) -> Result<String, String> {
+    match op {
+        TextOp::Upper => Ok(text.to_uppercase()),
+        TextOp::Lower => Ok(text.to_lowercase()),
+        TextOp::Title => Ok(title_case(text)),
+        TextOp::Snake => Ok(map_lines(text, |l| join_words(l, "_"))),
+        TextOp::Camel => Ok(map_lines(text, camel_case)),
+        TextOp::Kebab => Ok(map_lines(text, |l| join_words(l, "-"))),
+        TextOp::Trim => Ok(map_lines(text.trim(), |l| l.trim_end().to_string())),
+        TextOp::NormalizeWhitespace => Ok(normalize_whitespace(text)),
+        TextOp::StripFormatting => Ok(strip_formatting(text)),
+        TextOp::SortLines => {
+            let mut lines: Vec<&str> = text.lines().collect();
+            lines.sort_unstable();
+            Ok(lines.join("\n"))
+        }
+        TextOp::DedupeLines => {
+            let mut seen = std::collections::HashSet::new();
+            let lines: Vec<&str> = text.lines().filter(|l| seen.insert(*l)).collect();
+            Ok(lines.join("\n"))
+        }
+        TextOp::Base64Encode => Ok(STANDARD.encode(text.as_bytes())),
+        TextOp::Base64Decode => {
+            let input = text.trim();
+            let bytes = STANDARD
+                .decode(input)
+                .or_else(|_| URL_SAFE.decode(input))
+                .or_else(|_| URL_SAFE_NO_PAD.decode(input))
+                .map_err(|e| format!("Invalid base64: {}", e))?;
+            String::from_utf8(bytes).map_err(|_| "Decoded base64 is not valid UTF-8".to_string())
+        }
+        TextOp::UrlEncode => Ok(utf8_percent_encode(text, URL_COMPONENT).to_string()),
+        TextOp::UrlDecode => percent_decode_str(&text.replace('+', " "))
+            .decode_utf8()
+            .map(|s| s.into_owned())
+            .map_err(|_| "Decoded URL is not valid UTF-8".to_string()),
+        TextOp::HexEncode => Ok(to_hex(text.as_bytes())),
+        TextOp::HexDecode => hex_decode(text),
+        TextOp::JsonEscape => {
+            let quoted = serde_json::to_string(text).map_err(|e| e.to_string())?;
+            Ok(quoted[1..quoted.len() - 1].to_string())
+        }
+        TextOp::JsonUnescape => {
+            let trimmed = text.trim();
+            let quoted = if trimmed.len() >= 2 && trimmed.starts_with('"') && trimmed.ends_with('"')
+            {
+                trimmed.to_string()
+            } else {
+                format!("\"{}\"", trimmed)
+            };
+            serde_json::from_str::<String>(&quoted)
+                .map_err(|e| format!("Invalid JSON string: {}", e))
+        }
+        TextOp::Sha256 => Ok(format!("{:x}", Sha256::digest(text.as_bytes()))),
+        TextOp::Count => Ok(format!(
+            "{} characters, {} words, {} lines, {} bytes",
+            text.chars().count(),
+            text.split_whitespace().count(),
+            text.lines().count(),
+            text.len()
+        )),
+    }
+}
+
+fn map_lines(text: &str, f: impl Fn(&str) -> String) -> String {
+    text.split('\n').map(f).collect::<Vec<_>>().join("\n")
+}
+
+/// Splits an identifier or phrase into words: `HTTPServer_port-v2` -> HTTP, Server, port, v2.
+fn split_words(text: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    for chunk in text.split(|c: char| !c.is_alphanumeric()) {
+        let chars: Vec<char> = chunk.chars().collect();
+        let mut current = String::new();
+        for (i, &c) in chars.iter().enumerate() {
+            let prev = i.checked_sub(1).map(|p| chars[p]);
+            let next = chars.get(i + 1).copied();
+            let boundary = match prev {
+                Some(p) if c.is_uppercase() => {
+                    p.is_lowercase()
+                        || p.is_numeric()
+                        || (p.is_uppercase() && next.is_some_and(|n| n.is_lowercase()))
+                }
+                _ => false,
+            };
+            if boundary && !current.is_empty() {
+                words.push(std::mem::take(&mut current));
+            }
+            current.push(c);
+        }
+        if !current.is_empty() {
+            words.push(current);
+        }
+    }
+    words
+}
+
+fn join_words(text: &str, separator: &str) -> String {
+    split_words(text)
+        .iter()
+        .map(|w| w.to_lowercase())
+        .collect::<Vec<_>>()
+        .join(separator)
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first
+            .to_uppercase()
+            .chain(chars.flat_map(char::to_lowercase))
+            .collect(),
+        None => String::new(),
+    }
+}
+
+fn camel_case(text: &str) -> String {
+    split_words(text)
+        .iter()
+        .enumerate()
+        .map(|(i, w)| {
+            if i == 0 {
+                w.to_lowercase()
+            } else {
+                capitalize(w)
+            }
+        })
+        .collect()
+}
+
+/// Capitalizes each whitespace-separated word while keeping the original spacing.
+fn title_case(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut at_word_start = true;
+    for c in text.chars() {
+        if c.is_whitespace() {
+            at_word_start = true;
+            out.push(c);
+        } else if at_word_start {
+            out.extend(c.to_uppercase());
+            at_word_start = false;
+        } else {
+            out.extend(c.to_lowercase());
+        }
+    }
+    out
+}
+
+/// Collapses runs of spaces/tabs, trims every line and squeezes blank lines to one.
+fn normalize_whitespace(text: &str) -> String {
+    let mut lines: Vec<String> = Vec::new();
+    for line in text.lines() {
+        let collapsed = line.split_whitespace().collect::<Vec<_>>().join(" ");
+        if collapsed.is_empty() && lines.last().is_none_or(|l| l.is_empty()) {
+            continue;
+        }
+        lines.push(collapsed);
+    }
+    while lines.last().is_some_and(|l| l.is_empty()) {
+        lines.pop();
+    }
+    lines.join("\n")
+}
+
+/// Removes HTML tags, ANSI escapes and invisible characters, and replaces
+/// typographic quotes/dashes with their plain ASCII equivalents.
+fn strip_formatting(text: &str) -> String {
+    let text = if text.contains('<') && text.contains('>') {
+        let fragment = scraper::Html::parse_fragment(text);
+        fragment.root_element().text().collect::<String>()
+    } else {
+        text.to_string()
+    };
+
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            // ANSI CSI sequence: ESC [ params final-byte
+            '\u{1b}' if chars.peek() == Some(&'[') => {
+                chars.next();
+                for n in chars.by_ref() {
+                    if ('@'..='~').contains(&n) {
+                        break;
+                    }
+                }
+            }
+            '\u{200b}' | '\u{200c}' | '\u{200d}' | '\u{2060}' | '\u{feff}' | '\u{ad}' => {}
+            '\u{a0}' | '\u{2007}' | '\u{202f}' => out.push(' '),
+            '\u{2018}' | '\u{2019}' | '\u{201a}' | '\u{2032}' => out.push('\''),
+            '\u{201c}' | '\u{201d}' | '\u{201e}' | '\u{2033}' => out.push('"'),
+            '\u{2013}' | '\u{2014}' | '\u{2212}' => out.push('-'),
+            '\u{2026}' => out.push_str("..."),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+fn hex_decode(text: &str) -> Result<String, String> {
+    let text = text.trim();
+    let text = text
+        .strip_prefix("0x")
+        .or_else(|| text.strip_prefix("0X"))
+        .unwrap_or(text);
+    let digits: String = text
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != ':')
+        .collect();
+    if let Some(c) = digits.chars().find(|c| !c.is_ascii_hexdigit()) {
+        return Err(format!("Invalid hex digit '{}'", c));
+    }
+    if digits.len() % 2 != 0 {
+        return Err("Hex input has an odd number of digits".to_string());
+    }
+    // All ASCII from here, so each byte is one digit
+    let bytes: Vec<u8> = digits
+        .as_bytes()
+        .chunks(2)
+        .map(|pair| (hex_value(pair[0]) << 4) | hex_value(pair[1]))
+        .collect();
+    String::from_utf8(bytes).map_err(|_| "Decoded hex is not valid UTF-8".to_string())
+}
+
+/// Value of an ASCII hex digit, already checked with `is_ascii_hexdigit`.
+fn hex_value(digit: u8) -> u8 {
+    match digit {
+        b'0'..=b'9' => digit - b'0',
+        b'a'..=b'f' => digit - b'a' + 10,
+        _ => digit - b'A' + 10,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hex_round_trips() {
+        assert_eq!(apply("hi!", TextOp::HexEncode).unwrap(), "686921");
+        assert_eq!(apply("686921", TextOp::HexDecode).unwrap(), "hi!");
+        // One prefix, separators and either case
+        assert_eq!(apply(" 0x68:69 21\n", TextOp::HexDecode).unwrap(), "hi!");
+        assert_eq!(apply("0X4A4b", TextOp::HexDecode).unwrap(), "JK");
+    }
+
+    #[test]
+    fn hex_rejects_malformed_input() {
+        assert_eq!(
+            apply("0xzz", TextOp::HexDecode).unwrap_err(),
+            "Invalid hex digit 'z'"
+        );
+        assert_eq!(
+            apply("0x0x41", TextOp::HexDecode).unwrap_err(),
+            "Invalid hex digit 'x'"
+        );
+        assert_eq!(
+            apply("abc", TextOp::HexDecode).unwrap_err(),
+            "Hex input has an odd number of digits"
+        );
+        assert_eq!(
+            apply("ff", TextOp::HexDecode).unwrap_err(),
+            "Decoded hex is not valid UTF-8"
+        );
+    }
+
+    #[test]
+    fn base64_round_trips_in_any_alphabet() {
+        assert_eq!(
+            apply("hello?>", TextOp::Base64Encode).unwrap(),
+            "aGVsbG8/Pg=="
+        );
+        assert_eq!(
+            apply("aGVsbG8/Pg==", TextOp::Base64Decode).unwrap(),
+            "hello?>"
+        );
+        assert_eq!(
+            apply("aGVsbG8_Pg==", TextOp::Base64Decode).unwrap(),
+            "hello?>"
+        );
+        assert_eq!(
+            apply(" aGVsbG8_Pg\n", TextOp::Base64Decode).unwrap(),
+            "hello?>"
+        );
+    }
+
+    #[test]
+    fn base64_rejects_malformed_input() {
+        assert!(apply("not base64!", TextOp::Base64Decode)
+            .unwrap_err()
+            .starts_with("Invalid base64"));
+        assert_eq!(
+            apply("/w==", TextOp::Base64Decode).unwrap_err(),
+            "Decoded base64 is not valid UTF-8"
+        );
+    }
+
+    #[test]
+    fn case_and_url_ops() {
+        assert_eq!(
+            apply("HTTPServer port", TextOp::Snake).unwrap(),
+            "http_server_port"
+        );
+        assert_eq!(apply("a b&c", TextOp::UrlEncode).unwrap(), "a%20b%26c");
+        assert_eq!(apply("a+b%26c", TextOp::UrlDecode).unwrap(), "a b&c");
+        assert!(apply("%ff", TextOp::UrlDecode).is_err());
+    }
+}
@@ -0,0 +1,197 @@
+//! In-memory undo/redo for history mutations. Each entry snapshots the fields
+//! a mutation can change on the items it touched, before and after, so undo
+//! and redo just write one of the snapshots back.
+
+use std::collections::VecDeque;
+
+use rusqlite::Connection;
+use serde::Serialize;
+
+use crate::db::id_list;
+
+/// Mutations kept for undo
+const MAX_UNDO: usize = 20;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MutationKind {
+    Delete,
+    Reorder,
+    Favorite,
+    Split,
+    Merge,
+}
+
+/// The undoable fields of an item
+#[derive(Debug, Clone, PartialEq)]
+struct ItemState {
+    id: i64,
+    sort_order: String,
+    is_favorite: i64,
+    deleted_at: Option<String>,
+}
+
+struct Mutation {
+    kind: MutationKind,
+    before: Vec<ItemState>,
+    after: Vec<ItemState>,
+}
+
+/// What undo and redo would do next, for enabling menu items
+#[derive(Debug, Serialize)]
+pub struct UndoState {
+    pub undo: Option<MutationKind>,
+    pub redo: Option<MutationKind>,
+}
+
+/// State of the items a mutation is about to touch, taken by `begin` and
+/// completed by `UndoStack::record`.
+pub struct Pending {
+    kind: MutationKind,
+    before: Vec<ItemState>,
+}
+
+pub fn begin(conn: &Connection, kind: MutationKind, ids: &[i64]) -> rusqlite::Result<Pending> {
+    Ok(Pending {
+        kind,
+        before: snapshot(conn, ids)?,
+    })
+}
+
+impl Pending {
+    /// Adds items found to be touched partway through the mutation, before
+    /// they change. Items already snapshotted keep their earlier state.
+    pub fn add(&mut self, conn: &Connection, ids: &[i64]) -> rusqlite::Result<()> {
+        for state in snapshot(conn, ids)? {
+            if !self.before.iter().any(|s| s.id == state.id) {
+                self.before.push(state);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+pub struct UndoStack {
+    undo: VecDeque<Mutation>,
+    redo: Vec<Mutation>,
+}
+
+impl UndoStack {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Completes `pending` once the mutation is done. `created` are items it
+    /// inserted, which undo moves to the trash (`deleted_at` = `now`).
+    pub fn record(
+        &mut self,
+        conn: &Connection,
+        pending: Pending,
+        created: &[i64],
+        now: &str,
+    ) -> rusqlite::Result<()> {
+        let mut ids: Vec<i64> = pending.before.iter().map(|s| s.id).collect();
+        for &id in created {
+            if !ids.contains(&id) {
+                ids.push(id);
+            }
+        }
+        let after = snapshot(conn, &ids)?;
+
+        let mut before = pending.before;
+        for state in &after {
+            if !before.iter().any(|s| s.id == state.id) {
+                before.push(ItemState {
+                    deleted_at: Some(now.to_string()),
+                    ..state.clone()
+                });
+            }
+        }
+        if before == after {
+            return Ok(());
+        }
+
+        self.redo.clear();
+        self.push_undo(Mutation {
+            kind: pending.kind,
+            before,
+            after,
+        });
+        Ok(())
+    }
+
+    /// Reverts the last mutation. `None` if there was nothing to undo.
+    pub fn undo(&mut self, conn: &Connection) -> rusqlite::Result<Option<MutationKind>> {
+        let Some(mutation) = self.undo.pop_back() else {
+            return Ok(None);
+        };
+        if let Err(e) = apply(conn, &mutation.before) {
+            self.undo.push_back(mutation);
+            return Err(e);
+        }
+        let kind = mutation.kind;
+        self.redo.push(mutation);
+        Ok(Some(kind))
+    }
+
+    /// Re-applies the last undone mutation. `None` if there was nothing to redo.
+    pub fn redo(&mut self, conn: &Connection) -> rusqlite::Result<Option<MutationKind>> {
+        let Some(mutation) = self.redo.pop() else {
+            return Ok(None);
+        };
+        if let Err(e) = apply(conn, &mutation.after) {
+            self.redo.push(mutation);
+            return Err(e);
+        }
+        let kind = mutation.kind;
+        self.push_undo(mutation);
+        Ok(Some(kind))
+    }
+
+    pub fn state(&self) -> UndoState {
+        UndoState {
+            undo: self.undo.back().map(|m| m.kind),
+            redo: self.redo.last().map(|m| m.kind),
+        }
+    }
+
+    fn push_undo(&mut self, mutation: Mutation) {
+        self.undo.push_back(mutation);
+        if self.undo.len() > MAX_UNDO {
+            self.undo.pop_front();
+        }
+    }
+}
+
+fn snapshot(conn: &Connection, ids: &[i64]) -> rusqlite::Result<Vec<ItemState>> {
+    conn.prepare(
+        "SELECT c.id, c.sort_order, c.is_favorite, c.deleted_at
+         FROM json_each(?1) j JOIN clipboard_items c ON c.id = j.value ORDER BY j.key",
+    )?
+    .query_map(rusqlite::params![id_list(ids)], |row| {
+        Ok(ItemState {
+            id: row.get(0)?,
+            sort_order: row.get(1)?,
+            is_favorite: row.get(2)?,
+            deleted_at: row.get(3)?,
+        })
+    })?
+    .collect()
+}
+
+/// Writes a snapshot back. Items deleted for good since are skipped.
+fn apply(conn: &Connection, states: &[ItemState]) -> rusqlite::Result<()> {
+    let mut stmt = conn.prepare(
+        "UPDATE clipboard_items SET sort_order = ?1, is_favorite = ?2, deleted_at = ?3 WHERE id = ?4",
+    )?;
+    for state in states {
+        stmt.execute(rusqlite::params![
+            state.sort_order,
+            state.is_favorite,
+            state.deleted_at,
+            state.id
+        ])?;
+    }
+    Ok(())
+}
@@ -1,6 +1,7 @@
 import "@/main.css";
-import { useCallback, useMemo, useState } from "react";
+import { useCallback, useState } from "react";
 import { useDebouncedState } from "@tanstack/react-pacer";
+import { useHotkey } from "@tanstack/react-hotkeys";
 
 import { ErrorBanner } from "@/components/clipboard-error-banner";
 import { ClipboardList } from "@/components/clipboard-list";
@@ -11,8 +12,12 @@ import { TooltipProvider } from "@/components/ui/tooltip";
 import { useClipboard } from "@/hooks/use-clipboard";
 import { useSettings } from "@/hooks/use-settings";
 import { useSystemTheme } from "@/hooks/use-system-theme";
-import { useClipboardHistory } from "@/hooks/use-clipboard-history";
+import {
+  useClipboardHistory,
+  useClipboardSearch,
+} from "@/hooks/use-clipboard-history";
 import { useClipboardMonitor } from "@/hooks/use-clipboard-monitor";
+import { clipboardDb } from "@/hooks/use-clipboard-db";
 import { ClipboardItem } from "@/types/clipboard";
 
 function App() {
@@ -21,7 +26,8 @@ function App() {
   const [searchInput, setSearchInput] = useState("");
   const [searchQuery, setSearchQuery] = useDebouncedState("", { wait: 150 });
 
-  const { historyLimit, setHistoryLimit } = useSettings();
+  const { historyLimit, setHistoryLimit, historyOrder, setHistoryOrder } =
+    useSettings();
 
   const { readContent, write, writeImage, reinitialize, error, dismissError } =
     useClipboard();
@@ -39,7 +45,13 @@ function App() {
     toggleFavorite,
     reorderItems,
     splitEnvItem,
-  } = useClipboardHistory(historyLimit);
+    undo,
+    redo,
+  } = useClipboardHistory(historyLimit, historyOrder);
+
+  // Left to the search input while it has text to undo
+  useHotkey("Mod+Z", undo, { enabled: searchInput === "" });
+  useHotkey("Mod+Shift+Z", redo, { enabled: searchInput === "" });
 
   const { systemInfo, previousContentRef } = useClipboardMonitor({
     onClipboardChange: addContentToHistory,
@@ -50,6 +62,10 @@ function App() {
 
   const handleCopy = useCallback(
     async (item: ClipboardItem) => {
+      // Logged first so the monitor's re-capture is recognised as an echo
+      await clipboardDb
+        .recordCopyEvent(item.id, "restored")
+        .catch((err) => console.error("Failed to record copy event:", err));
       if (item.content_type === "text" && item.text_content) {
         await write(item.text_content);
       } else if (item.content_type === "image" && item.image_data) {
@@ -72,19 +88,8 @@ function App() {
     }
   }, [reinitialize, readContent, previousContentRef, setCurrentContent]);
 
-  const filteredItems = useMemo(() => {
-    const q = searchQuery.trim().toLowerCase();
-    if (!q) return history;
-    return history.filter((item) => {
-      if (item.content_type === "text" && item.text_content) {
-        return item.text_content.toLowerCase().includes(q);
-      }
-      if (item.content_type === "image") {
-        return "image".includes(q);
-      }
-      return false;
-    });
-  }, [history, searchQuery]);
+  // Text and OCR text are matched by the database's full-text index
+  const { results: searchResults } = useClipboardSearch(searchQuery.trim());
 
   const isSearching = searchQuery.trim().length > 0;
 
@@ -104,6 +109,8 @@ function App() {
           }}
           historyLimit={historyLimit}
           onHistoryLimitChange={setHistoryLimit}
+          historyOrder={historyOrder}
+          onHistoryOrderChange={setHistoryOrder}
         />
 
         {error && (
@@ -119,7 +126,7 @@ function App() {
             <ClipboardItemSkeletonList />
           ) : (
             <ClipboardList
-              items={filteredItems}
+              items={isSearching ? searchResults : history}
               currentContent={currentContent}
               onCopy={handleCopy}
               onDelete={deleteItem}
@@ -1,4 +1,4 @@
-import { Copy, Check, Trash2, SplitSquareHorizontal } from "lucide-react";
+import { Copy, Check, Trash2, SplitSquareHorizontal, ScanText } from "lucide-react";
 import { Button } from "@/components/ui/button";
 import {
   Tooltip,
@@ -12,6 +12,7 @@ type ClipboardItemActionsProps = {
   onDelete: () => void;
   onSplitEnv?: () => void;
   showSplit: boolean;
+  onCopyText?: () => void;
 };
 
 export const ClipboardItemActions = ({
@@ -20,6 +21,7 @@ export const ClipboardItemActions = ({
   onDelete,
   onSplitEnv,
   showSplit,
+  onCopyText,
 }: ClipboardItemActionsProps) => {
   return (
     <div className="flex flex-col items-center gap-0.5 shrink-0 pt-0.5">
@@ -61,6 +63,26 @@ export const ClipboardItemActions = ({
         </Tooltip>
       )}
 
+      {onCopyText && (
+        <Tooltip>
+          <TooltipTrigger
+            render={
+              <Button
+                variant="ghost"
+                size="icon-xs"
+                onClick={onCopyText}
+                className="text-muted-foreground hover:text-foreground"
+              />
+            }
+          >
+            <ScanText className="size-3.5" />
+          </TooltipTrigger>
+          <TooltipContent className="pointer-events-none">
+            Copy text from image
+          </TooltipContent>
+        </Tooltip>
+      )}
+
       <Tooltip>
         <TooltipTrigger
           render={
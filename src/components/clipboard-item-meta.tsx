@@ -20,7 +20,7 @@ export const ClipboardItemMeta = ({ item }: { item: ClipboardItem }) => {
         </Badge>
       )}
 
-      {item.is_env && !item.kv_key && (
+      {item.content_kind === "env" && !item.kv_key && (
         <Badge
           variant="outline"
           className="text-[10px] px-1.5 py-0 h-4 text-amber-500 border-amber-500/40"
@@ -1,4 +1,5 @@
 import { GripHorizontal } from "lucide-react";
+import { invoke } from "@tauri-apps/api/core";
 import { ClipboardItem as ClipboardItemType } from "@/types/clipboard";
 import { Card, CardContent } from "@/components/ui/card";
 import { ClipboardItemContent } from "@/components/clipboard-item-content";
@@ -43,7 +44,15 @@ export const ClipboardItem = ({
           onCopy={() => onCopy(item)}
           onDelete={() => onDelete(item.id)}
           onSplitEnv={onSplitEnv ? () => onSplitEnv(item.id) : undefined}
-          showSplit={!!item.is_env && !item.kv_key}
+          showSplit={item.content_kind === "env" && !item.kv_key}
+          onCopyText={
+            item.content_type === "image"
+              ? () =>
+                  invoke("copy_image_text", { id: item.id }).catch((err) =>
+                    console.error("Failed to copy text from image:", err),
+                  )
+              : undefined
+          }
         />
       </CardContent>
     </Card>
@@ -7,7 +7,7 @@ import {
   Trash2,
 } from "lucide-react";
 import { useHotkey } from "@tanstack/react-hotkeys";
-import { SystemInfo } from "@/types/clipboard";
+import { HistoryOrder, SystemInfo } from "@/types/clipboard";
 import { Button } from "@/components/ui/button";
 import { Badge } from "@/components/ui/badge";
 import { Input } from "@/components/ui/input";
@@ -28,6 +28,13 @@ import {
 
 const HISTORY_LIMIT_OPTIONS = [25, 50, 100, 200, 500] as const;
 
+const HISTORY_ORDER_OPTIONS: { value: HistoryOrder; label: string }[] = [
+  { value: "manual", label: "Manual" },
+  { value: "recent", label: "Recently used" },
+  { value: "frecency", label: "Frequently used" },
+  { value: "most_used", label: "Most used" },
+];
+
 type ClipboardHeaderProps = {
   isMonitoring: boolean;
   onToggleMonitoring: () => void;
@@ -38,6 +45,8 @@ type ClipboardHeaderProps = {
   onSearchChange: (query: string) => void;
   historyLimit: number;
   onHistoryLimitChange: (limit: number) => void;
+  historyOrder: HistoryOrder;
+  onHistoryOrderChange: (order: HistoryOrder) => void;
 };
 
 export const ClipboardHeader = ({
@@ -50,6 +59,8 @@ export const ClipboardHeader = ({
   onSearchChange,
   historyLimit,
   onHistoryLimitChange,
+  historyOrder,
+  onHistoryOrderChange,
 }: ClipboardHeaderProps) => {
   const searchRef = useRef<HTMLInputElement>(null);
 
@@ -132,6 +143,27 @@ export const ClipboardHeader = ({
             </DropdownMenuSubContent>
           </DropdownMenuSub>
 
+          <DropdownMenuSub>
+            <DropdownMenuSubTrigger>Sort by</DropdownMenuSubTrigger>
+            <DropdownMenuSubContent>
+              <DropdownMenuRadioGroup
+                value={historyOrder}
+                onValueChange={(value) =>
+                  onHistoryOrderChange(value as HistoryOrder)
+                }
+              >
+                {HISTORY_ORDER_OPTIONS.map((option) => (
+                  <DropdownMenuRadioItem
+                    key={option.value}
+                    value={option.value}
+                  >
+                    {option.label}
+                  </DropdownMenuRadioItem>
+                ))}
+              </DropdownMenuRadioGroup>
+            </DropdownMenuSubContent>
+          </DropdownMenuSub>
+
           {hasHistory && (
             <>
               <DropdownMenuSeparator />
@@ -16,6 +16,7 @@ type LinkPreviewData = {
   image: string | null;
   favicon: string | null;
   site_name: string | null;
+  fetched_at: string;
 };
 
 const URL_REGEX = /^https?:\/\/[^\s]+$/;
@@ -31,6 +32,8 @@ export function LinkPreview({ url }: { url: string }) {
   const [data, setData] = useState<LinkPreviewData | null>(cached ?? null);
   const [loading, setLoading] = useState(!previewCache.has(url));
   const [error, setError] = useState(false);
+  // Privacy settings only allow fetching this link when the user asks
+  const [deferred, setDeferred] = useState(false);
   const [showQr, setShowQr] = useState(false);
   const [copied, setCopied] = useState(false);
   const qrRef = useRef<HTMLCanvasElement>(null);
@@ -45,6 +48,38 @@ export function LinkPreview({ url }: { url: string }) {
     setTimeout(() => setCopied(false), 1500);
   }, []);
 
+  const load = useCallback(
+    (onDemand: boolean, isCancelled: () => boolean = () => false) => {
+      setLoading(true);
+      setError(false);
+      setDeferred(false);
+
+      invoke<LinkPreviewData | null>("fetch_link_preview", { url, onDemand })
+        .then((result) => {
+          if (result === null) {
+            if (!isCancelled()) {
+              setDeferred(true);
+              setLoading(false);
+            }
+            return;
+          }
+          previewCache.set(url, result);
+          if (!isCancelled()) {
+            setData(result);
+            setLoading(false);
+          }
+        })
+        .catch(() => {
+          previewCache.set(url, null);
+          if (!isCancelled()) {
+            setError(true);
+            setLoading(false);
+          }
+        });
+    },
+    [url],
+  );
+
   useEffect(() => {
     if (previewCache.has(url)) {
       setData(previewCache.get(url)!);
@@ -53,29 +88,12 @@ export function LinkPreview({ url }: { url: string }) {
     }
 
     let cancelled = false;
-    setLoading(true);
-    setError(false);
-
-    invoke<LinkPreviewData>("fetch_link_preview", { url })
-      .then((result) => {
-        previewCache.set(url, result);
-        if (!cancelled) {
-          setData(result);
-          setLoading(false);
-        }
-      })
-      .catch(() => {
-        previewCache.set(url, null);
-        if (!cancelled) {
-          setError(true);
-          setLoading(false);
-        }
-      });
+    load(false, () => cancelled);
 
     return () => {
       cancelled = true;
     };
-  }, [url]);
+  }, [url, load]);
 
   if (loading) {
     return (
@@ -86,6 +104,22 @@ export function LinkPreview({ url }: { url: string }) {
     );
   }
 
+  if (deferred) {
+    return (
+      <button
+        type="button"
+        className="flex w-full items-center gap-2 rounded-md border border-dashed border-border/50 px-2.5 py-1.5 text-xs text-muted-foreground hover:bg-muted/30 hover:text-foreground transition-colors"
+        onClick={(e) => {
+          e.stopPropagation();
+          load(true);
+        }}
+      >
+        <Globe className="size-3.5 shrink-0" />
+        <span className="truncate">Load preview for {new URL(url).hostname}</span>
+      </button>
+    );
+  }
+
   if (error || !data || (!data.title && !data.description)) {
     return null;
   }
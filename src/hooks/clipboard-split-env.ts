@@ -1,11 +1,11 @@
 import { invoke } from "@tauri-apps/api/core";
 import { generateNKeysBetween } from "jittered-fractional-indexing";
 import { ClipboardItem } from "@/types/clipboard";
-import { clipboardDb } from "@/hooks/use-clipboard-db";
+import { clipboardDb, InsertParams } from "@/hooks/use-clipboard-db";
 
 /**
  * Splits an env-format clipboard item into individual key-value items.
- * Returns the new items to insert, or null if the split couldn't be performed.
+ * Returns the new items, or null if the split couldn't be performed.
  */
 export async function splitEnvItemInDb(
   item: ClipboardItem,
@@ -22,61 +22,51 @@ export async function splitEnvItemInDb(
   const totalSlots = pairs.length * 2;
   const sortKeys = generateNKeysBetween(beforeSort, afterSort, totalSlots);
   const now = Date.now().toString();
-  const newItems: ClipboardItem[] = [];
+  const parts: InsertParams[] = [];
 
   for (let i = 0; i < pairs.length; i++) {
     const [key, value] = pairs[i];
 
-    try {
-      const keyItem = await clipboardDb.insertItem({
-        content_type: "text",
-        text_content: key,
-        image_data: null,
-        image_width: null,
-        image_height: null,
-        char_count: key.length,
-        line_count: 1,
-        source_app: null,
-        sort_order: sortKeys[i * 2],
-        kv_key: null,
-        detected_date: null,
-        detected_color: null,
-        created_at: now,
-        updated_at: now,
-      });
-      newItems.push(keyItem);
-    } catch (err) {
-      console.error("Failed to insert split env key:", err);
-    }
+    parts.push({
+      content_type: "text",
+      text_content: key,
+      image_data: null,
+      image_width: null,
+      image_height: null,
+      char_count: key.length,
+      line_count: 1,
+      source_app: null,
+      sort_order: sortKeys[i * 2],
+      kv_key: null,
+      detected_date: null,
+      detected_color: null,
+      created_at: now,
+      updated_at: now,
+    });
 
-    try {
-      const valueItem = await clipboardDb.insertItem({
-        content_type: "text",
-        text_content: value,
-        image_data: null,
-        image_width: null,
-        image_height: null,
-        char_count: value.length,
-        line_count: value.split("\n").length,
-        source_app: null,
-        sort_order: sortKeys[i * 2 + 1],
-        kv_key: key,
-        detected_date: null,
-        detected_color: null,
-        created_at: now,
-        updated_at: now,
-      });
-      newItems.push(valueItem);
-    } catch (err) {
-      console.error("Failed to insert split env value:", err);
-    }
+    parts.push({
+      content_type: "text",
+      text_content: value,
+      image_data: null,
+      image_width: null,
+      image_height: null,
+      char_count: value.length,
+      line_count: value.split("\n").length,
+      source_app: null,
+      sort_order: sortKeys[i * 2 + 1],
+      kv_key: key,
+      detected_date: null,
+      detected_color: null,
+      created_at: now,
+      updated_at: now,
+    });
   }
 
   try {
-    await clipboardDb.deleteItem(item.id);
+    // One transaction, so undo restores the original and removes the parts
+    return await clipboardDb.splitItem(item.id, parts);
   } catch (err) {
-    console.error("Failed to delete original env item:", err);
+    console.error("Failed to split env item:", err);
+    return null;
   }
-
-  return newItems;
 }
@@ -1,7 +1,31 @@
 import { invoke } from "@tauri-apps/api/core";
-import { ClipboardItem } from "@/types/clipboard";
+import {
+  BatchExport,
+  BatchExportFormat,
+  BatchSummary,
+  ClearOptions,
+  ClearSummary,
+  ClipboardItem,
+  ClipboardItemSummary,
+  CopyEvent,
+  CopyEventKind,
+  CopyTimelineFilter,
+  FuzzySearchResult,
+  HistoryOrder,
+  ItemCursor,
+  LanPeer,
+  LanStatus,
+  MutationKind,
+  PairingCode,
+  Register,
+  SimilarImage,
+  SyncReport,
+  TagCount,
+  UndoState,
+} from "@/types/clipboard";
+import { StatsPeriod, UsageStats } from "@/types/stats";
 
-type InsertParams = {
+export type InsertParams = {
   content_type: string;
   text_content: string | null;
   image_data: string | null;
@@ -10,6 +34,7 @@ type InsertParams = {
   char_count: number | null;
   line_count: number | null;
   source_app: string | null;
+  source_device?: string | null;
   sort_order: string;
   kv_key: string | null;
   detected_date: string | null;
@@ -24,26 +49,150 @@ type UpdateSortOrderParams = {
 };
 
 export const clipboardDb = {
-  getAllItems: (limit: number, offset = 0) =>
-    invoke<ClipboardItem[]>("db_get_all_items", { limit, offset }),
+  getAllItems: (limit: number, offset = 0, order: HistoryOrder = "manual") =>
+    invoke<ClipboardItem[]>("db_get_all_items", { limit, offset, order }),
+
+  // Keyset paging in manual order, stable while items are bumped
+  getItemsAfter: (limit: number, after: ItemCursor | null = null) =>
+    invoke<ClipboardItem[]>("db_get_items_after", { limit, after }),
+
+  getItemSummaries: (limit: number, after: ItemCursor | null = null) =>
+    invoke<ClipboardItemSummary[]>("db_get_item_summaries", { limit, after }),
+
+  getItem: (id: number) => invoke<ClipboardItem>("db_get_item", { id }),
 
   getItemCount: () => invoke<number>("db_get_item_count"),
 
-  insertItem: (params: InsertParams) =>
-    invoke<ClipboardItem>("db_insert_item", { params }),
+  // Sort key above every item in the database, loaded or not
+  getTopSortOrder: () => invoke<string>("db_top_sort_order"),
+
+  // Inserts, or bumps the existing item with the same content
+  recordCapture: (params: InsertParams) =>
+    invoke<ClipboardItem>("db_record_capture", { params }),
 
   bumpItem: (id: number, sortOrder: string) =>
     invoke<ClipboardItem>("db_bump_item", { id, sort_order: sortOrder }),
 
+  // Moves to the trash
   deleteItem: (id: number) => invoke<void>("db_delete_item", { id }),
 
+  // Trashes everything except favorites
   clearAll: () => invoke<void>("db_clear_all"),
 
+  clearHistory: (options: ClearOptions = {}) =>
+    invoke<ClearSummary>("db_clear_history", { options }),
+
+  getTrash: (limit = 100, offset = 0) =>
+    invoke<ClipboardItem[]>("db_get_trash", { limit, offset }),
+
+  restoreItems: (ids: number[]) =>
+    invoke<BatchSummary>("db_restore_items", { ids }),
+
+  emptyTrash: () => invoke<number>("db_empty_trash"),
+
+  // Inserts the parts and trashes the original as one undoable step
+  splitItem: (id: number, parts: InsertParams[]) =>
+    invoke<ClipboardItem[]>("db_split_item", { id, parts }),
+
+  undo: () => invoke<MutationKind | null>("db_undo"),
+
+  redo: () => invoke<MutationKind | null>("db_redo"),
+
+  getUndoState: () => invoke<UndoState>("db_get_undo_state"),
+
   toggleFavorite: (id: number) =>
     invoke<ClipboardItem>("db_toggle_favorite", { id }),
 
   updateSortOrders: (items: UpdateSortOrderParams[]) =>
     invoke<void>("db_update_sort_orders", { items }),
 
+  batchDelete: (ids: number[]) =>
+    invoke<BatchSummary>("db_batch_delete", { ids }),
+
+  batchSetFavorite: (ids: number[], favorite: boolean) =>
+    invoke<BatchSummary>("db_batch_set_favorite", { ids, favorite }),
+
+  batchAddTag: (ids: number[], tag: string) =>
+    invoke<BatchSummary>("db_batch_add_tag", { ids, tag }),
+
+  batchRemoveTag: (ids: number[], tag: string) =>
+    invoke<BatchSummary>("db_batch_remove_tag", { ids, tag }),
+
+  // Text items only, joined in the order given; separator defaults to "\n"
+  batchMerge: (ids: number[], separator?: string) =>
+    invoke<BatchSummary>("db_batch_merge", { ids, separator }),
+
+  batchCopy: (
+    ids: number[],
+    output: "clipboard" | "new_item" = "clipboard",
+    separator?: string,
+  ) => invoke<BatchSummary>("db_batch_copy", { ids, separator, output }),
+
+  batchExport: (ids: number[], format: BatchExportFormat, separator?: string) =>
+    invoke<BatchExport>("db_batch_export", { ids, format, separator }),
+
+  getItemTags: (id: number) => invoke<string[]>("db_get_item_tags", { id }),
+
+  getTags: () => invoke<TagCount[]>("db_get_tags"),
+
   dedupItem: (id: number) => invoke<number>("db_dedup_item", { id }),
+
+  searchFuzzy: (query: string, limit = 50) =>
+    invoke<FuzzySearchResult[]>("search_items_fuzzy", { query, limit }),
+
+  // Filter syntax, e.g. `type:image app:firefox fav:yes "exact phrase"`
+  searchItems: (query: string, limit = 100, offset = 0) =>
+    invoke<ClipboardItem[]>("search_items", { query, limit, offset }),
+
+  findSimilarImages: (id: number, maxDistance?: number) =>
+    invoke<SimilarImage[]>("db_find_similar_images", { id, maxDistance }),
+
+  recordCopyEvent: (id: number, kind: CopyEventKind) =>
+    invoke<void>("db_record_copy_event", { id, kind }),
+
+  getCopyTimeline: (filter: CopyTimelineFilter = {}, limit = 100, offset = 0) =>
+    invoke<CopyEvent[]>("db_get_copy_timeline", { filter, limit, offset }),
+
+  getUsageStats: (period: StatsPeriod = "day", periods = 30, limit = 10) =>
+    invoke<UsageStats>("db_get_usage_stats", { period, periods, limit }),
+
+  // Merges other devices' changes from the sync folder or WebDAV server
+  syncNow: () => invoke<SyncReport>("sync_now"),
+
+  getRegisters: () => invoke<Register[]>("db_get_registers"),
+
+  // Stores the current clipboard content in a register
+  storeRegister: (name: string) => invoke<Register>("store_register", { name }),
+
+  // Puts a register's content back on the clipboard
+  pasteRegister: (name: string) => invoke<void>("paste_register", { name }),
+
+  setRegisterFromItem: (name: string, id: number) =>
+    invoke<Register>("db_set_register", { name, id }),
+
+  clearRegister: (name: string) => invoke<void>("db_clear_register", { name }),
+
+  lanStart: () => invoke<LanStatus>("lan_start"),
+
+  lanStop: () => invoke<void>("lan_stop"),
+
+  lanStatus: () => invoke<LanStatus>("lan_status"),
+
+  lanGetPeers: () => invoke<LanPeer[]>("lan_get_peers"),
+
+  // Code to enter on the other device, valid for two minutes
+  lanStartPairing: () => invoke<PairingCode>("lan_start_pairing"),
+
+  // Resolves once the other device's user confirms the fingerprint
+  lanPair: (address: string, code: string) =>
+    invoke<LanPeer>("lan_pair", { address, code }),
+
+  // After comparing the fingerprints shown on both devices
+  lanConfirmPairing: (accept: boolean) =>
+    invoke<void>("lan_confirm_pairing", { accept }),
+
+  lanUnpair: (publicKey: string) => invoke<void>("lan_unpair", { publicKey }),
+
+  // Resolves to how many paired devices received it
+  lanShareItem: (id: number) => invoke<number>("lan_share_item", { id }),
 };
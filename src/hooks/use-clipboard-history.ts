@@ -1,33 +1,66 @@
-import { useState, useCallback, useRef } from "react";
-import { invoke } from "@tauri-apps/api/core";
-import { useQueryClient, useInfiniteQuery } from "@tanstack/react-query";
+import { useState, useCallback, useEffect, useRef } from "react";
+import { listen } from "@tauri-apps/api/event";
+import {
+  useQueryClient,
+  useInfiniteQuery,
+  useQuery,
+} from "@tanstack/react-query";
 import { generateKeyBetween } from "jittered-fractional-indexing";
-import { ClipboardContent } from "@/types/clipboard";
+import {
+  ClipboardContent,
+  HistoryOrder,
+  ItemCursor,
+} from "@/types/clipboard";
 import { clipboardDb } from "@/hooks/use-clipboard-db";
-import { enrichAllWithEnvDetection } from "@/hooks/clipboard-enrichment";
 import { splitEnvItemInDb } from "@/hooks/clipboard-split-env";
 
 const HISTORY_KEY = "clipboard-history";
 
-export const useClipboardHistory = (maxItems: number) => {
+type HistoryPageParam = { cursor: ItemCursor | null; offset: number };
+
+const SEARCH_LIMIT = 200;
+
+// Searches the whole history in the database, so items not loaded yet are
+// found too. Keyed under HISTORY_KEY so history changes refresh results.
+export const useClipboardSearch = (query: string) => {
+  const { data, isFetching } = useQuery({
+    queryKey: [HISTORY_KEY, "search", query],
+    queryFn: () => clipboardDb.searchItems(query, SEARCH_LIMIT),
+    enabled: query.length > 0,
+    placeholderData: (previous) => previous,
+  });
+  return { results: data ?? [], isFetching };
+};
+
+export const useClipboardHistory = (
+  maxItems: number,
+  order: HistoryOrder = "manual",
+) => {
   const queryClient = useQueryClient();
   const [currentContent, setCurrentContent] = useState<ClipboardContent>({
     type: "empty",
   });
 
   const { data, fetchNextPage, hasNextPage, isLoading } = useInfiniteQuery({
-    queryKey: [HISTORY_KEY, maxItems],
-    queryFn: async ({ pageParam = 0 }) => {
-      const items = await clipboardDb
-        .getAllItems(maxItems, pageParam)
-        .then(enrichAllWithEnvDetection);
-      return items;
+    queryKey: [HISTORY_KEY, maxItems, order],
+    queryFn: async ({ pageParam }) => {
+      // Manual order pages by cursor so bumps while scrolling don't skip rows
+      if (order === "manual") {
+        return clipboardDb.getItemsAfter(maxItems, pageParam.cursor);
+      }
+      return clipboardDb.getAllItems(maxItems, pageParam.offset, order);
     },
-    initialPageParam: 0,
-    getNextPageParam: (_lastPage, allPages) => {
+    initialPageParam: { cursor: null, offset: 0 } as HistoryPageParam,
+    getNextPageParam: (_lastPage, allPages): HistoryPageParam | undefined => {
       const totalLoaded = allPages.reduce((sum, p) => sum + p.length, 0);
+      const last = _lastPage[_lastPage.length - 1];
       // If last page was full, there might be more
-      if (_lastPage.length === maxItems) return totalLoaded;
+      if (_lastPage.length === maxItems && last) {
+        return {
+          cursor: { sort_order: last.sort_order, id: last.id },
+          offset: totalLoaded,
+        };
+      }
       return undefined;
     },
   });
@@ -40,24 +73,32 @@ export const useClipboardHistory = (maxItems: number) => {
     queryClient.invalidateQueries({ queryKey: [HISTORY_KEY] });
   }, [queryClient]);
 
-  const getTopSortOrder = useCallback(() => {
-    const items = historyRef.current;
-    return generateKeyBetween(null, items[0]?.sort_order ?? null);
-  }, []);
+  // Image items get their OCR text in the background after insert
+  useEffect(() => {
+    const unlisten = listen<number>("ocr-completed", () => invalidate());
+    return () => {
+      unlisten.then((fn) => fn());
+    };
+  }, [invalidate]);
+
+  // Items shared by paired devices on the network
+  useEffect(() => {
+    const unlisten = listen<number>("lan-item-received", () => invalidate());
+    return () => {
+      unlisten.then((fn) => fn());
+    };
+  }, [invalidate]);
 
   const addTextToHistory = useCallback(
     async (text: string) => {
       if (!text.trim()) return;
 
-      const sortOrder = getTopSortOrder();
       const now = Date.now().toString();
 
       try {
-        const [detectedDate, detectedColor] = await Promise.all([
-          invoke<string | null>("detect_date_content", { text }),
-          invoke<string | null>("detect_color_content", { text }),
-        ]);
-        const rawItem = await clipboardDb.insertItem({
+        // The loaded history may be filtered or reordered, so ask the database
+        const sortOrder = await clipboardDb.getTopSortOrder();
+        await clipboardDb.recordCapture({
           content_type: "text",
           text_content: text,
           image_data: null,
@@ -68,30 +109,28 @@ export const useClipboardHistory = (maxItems: number) => {
           source_app: null,
           sort_order: sortOrder,
           kv_key: null,
-          detected_date: detectedDate,
-          detected_color: detectedColor,
+          detected_date: null,
+          detected_color: null,
           created_at: now,
           updated_at: now,
         });
-        // Dedup then refresh
-        await clipboardDb.dedupItem(rawItem.id);
         invalidate();
       } catch (err) {
         console.error("Failed to insert clipboard item:", err);
       }
     },
-    [getTopSortOrder, invalidate],
+    [invalidate],
   );
 
   const addImageToHistory = useCallback(
     async (base64Data: string, width: number, height: number) => {
       if (!base64Data) return;
 
-      const sortOrder = getTopSortOrder();
       const now = Date.now().toString();
 
       try {
-        const item = await clipboardDb.insertItem({
+        const sortOrder = await clipboardDb.getTopSortOrder();
+        await clipboardDb.recordCapture({
           content_type: "image",
           text_content: null,
           image_data: base64Data,
@@ -107,13 +146,12 @@ export const useClipboardHistory = (maxItems: number) => {
           created_at: now,
           updated_at: now,
         });
-        await clipboardDb.dedupItem(item.id);
         invalidate();
       } catch (err) {
         console.error("Failed to insert clipboard item:", err);
       }
     },
-    [getTopSortOrder, invalidate],
+    [invalidate],
   );
 
   const addContentToHistory = useCallback(
@@ -167,6 +205,8 @@ export const useClipboardHistory = (maxItems: number) => {
 
   const reorderItems = useCallback(
     async (activeId: number, overId: number) => {
+      // Drag-and-drop only edits the manual order
+      if (order !== "manual") return;
       const items = historyRef.current;
       const oldIndex = items.findIndex((i) => i.id === activeId);
       const newIndex = items.findIndex((i) => i.id === overId);
@@ -185,13 +225,16 @@ export const useClipboardHistory = (maxItems: number) => {
 
       // Optimistic update
       const updated = { ...moved, sort_order: newSortOrder };
-      queryClient.setQueryData([HISTORY_KEY, maxItems], (old: typeof data) => {
-        if (!old) return old;
-        const newFlat = reordered.map((item) =>
-          item.id === activeId ? updated : item,
-        );
-        return { ...old, pages: [newFlat] };
-      });
+      queryClient.setQueryData(
+        [HISTORY_KEY, maxItems, order],
+        (old: typeof data) => {
+          if (!old) return old;
+          const newFlat = reordered.map((item) =>
+            item.id === activeId ? updated : item,
+          );
+          return { ...old, pages: [newFlat] };
+        },
+      );
 
       try {
         await clipboardDb.updateSortOrders([
@@ -202,7 +245,7 @@ export const useClipboardHistory = (maxItems: number) => {
         invalidate();
       }
     },
-    [maxItems, queryClient, invalidate],
+    [maxItems, order, queryClient, invalidate],
   );
 
   const splitEnvItem = useCallback(
@@ -224,6 +267,23 @@ export const useClipboardHistory = (maxItems: number) => {
     [invalidate],
   );
 
+  // Deletes, reorders, favorite toggles and splits can be undone
+  const undo = useCallback(async () => {
+    try {
+      if (await clipboardDb.undo()) invalidate();
+    } catch (err) {
+      console.error("Failed to undo:", err);
+    }
+  }, [invalidate]);
+
+  const redo = useCallback(async () => {
+    try {
+      if (await clipboardDb.redo()) invalidate();
+    } catch (err) {
+      console.error("Failed to redo:", err);
+    }
+  }, [invalidate]);
+
   const loadMore = useCallback(() => {
     if (hasNextPage) fetchNextPage();
   }, [hasNextPage, fetchNextPage]);
@@ -241,5 +301,7 @@ export const useClipboardHistory = (maxItems: number) => {
     toggleFavorite,
     reorderItems,
     splitEnvItem,
+    undo,
+    redo,
   };
 };
@@ -1,8 +1,17 @@
 import { useState, useCallback, useEffect } from "react";
 import { invoke } from "@tauri-apps/api/core";
+import { HistoryOrder } from "@/types/clipboard";
+
+const HISTORY_ORDERS: readonly HistoryOrder[] = [
+  "manual",
+  "recent",
+  "frecency",
+  "most_used",
+];
 
 const DEFAULTS = {
   history_limit: 50,
+  history_order: "manual" as HistoryOrder,
 } as const;
 
 type SettingsKey = keyof typeof DEFAULTS;
@@ -11,17 +20,26 @@ export const useSettings = () => {
   const [historyLimit, setHistoryLimitState] = useState<number>(
     DEFAULTS.history_limit,
   );
+  const [historyOrder, setHistoryOrderState] = useState<HistoryOrder>(
+    DEFAULTS.history_order,
+  );
   const [isLoaded, setIsLoaded] = useState(false);
 
   useEffect(() => {
-    invoke<string | null>("get_setting", { key: "history_limit" })
-      .then((value) => {
-        if (value) {
-          const parsed = parseInt(value, 10);
+    Promise.all([
+      invoke<string | null>("get_setting", { key: "history_limit" }),
+      invoke<string | null>("get_setting", { key: "history_order" }),
+    ])
+      .then(([limit, order]) => {
+        if (limit) {
+          const parsed = parseInt(limit, 10);
           if (!isNaN(parsed) && parsed > 0) {
             setHistoryLimitState(parsed);
           }
         }
+        if (order && HISTORY_ORDERS.includes(order as HistoryOrder)) {
+          setHistoryOrderState(order as HistoryOrder);
+        }
       })
       .finally(() => setIsLoaded(true));
   }, []);
@@ -34,9 +52,19 @@ export const useSettings = () => {
     });
   }, []);
 
+  const setHistoryOrder = useCallback(async (order: HistoryOrder) => {
+    setHistoryOrderState(order);
+    await invoke("set_setting", {
+      key: "history_order" satisfies SettingsKey,
+      value: order,
+    });
+  }, []);
+
   return {
     historyLimit,
     setHistoryLimit,
+    historyOrder,
+    setHistoryOrder,
     isLoaded,
   };
 };
@@ -1,5 +1,22 @@
 export type ClipboardItemType = "text" | "image";
 
+export type ContentKind =
+  | "text"
+  | "image"
+  | "url"
+  | "email"
+  | "path"
+  | "json"
+  | "yaml"
+  | "toml"
+  | "env"
+  | "color"
+  | "date"
+  | "number"
+  | "code"
+  | "phone"
+  | "uuid";
+
 export type ClipboardItem = {
   id: number;
   content_type: ClipboardItemType;
@@ -10,6 +27,8 @@ export type ClipboardItem = {
   char_count: number | null;
   line_count: number | null;
   source_app: string | null;
+  // Name of the LAN peer it was received from
+  source_device: string | null;
   is_favorite: boolean;
   sort_order: string;
   copy_count: number;
@@ -17,9 +36,172 @@ export type ClipboardItem = {
   detected_date: string | null;
   detected_color: string | null;
   content_hash: string | null;
+  content_kind: ContentKind | null;
+  code_language: string | null;
+  ocr_text: string | null;
+  perceptual_hash: string | null;
+  // Epoch millis when moved to the trash, null while in history
+  deleted_at: string | null;
   created_at: string;
   updated_at: string;
-  is_env?: boolean;
+};
+
+// List projection without image data or hashes, see db_get_item_summaries
+export type ClipboardItemSummary = Omit<
+  ClipboardItem,
+  | "text_content"
+  | "image_data"
+  | "content_hash"
+  | "ocr_text"
+  | "perceptual_hash"
+  | "deleted_at"
+> & {
+  // Start of text_content; the full item comes from getItem
+  text_preview: string | null;
+};
+
+// Keyset position in manual order: the last item of the previous page
+export type ItemCursor = {
+  sort_order: string;
+  id: number;
+};
+
+export type BatchSummary = {
+  requested: number;
+  affected: number[];
+  missing: number[];
+  // e.g. images when joining text
+  skipped: number[];
+  item: ClipboardItem | null;
+};
+
+export type BatchExportFormat = "json" | "text";
+
+export type BatchExport = {
+  file_name: string;
+  content: string;
+  summary: BatchSummary;
+};
+
+export type TagCount = {
+  tag: string;
+  count: number;
+};
+
+// Filters for clearHistory; all set ones have to match
+export type ClearOptions = {
+  keep_favorites?: boolean;
+  // Epoch millis; only items last used before this
+  before?: number;
+  content_type?: ClipboardItemType;
+  source_app?: string;
+  // Only text that looks like a password, token or private key
+  secrets_only?: boolean;
+  // Delete for good and vacuum, instead of moving to the trash
+  permanent?: boolean;
+};
+
+export type ClearSummary = {
+  removed: number;
+  bytes_reclaimed: number;
+};
+
+// Result of syncing with the other devices
+export type SyncReport = {
+  devices: number;
+  received: number;
+  published: number;
+};
+
+// Named register (a-z, 1-9) holding its own copy of clipboard content
+export type Register = {
+  name: string;
+  content_type: ClipboardItemType;
+  text_content: string | null;
+  image_data: string | null;
+  image_width: number | null;
+  image_height: number | null;
+  updated_at: string;
+};
+
+// Another device on the network, paired or just discovered
+export type LanPeer = {
+  public_key: string;
+  name: string;
+  // host:port last seen or given when pairing
+  address: string | null;
+  paired: boolean;
+  online: boolean;
+};
+
+export type PairingCode = {
+  code: string;
+  // Epoch millis
+  expires_at: number;
+};
+
+// Payload of the "lan-pairing-fingerprint" event, shown on both devices
+export type PairingFingerprint = {
+  // The other device's name, or its address on the device that connected
+  device: string;
+  fingerprint: string;
+  // Answer with clipboardDb.lanConfirmPairing
+  needs_confirmation: boolean;
+};
+
+export type LanStatus = {
+  running: boolean;
+  name: string | null;
+  public_key: string | null;
+  port: number | null;
+  // Why mDNS discovery isn't running; paired peers can still be reached
+  discovery_error: string | null;
+};
+
+export type MutationKind =
+  | "delete"
+  | "reorder"
+  | "favorite"
+  | "split"
+  | "merge";
+
+export type UndoState = {
+  undo: MutationKind | null;
+  redo: MutationKind | null;
+};
+
+export type HistoryOrder = "manual" | "recent" | "frecency" | "most_used";
+
+export type FuzzySearchResult = {
+  item: ClipboardItem;
+  score: number;
+  // Matched char offsets into text_content, or ocr_text for images
+  indices: number[];
+};
+
+export type SimilarImage = {
+  item: ClipboardItem;
+  distance: number;
+};
+
+export type CopyEventKind = "captured" | "restored" | "pasted";
+
+export type CopyEvent = {
+  id: number;
+  item_id: number;
+  kind: CopyEventKind;
+  source_app: string | null;
+  selection: string | null;
+  created_at: string;
+  content_type: "text" | "image";
+  preview: string | null;
+};
+
+export type CopyTimelineFilter = {
+  item_id?: number;
+  kind?: CopyEventKind;
+  since?: number;
+  until?: number;
 };
 
 export type ClipboardError = {
@@ -0,0 +1,23 @@
+export type StatsPeriod = "day" | "week";
+
+export type UsageStats = {
+  total_items: number;
+  favorite_items: number;
+  items_per_period: { period: string; count: number }[];
+  top_reused: {
+    id: number;
+    content_type: string;
+    content_kind: string | null;
+    preview: string | null;
+    copy_count: number;
+    is_favorite: boolean;
+  }[];
+  type_breakdown: {
+    content_type: string;
+    content_kind: string | null;
+    count: number;
+  }[];
+  storage: { image_bytes: number; text_bytes: number };
+  source_apps: { source_app: string; items: number; copies: number }[];
+  avg_time_to_reuse_ms: number | null;
+};